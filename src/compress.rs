@@ -0,0 +1,116 @@
+use std::io::Write;
+
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use hyper::{
+    header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, VARY},
+    Body, Request, Response,
+};
+
+/// Smaller bodies are not worth the compression overhead
+const MIN_COMPRESS_LEN: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// Choose the encoding from the `Accept-Encoding` header of the request, preferring gzip
+    pub fn from_request(req: &Request<Body>) -> Option<Self> {
+        let accepted: Vec<_> = req
+            .headers()
+            .get(ACCEPT_ENCODING)?
+            .to_str()
+            .ok()?
+            .split(',')
+            .filter_map(|e| {
+                let mut parts = e.split(';').map(str::trim);
+                let name = parts.next()?;
+                let quality = parts
+                    .find_map(|p| p.strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (quality > 0.0).then_some(name)
+            })
+            .collect();
+        if accepted.contains(&"gzip") {
+            Some(Encoding::Gzip)
+        } else if accepted.contains(&"deflate") {
+            Some(Encoding::Deflate)
+        } else {
+            None
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn encode(&self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Compress html and text responses, other content types like binary txs or images are returned
+/// unchanged
+pub async fn compress(encoding: Option<Encoding>, resp: Response<Body>) -> Response<Body> {
+    let compressible = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|c| c.to_str().ok())
+        .map(|c| c.starts_with("text/html") || c.starts_with("text/plain"))
+        .unwrap_or(false);
+    if !compressible || resp.headers().contains_key(CONTENT_ENCODING) {
+        return resp;
+    }
+
+    let (mut parts, body) = resp.into_parts();
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("Accept-Encoding"));
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::warn!("cannot read body to compress: {e:?}");
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    let encoding = match encoding {
+        Some(encoding) if bytes.len() >= MIN_COMPRESS_LEN => encoding,
+        _ => return Response::from_parts(parts, bytes.into()),
+    };
+
+    match encoding.encode(&bytes) {
+        Ok(compressed) => {
+            parts.headers.insert(
+                CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.as_str()),
+            );
+            parts.headers.remove(CONTENT_LENGTH);
+            Response::from_parts(parts, compressed.into())
+        }
+        Err(e) => {
+            log::warn!("cannot compress body: {e:?}");
+            Response::from_parts(parts, bytes.into())
+        }
+    }
+}
@@ -43,6 +43,9 @@ pub enum Error {
     #[error(transparent)]
     BitcoinAddress(#[from] bitcoin::address::ParseError),
 
+    #[error(transparent)]
+    Bip32(#[from] bitcoin::bip32::Error),
+
     #[error(transparent)]
     Bmp(#[from] qr_code::bmp_monochrome::BmpError),
 
@@ -52,6 +55,9 @@ pub enum Error {
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
+    #[error(transparent)]
+    Fmt(#[from] std::fmt::Error),
+
     #[error(transparent)]
     Rocksdb(#[from] rocksdb::Error),
 
@@ -64,9 +70,6 @@ pub enum Error {
     #[error(transparent)]
     ZmqSubscribe(#[from] async_zmq::SubscribeError),
 
-    #[error(transparent)]
-    ExtractTx(#[from] bitcoin::psbt::ExtractTxError),
-
     #[error("Bitcoin core RPC chaininfo failed status_code:{0}")]
     RpcChainInfo(StatusCode),
 
@@ -76,8 +79,8 @@ pub enum Error {
     #[error("Bitcoin core RPC tx json failed. txid:{1} status_code:{0}")]
     RpcTxJson(StatusCode, Txid),
 
-    #[error("Bitcoin core RPC txout failed. txid:{1} vout:{2} status_code:{0}")]
-    RpcTxOut(StatusCode, Txid, u32),
+    #[error("Bitcoin core RPC txout failed. txid:{1} status_code:{0}")]
+    RpcTxOut(StatusCode, Txid),
 
     #[error("Bitcoin core RPC block json failed. block_hash:{0} status_code:{0}")]
     RpcBlockJson(StatusCode, BlockHash),
@@ -100,6 +103,9 @@ pub enum Error {
     #[error("Bitcoin core RPC mempool content failed. status_code:{0}")]
     RpcMempoolContent(StatusCode),
 
+    #[error("Bitcoin core RPC call timed out")]
+    RpcTimeout,
+
     #[error("Invalid page number")]
     InvalidPageNumber,
 
@@ -109,6 +115,9 @@ pub enum Error {
     #[error("Page not found")]
     NotFound,
 
+    #[error("The original transaction {0} is not available, the node dropped it from the mempool after the replacement")]
+    OriginalTxNotAvailable(Txid),
+
     #[error("Header not found {0}")]
     HeaderNotFound(BlockHash),
 
@@ -127,18 +136,76 @@ pub enum Error {
     #[error("address and fbbe doesn't have the same network. fbbe:{fbbe} address:{address}")]
     AddressWrongNetwork { fbbe: Network, address: Address },
 
-    #[error("Network '{0}' not parsed, valid values are: bitcoin, mainnet, main | testnet, test | signet | regtest")]
+    #[error("Network '{0}' not parsed, valid values are: bitcoin, mainnet, main | testnet, test | testnet4 | signet | regtest")]
     NetworkParseError(String),
 
+    #[error("'{0}' is not in the form network=address, eg. testnet=127.0.0.1:18332")]
+    NetworkAddrParseError(String),
+
     #[error("Height not found")]
     HeightNotFound,
+
+    #[error("Search by hash prefix requires at least {0} hex chars")]
+    SearchPrefixTooShort(usize),
+
+    #[error("Search prefix {0} matches more than one block or transaction")]
+    SearchPrefixAmbiguous(String),
+
+    #[error("Bitcoind RPC credentials are needed (`--rpc-user-password`, `--rpc-user` and `--rpc-password` or `--rpc-cookie-file`)")]
+    RpcAuthMissing,
+
+    #[error("Bitcoin core RPC refused the provided credentials")]
+    RpcUnauthorized,
+
+    #[error("Bitcoin core RPC {0} failed: {1}")]
+    RpcCallFailed(&'static str, String),
+
+    #[error("Transaction rejected by the node: {0}")]
+    TxRejected(String),
+
+    #[error("Missing or wrong admin token")]
+    AdminUnauthorized,
+
+    #[error("This instance has no address index (`--addr-index-path`), needed for {0}")]
+    AddressIndexMissing(&'static str),
+
+    #[error("This instance doesn't index OP_RETURN data (`--index-op-return`)")]
+    OpReturnIndexMissing,
+
+    #[error("The extended key is not for network {0}")]
+    XpubWrongNetwork(Network),
+
+    #[error("The lightning invoice is not for network {0}")]
+    InvoiceWrongNetwork(Network),
+
+    #[error("Rate limiting on a unix socket requires `--trust-forwarded-for`, connections have no client ip")]
+    RateLimitWithoutClientIp,
+
+    #[error("The root network {0} is not served by this process, add it with `--network` or `--other-bitcoind-addr`")]
+    RootNetworkNotServed(Network),
 }
 
 impl From<Error> for StatusCode {
     fn from(e: Error) -> Self {
         match e {
-            Error::BadRequest => StatusCode::BAD_REQUEST,
-            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::BadRequest
+            | Error::InvalidPageNumber
+            | Error::AddressWrongNetwork { .. }
+            | Error::SearchPrefixTooShort(_)
+            | Error::SearchPrefixAmbiguous(_)
+            | Error::TxRejected(_)
+            | Error::Bip32(_)
+            | Error::XpubWrongNetwork(_)
+            | Error::InvoiceWrongNetwork(_) => StatusCode::BAD_REQUEST,
+            Error::AddressIndexMissing(_) | Error::OpReturnIndexMissing => {
+                StatusCode::NOT_IMPLEMENTED
+            }
+            Error::NotFound | Error::HeightNotFound | Error::OriginalTxNotAvailable(_) => {
+                StatusCode::NOT_FOUND
+            }
+            Error::AdminUnauthorized => StatusCode::UNAUTHORIZED,
+            Error::ContentTypeUnsupported(..) => StatusCode::NOT_ACCEPTABLE,
+            Error::RpcTimeout => StatusCode::GATEWAY_TIMEOUT,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
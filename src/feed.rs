@@ -0,0 +1,42 @@
+use std::fmt::Write;
+
+use crate::{error::Error, network, state::SharedState, NetworkExt};
+
+/// Number of the most recent blocks included in the feed
+const FEED_BLOCKS: u32 = 10;
+
+/// Atom feed of the most recent blocks, links are absolute urls starting with `origin`, eg.
+/// `https://fbbe.info`
+pub async fn page(state: &SharedState, origin: &str) -> Result<String, Error> {
+    let tip = state.chain_info.lock().await.best_block_hash;
+    let tip_height_time = state.height_time(tip).await?;
+    let base = format!("{origin}{}", network().as_url_path());
+
+    let mut feed = String::new();
+    writeln!(feed, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(feed, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#)?;
+    writeln!(feed, "<title>FBBE - {:?} blocks</title>", network())?;
+    writeln!(feed, "<id>{base}feed.xml</id>")?;
+    writeln!(feed, r#"<link rel="self" href="{base}feed.xml"/>"#)?;
+    writeln!(feed, "<updated>{}</updated>", tip_height_time.rfc3339())?;
+
+    let from = tip_height_time.height.saturating_sub(FEED_BLOCKS - 1);
+    for height in (from..=tip_height_time.height).rev() {
+        let block_hash = match state.height_to_hash(height).await {
+            Some(block_hash) => block_hash,
+            None => break,
+        };
+        let height_time = state.height_time(block_hash).await?;
+        let link = format!("{base}b/{block_hash}");
+        writeln!(feed, "<entry>")?;
+        writeln!(feed, "<title>Block {height}</title>")?;
+        writeln!(feed, "<id>{link}</id>")?;
+        writeln!(feed, r#"<link href="{link}"/>"#)?;
+        writeln!(feed, "<updated>{}</updated>", height_time.rfc3339())?;
+        writeln!(feed, "<summary>{block_hash}</summary>")?;
+        writeln!(feed, "</entry>")?;
+    }
+    writeln!(feed, "</feed>")?;
+
+    Ok(feed)
+}
@@ -1,19 +1,187 @@
 use bitcoin::Network;
-use once_cell::sync::OnceCell;
-use std::{collections::HashSet, net::SocketAddr};
+use futures::Future;
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::{proto::MetricFamily, Registry};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    net::SocketAddr,
+    path::PathBuf,
+    time::Duration,
+};
+use tokio::sync::watch;
 
-use crate::{create_local_socket, Arguments};
+use crate::{create_local_socket, rate_limit::RateLimiter, Arguments};
 
 static NETWORK: OnceCell<Network> = OnceCell::new();
 
+tokio::task_local! {
+    /// Set for requests and background tasks of the networks served by this process
+    static SCOPED_NETWORK: Network;
+}
+
+/// The network of the current request or background task, panics outside of [`with_network`]
 pub(crate) fn network() -> Network {
+    SCOPED_NETWORK
+        .try_with(|network| *network)
+        .expect("network() called outside of a network scope")
+}
+
+/// The network given with `--network`
+pub(crate) fn main_network() -> Network {
     *NETWORK.get().expect("must be initialized")
 }
 
-static BITCOIND_ADDR: OnceCell<SocketAddr> = OnceCell::new();
+/// Run `f` with `network()` returning `network`
+pub(crate) async fn with_network<F: Future>(network: Network, f: F) -> F::Output {
+    SCOPED_NETWORK.scope(network, f).await
+}
+
+/// Like `tokio::spawn`, but the spawned task keeps the `network()` of the caller
+pub(crate) fn spawn<F>(f: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(SCOPED_NETWORK.scope(network(), f))
+}
+
+/// Set when the process is stopping, background loops check it to stop at a consistent point
+static SHUTDOWN: Lazy<watch::Sender<bool>> = Lazy::new(|| watch::channel(false).0);
 
+/// Number of live [`WriterGuard`]s
+static WRITERS: Lazy<watch::Sender<usize>> = Lazy::new(|| watch::channel(0).0);
+
+pub(crate) fn shutting_down() -> bool {
+    *SHUTDOWN.borrow()
+}
+
+/// Completes when the process is stopping
+pub(crate) async fn shutdown_requested() {
+    let mut shutdown = SHUTDOWN.subscribe();
+    let _ = shutdown.wait_for(|s| *s).await;
+}
+
+/// Sleep for `duration`, returns `false` if interrupted because the process is stopping
+pub(crate) async fn sleep_unless_shutdown(duration: Duration) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => true,
+        _ = shutdown_requested() => false,
+    }
+}
+
+/// Held by tasks writing to the database, the process waits they are dropped before exiting
+pub(crate) struct WriterGuard(());
+
+impl WriterGuard {
+    pub(crate) fn new() -> Self {
+        WRITERS.send_modify(|n| *n += 1);
+        WriterGuard(())
+    }
+}
+
+impl Drop for WriterGuard {
+    fn drop(&mut self) {
+        WRITERS.send_modify(|n| *n -= 1);
+    }
+}
+
+/// Ask the background loops to stop and wait, at most `max_wait`, the writers to finish
+pub(crate) async fn shutdown(max_wait: Duration) {
+    SHUTDOWN.send_replace(true);
+    let mut writers = WRITERS.subscribe();
+    if tokio::time::timeout(max_wait, writers.wait_for(|n| *n == 0))
+        .await
+        .is_err()
+    {
+        log::warn!("writers still running after {max_wait:?}, exiting anyway");
+    }
+}
+
+static BITCOIND_ADDRS: OnceCell<HashMap<Network, SocketAddr>> = OnceCell::new();
+
+/// The address of the bitcoind node of the current `network()`
 pub(crate) fn bitcoind_addr() -> &'static SocketAddr {
-    BITCOIND_ADDR.get().expect("must be initialized")
+    BITCOIND_ADDRS
+        .get()
+        .expect("must be initialized")
+        .get(&network())
+        .expect("every served network has a bitcoind")
+}
+
+static SERVED_NETWORKS: OnceCell<Vec<Network>> = OnceCell::new();
+
+/// The networks served by this process, the main network first
+pub(crate) fn served_networks() -> &'static [Network] {
+    SERVED_NETWORKS.get().expect("must be initialized")
+}
+
+static REGISTRIES: OnceCell<HashMap<Network, Registry>> = OnceCell::new();
+
+/// Where the metrics of the current `network()` are registered. The main network uses the default
+/// registry, the other networks have their own with a `network` label.
+pub(crate) fn registry() -> &'static Registry {
+    match REGISTRIES
+        .get()
+        .expect("must be initialized")
+        .get(&network())
+    {
+        Some(registry) => registry,
+        None => prometheus::default_registry(),
+    }
+}
+
+/// The metrics of every served network, the ones with the same name merged in a single family
+pub(crate) fn gather_metrics() -> Vec<MetricFamily> {
+    let mut families = prometheus::gather();
+    for registry in REGISTRIES.get().expect("must be initialized").values() {
+        for mut family in registry.gather() {
+            match families
+                .iter_mut()
+                .find(|f| f.get_name() == family.get_name())
+            {
+                Some(existing) => {
+                    for metric in family.take_metric() {
+                        existing.mut_metric().push(metric);
+                    }
+                }
+                None => families.push(family),
+            }
+        }
+    }
+    families
+}
+
+/// Credentials for the bitcoind JSON-RPC interface, needed only by the calls REST doesn't offer
+pub(crate) enum RpcAuth {
+    /// In the form `user:password`
+    UserPassword(String),
+
+    /// Read at every call since the node rewrites it at every restart
+    CookieFile(PathBuf),
+}
+
+static RPC_AUTH: OnceCell<Option<RpcAuth>> = OnceCell::new();
+
+/// Credentials are given only for the node of the main network
+pub(crate) fn rpc_auth() -> Option<&'static RpcAuth> {
+    if network() != main_network() {
+        return None;
+    }
+    RPC_AUTH.get().expect("must be initialized").as_ref()
+}
+
+static RPC_TIMEOUT: OnceCell<Duration> = OnceCell::new();
+
+/// How long to wait for bitcoind to answer a call
+pub(crate) fn rpc_timeout() -> Duration {
+    *RPC_TIMEOUT.get().expect("must be initialized")
+}
+
+static RPC_RETRIES: OnceCell<u32> = OnceCell::new();
+
+/// How many times a call to bitcoind answering 503 is retried
+pub(crate) fn rpc_retries() -> u32 {
+    *RPC_RETRIES.get().expect("must be initialized")
 }
 
 static NETWORKS: OnceCell<Vec<Network>> = OnceCell::new();
@@ -22,6 +190,40 @@ pub(crate) fn networks() -> &'static [Network] {
     NETWORKS.get().expect("must be initialized")
 }
 
+static ROOT_NETWORK: OnceCell<Network> = OnceCell::new();
+
+/// The network whose urls have no network path prefix
+pub(crate) fn root_network() -> Network {
+    *ROOT_NETWORK.get().expect("must be initialized")
+}
+
+/// Operator customizations of the html pages footer
+pub(crate) struct Footer {
+    pub notice: Option<String>,
+    pub notice_url: Option<String>,
+    pub upstream_links: bool,
+}
+
+static FOOTER: OnceCell<Footer> = OnceCell::new();
+
+pub(crate) fn footer() -> &'static Footer {
+    FOOTER.get().expect("must be initialized")
+}
+
+static ROWS_PER_PAGE: OnceCell<usize> = OnceCell::new();
+
+/// Inputs and outputs in a page of a transaction, transactions in a page of a block
+pub(crate) fn rows_per_page() -> usize {
+    *ROWS_PER_PAGE.get().expect("must be initialized")
+}
+
+static RATE_LIMITER: OnceCell<Option<RateLimiter>> = OnceCell::new();
+
+/// Shared by all the served networks, `None` if requests are not limited
+pub(crate) fn rate_limiter() -> Option<&'static RateLimiter> {
+    RATE_LIMITER.get().expect("must be initialized").as_ref()
+}
+
 pub(crate) fn init_globals(args: &mut Arguments) {
     NETWORK
         .set(
@@ -33,19 +235,24 @@ pub(crate) fn init_globals(args: &mut Arguments) {
         .expect("static global must be empty here");
 
     let mut networks = HashSet::new();
-    networks.insert(network());
+    networks.insert(main_network());
     networks.extend(args.other_network.iter());
+    networks.extend(args.other_bitcoind_addr.iter().map(|e| e.network));
     let networks: Vec<_> = networks.into_iter().collect();
     log::info!("networks {:?}", networks);
 
     NETWORKS
         .set(networks)
         .expect("static global must be empty here");
+    ROOT_NETWORK
+        .set(args.root_network.unwrap_or(Network::Bitcoin))
+        .expect("static global must be empty here");
 
     let bitcoind_addr = args.bitcoind_addr.take().unwrap_or_else(|| {
-        let port = match network() {
+        let port = match main_network() {
             Network::Bitcoin => 8332,
             Network::Testnet => 18332,
+            Network::Testnet4 => 48332,
             Network::Signet => 38332,
             Network::Regtest => 18443,
             _ => panic!("non existing network"),
@@ -53,7 +260,74 @@ pub(crate) fn init_globals(args: &mut Arguments) {
         create_local_socket(port)
     });
     log::info!("bitcoind_addr {}", bitcoind_addr);
-    BITCOIND_ADDR
-        .set(bitcoind_addr)
+
+    let mut served_networks = vec![main_network()];
+    let mut bitcoind_addrs = HashMap::new();
+    bitcoind_addrs.insert(main_network(), bitcoind_addr);
+    for other in args.other_bitcoind_addr.iter() {
+        if let Entry::Vacant(entry) = bitcoind_addrs.entry(other.network) {
+            log::info!(
+                "serving also {} with bitcoind_addr {}",
+                other.network,
+                other.addr
+            );
+            entry.insert(other.addr);
+            served_networks.push(other.network);
+        }
+    }
+    BITCOIND_ADDRS
+        .set(bitcoind_addrs)
+        .expect("static global must be empty here");
+    let registries = served_networks[1..]
+        .iter()
+        .map(|network| {
+            let labels = HashMap::from([("network".to_string(), network.to_string())]);
+            let registry = Registry::new_custom(None, Some(labels)).expect("valid label");
+            (*network, registry)
+        })
+        .collect();
+    REGISTRIES
+        .set(registries)
+        .expect("static global must be empty here");
+    SERVED_NETWORKS
+        .set(served_networks)
+        .expect("static global must be empty here");
+
+    let user_password = args.rpc_user_password.take().or_else(|| {
+        let user = args.rpc_user.take()?;
+        let password = args.rpc_password.take()?;
+        Some(format!("{user}:{password}"))
+    });
+    let rpc_auth = match (user_password, args.rpc_cookie_file.take()) {
+        (Some(user_password), _) => Some(RpcAuth::UserPassword(user_password)),
+        (None, Some(cookie_file)) => Some(RpcAuth::CookieFile(cookie_file)),
+        (None, None) => None,
+    };
+    RPC_AUTH
+        .set(rpc_auth)
+        .expect("static global must be empty here");
+
+    RPC_TIMEOUT
+        .set(Duration::from_secs(args.rpc_timeout_secs))
+        .expect("static global must be empty here");
+    RPC_RETRIES
+        .set(args.rpc_retries)
+        .expect("static global must be empty here");
+
+    FOOTER
+        .set(Footer {
+            notice: args.footer_notice.clone(),
+            notice_url: args.footer_notice_url.as_ref().map(|u| u.to_string()),
+            upstream_links: !args.no_upstream_links,
+        })
+        .expect("static global must be empty here");
+    ROWS_PER_PAGE
+        .set(args.rows_per_page)
+        .expect("static global must be empty here");
+    RATE_LIMITER
+        .set(
+            args.rate_limit_per_sec
+                .map(|per_sec| RateLimiter::new(per_sec, args.rate_limit_burst)),
+        )
         .expect("static global must be empty here");
 }
@@ -0,0 +1,255 @@
+//! Decode BOLT11 lightning invoices, enough to show what is being paid.
+//!
+//! The signature is not verified and the payee is known only if the invoice contains it
+//! explicitly, recovering it from the signature is not supported.
+
+use std::{fmt::Display, str::FromStr};
+
+use bitcoin::bech32::{self, primitives::decode::CheckedHrpstring, Checksum, Fe32};
+use bitcoin::Network;
+
+use crate::network;
+
+/// The bech32 checksum without the 1023 characters limit of addresses, since invoices are not
+/// limited in length
+enum Bolt11 {}
+
+impl Checksum for Bolt11 {
+    type MidstateRepr = <bech32::Bech32 as Checksum>::MidstateRepr;
+    const CODE_LENGTH: usize = usize::MAX;
+    const CHECKSUM_LENGTH: usize = bech32::Bech32::CHECKSUM_LENGTH;
+    const GENERATOR_SH: [Self::MidstateRepr; 5] = bech32::Bech32::GENERATOR_SH;
+    const TARGET_RESIDUE: Self::MidstateRepr = bech32::Bech32::TARGET_RESIDUE;
+}
+
+/// 5 bits words of the signature and the recovery id, at the end of the data part
+const SIGNATURE_WORDS: usize = 104;
+
+/// 5 bits words of the timestamp, at the start of the data part
+const TIMESTAMP_WORDS: usize = 7;
+
+/// Expiry in seconds when the invoice doesn't specify it
+const DEFAULT_EXPIRY: u64 = 3600;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InvoiceError {
+    /// Invalid characters or wrong checksum, case is ignored
+    Bech32,
+
+    /// The human readable part doesn't start with `ln` followed by a known currency
+    Currency,
+    Amount,
+    Truncated,
+    MissingPaymentHash,
+}
+
+/// A lightning invoice
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    /// The currency prefix, like `bc` for mainnet
+    pub currency: &'static str,
+    pub amount_msat: Option<u64>,
+
+    /// Unix time of creation
+    pub timestamp: u64,
+
+    /// Seconds after `timestamp` when the invoice expires
+    pub expiry: u64,
+    pub payment_hash: [u8; 32],
+    pub description: Option<String>,
+    pub description_hash: Option<[u8; 32]>,
+    pub payee: Option<[u8; 33]>,
+
+    /// The invoice as given, lowercase
+    pub original: String,
+}
+
+impl FromStr for Invoice {
+    type Err = InvoiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let original = s.to_ascii_lowercase();
+        let checked =
+            CheckedHrpstring::new::<Bolt11>(&original).map_err(|_| InvoiceError::Bech32)?;
+        let hrp = checked.hrp();
+        let data: Vec<Fe32> = checked
+            .data_part_ascii_no_checksum()
+            .iter()
+            .map(|c| Fe32::from_char_unchecked(*c))
+            .collect();
+
+        let rest = hrp
+            .as_str()
+            .strip_prefix("ln")
+            .ok_or(InvoiceError::Currency)?;
+        // longest first, since `bc` is a prefix of `bcrt`
+        let currency = ["bcrt", "bc", "tbs", "tb"]
+            .into_iter()
+            .find(|c| rest.starts_with(c))
+            .ok_or(InvoiceError::Currency)?;
+        let amount_msat = parse_amount(&rest[currency.len()..])?;
+
+        if data.len() < TIMESTAMP_WORDS + SIGNATURE_WORDS {
+            return Err(InvoiceError::Truncated);
+        }
+        let timestamp = words_to_int(&data[..TIMESTAMP_WORDS]);
+        let mut fields = &data[TIMESTAMP_WORDS..data.len() - SIGNATURE_WORDS];
+
+        let mut invoice = Invoice {
+            currency,
+            amount_msat,
+            timestamp,
+            expiry: DEFAULT_EXPIRY,
+            payment_hash: [0u8; 32],
+            description: None,
+            description_hash: None,
+            payee: None,
+            original: original.clone(),
+        };
+        let mut payment_hash = None;
+        while !fields.is_empty() {
+            if fields.len() < 3 {
+                return Err(InvoiceError::Truncated);
+            }
+            let len = words_to_int(&fields[1..3]) as usize;
+            let value = fields.get(3..3 + len).ok_or(InvoiceError::Truncated)?;
+
+            // unknown fields and the known ones with unexpected length must be skipped
+            match (fields[0], len) {
+                (Fe32::P, 52) => payment_hash = words_to_bytes(value).try_into().ok(),
+                (Fe32::D, _) => {
+                    invoice.description = String::from_utf8(words_to_bytes(value)).ok();
+                }
+                (Fe32::H, 52) => invoice.description_hash = words_to_bytes(value).try_into().ok(),
+                (Fe32::X, _) if len <= 12 => invoice.expiry = words_to_int(value),
+                (Fe32::N, 53) => invoice.payee = words_to_bytes(value).try_into().ok(),
+                _ => (),
+            }
+            fields = &fields[3 + len..];
+        }
+        invoice.payment_hash = payment_hash.ok_or(InvoiceError::MissingPaymentHash)?;
+
+        Ok(invoice)
+    }
+}
+
+impl Display for Invoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.original)
+    }
+}
+
+impl Invoice {
+    /// Whether the invoice is meant for the network of this instance
+    pub fn is_current_network(&self) -> bool {
+        matches!(
+            (self.currency, network()),
+            ("bc", Network::Bitcoin)
+                | ("tb", Network::Testnet | Network::Testnet4)
+                | ("tbs", Network::Signet)
+                | ("bcrt", Network::Regtest)
+        )
+    }
+}
+
+/// The amount in the human readable part, like `2500u`, converted in millisatoshi
+fn parse_amount(amount: &str) -> Result<Option<u64>, InvoiceError> {
+    if amount.is_empty() {
+        return Ok(None);
+    }
+    let (digits, msat_per_unit) = match amount.as_bytes()[amount.len() - 1] {
+        b'm' => (&amount[..amount.len() - 1], 100_000_000),
+        b'u' => (&amount[..amount.len() - 1], 100_000),
+        b'n' => (&amount[..amount.len() - 1], 100),
+        b'p' => (&amount[..amount.len() - 1], 0),
+        _ => (amount, 100_000_000_000),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(InvoiceError::Amount);
+    }
+    let value: u64 = digits.parse().map_err(|_| InvoiceError::Amount)?;
+    match msat_per_unit {
+        // a pico-bitcoin is a tenth of millisatoshi
+        0 if value % 10 == 0 => Ok(Some(value / 10)),
+        0 => Err(InvoiceError::Amount),
+        _ => value
+            .checked_mul(msat_per_unit)
+            .map(Some)
+            .ok_or(InvoiceError::Amount),
+    }
+}
+
+/// Big endian integer of the 5 bits `words`, which must be at most 12
+fn words_to_int(words: &[Fe32]) -> u64 {
+    words.iter().fold(0, |acc, w| (acc << 5) | w.to_u8() as u64)
+}
+
+/// Regroup the 5 bits `words` in bytes, the trailing bits not filling a byte are dropped
+fn words_to_bytes(words: &[Fe32]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(words.len() * 5 / 8);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for word in words {
+        acc = (acc << 5) | word.to_u8() as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((acc >> bits) as u8);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use bitcoin::hex::DisplayHex;
+
+    use super::{parse_amount, Invoice, InvoiceError};
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount(""), Ok(None));
+        assert_eq!(parse_amount("2500u"), Ok(Some(250_000_000)));
+        assert_eq!(parse_amount("20m"), Ok(Some(2_000_000_000)));
+        assert_eq!(parse_amount("10p"), Ok(Some(1)));
+        assert_eq!(parse_amount("1p"), Err(InvoiceError::Amount));
+        assert_eq!(parse_amount("u"), Err(InvoiceError::Amount));
+    }
+
+    #[test]
+    fn test_invoice() {
+        // BOLT11 examples
+        let invoice: Invoice = "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu9qrsgquk0rl77nj30yxdy8j9vdx85fkpmdla2087ne0xh8nhedh8w27kyke0lp53ut353s06fv3qfegext0eh0ymjpf39tuven09sam30g4vgpfna3rh".parse().unwrap();
+        assert_eq!(invoice.currency, "bc");
+        assert_eq!(invoice.amount_msat, Some(250_000_000));
+        assert_eq!(invoice.timestamp, 1496314658);
+        assert_eq!(invoice.expiry, 60);
+        assert_eq!(invoice.description.as_deref(), Some("1 cup coffee"));
+        assert_eq!(
+            invoice.payment_hash.to_lower_hex_string(),
+            "0001020304050607080900010203040506070809000102030405060708090102"
+        );
+
+        let invoice: Invoice = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql".parse().unwrap();
+        assert_eq!(invoice.amount_msat, None);
+        assert_eq!(invoice.expiry, 3600);
+        let upper: Invoice = invoice.original.to_uppercase().parse().unwrap();
+        assert_eq!(upper.payment_hash, invoice.payment_hash);
+        assert_eq!(
+            invoice.description.as_deref(),
+            Some("Please consider supporting this project")
+        );
+
+        // last character changed
+        assert_eq!(
+            "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyqq".parse::<Invoice>().unwrap_err(),
+            InvoiceError::Bech32
+        );
+        assert_eq!(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+                .parse::<Invoice>()
+                .unwrap_err(),
+            InvoiceError::Currency
+        );
+    }
+}
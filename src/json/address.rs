@@ -0,0 +1,75 @@
+use bitcoin::{BlockHash, OutPoint, ScriptBuf, Txid};
+use serde::Serialize;
+
+use crate::threads::index_addresses::AddressSeen;
+
+#[derive(Serialize)]
+pub struct Address {
+    pub address: String,
+    pub address_type: Option<String>,
+    pub script_pubkey: ScriptBuf,
+
+    /// Empty if the address index is not enabled
+    pub seen: Vec<Seen>,
+}
+
+#[derive(Serialize)]
+pub struct Seen {
+    pub funding: Funding,
+
+    /// Height of the spending block, present even when `spending` is not looked up
+    pub spent_height: Option<u32>,
+    pub spending: Option<Spending>,
+}
+
+#[derive(Serialize)]
+pub struct Funding {
+    pub outpoint: OutPoint,
+    pub block_hash: BlockHash,
+    pub height: u32,
+    pub time: u32,
+    pub value: u64,
+}
+
+#[derive(Serialize)]
+pub struct Spending {
+    pub txid: Txid,
+    pub vin: usize,
+    pub block_hash: BlockHash,
+    pub height: u32,
+    pub time: u32,
+}
+
+impl Address {
+    pub fn new(address: &bitcoin::Address, address_seen: &[AddressSeen]) -> Self {
+        Address {
+            address: address.to_string(),
+            address_type: address.address_type().map(|t| t.to_string()),
+            script_pubkey: address.script_pubkey(),
+            seen: address_seen.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<&AddressSeen> for Seen {
+    fn from(value: &AddressSeen) -> Self {
+        let funding = &value.funding;
+        Seen {
+            funding: Funding {
+                outpoint: funding.out_point,
+                block_hash: funding.block_hash,
+                height: funding.height_time.height,
+                time: funding.height_time.time,
+                value: funding.value,
+            },
+            spent_height: value.spent_height,
+            spending: value.spending.as_ref().map(|s| Spending {
+                txid: s.txid,
+                vin: s.vin,
+                block_hash: s.block_hash,
+                height: s.height_time.height,
+                time: s.height_time.time,
+            }),
+        }
+    }
+}
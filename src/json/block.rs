@@ -0,0 +1,73 @@
+use bitcoin::{block::Header, consensus::encode::serialize_hex, BlockHash, TxMerkleNode, Txid};
+use serde::Serialize;
+
+use crate::rpc::block::BlockNoTxDetails;
+
+#[derive(Serialize)]
+pub struct Block<'a> {
+    pub hash: BlockHash,
+    pub height: u32,
+    pub version: u32,
+    pub merkle_root: &'a str,
+    pub time: u32,
+    pub previous_block_hash: Option<&'a str>,
+    pub next_block_hash: Option<&'a str>,
+    pub size: usize,
+    pub weight: usize,
+    pub bits: &'a str,
+    pub difficulty: f64,
+    pub nonce: u32,
+    pub tx: &'a [Txid],
+
+    /// Changes with every new block, thus responses including it are not cached
+    pub confirmations: u32,
+}
+
+impl<'a> Block<'a> {
+    pub fn new(block: &'a BlockNoTxDetails, tip_height: u32) -> Self {
+        Block {
+            hash: block.hash,
+            height: block.height,
+            version: block.version,
+            merkle_root: &block.merkleroot,
+            time: block.time,
+            previous_block_hash: block.previousblockhash.as_deref(),
+            next_block_hash: block.nextblockhash.as_deref(),
+            size: block.size,
+            weight: block.weight,
+            bits: &block.bits,
+            difficulty: block.difficulty,
+            nonce: block.nonce,
+            tx: &block.tx,
+            confirmations: tip_height.saturating_sub(block.height) + 1,
+        }
+    }
+}
+
+/// The 80 bytes header of a block, parsed and hex encoded
+#[derive(Serialize)]
+pub struct BlockHeader {
+    pub hash: BlockHash,
+    pub version: i32,
+    pub merkle_root: TxMerkleNode,
+    pub time: u32,
+    pub previous_block_hash: BlockHash,
+    pub bits: String,
+    pub nonce: u32,
+    pub hex: String,
+}
+
+impl From<&Header> for BlockHeader {
+    fn from(header: &Header) -> Self {
+        BlockHeader {
+            hash: header.block_hash(),
+            version: header.version.to_consensus(),
+            merkle_root: header.merkle_root,
+            time: header.time,
+            previous_block_hash: header.prev_blockhash,
+            bits: format!("{:08x}", header.bits.to_consensus()),
+            nonce: header.nonce,
+            hex: serialize_hex(header),
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use serde::Serialize;
+
+/// Current state of the in-memory caches, returned by the admin endpoint
+#[derive(Serialize)]
+pub struct Cache {
+    pub tx_cache_len: usize,
+
+    /// Average serialized size of the cached transactions
+    pub tx_cache_avg: f64,
+    pub tx_cache_full: bool,
+    pub txid_blockhash_len: usize,
+    pub txid_blockhash_cap: usize,
+}
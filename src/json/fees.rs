@@ -0,0 +1,48 @@
+use serde::Serialize;
+
+use crate::{state::BlockTemplate, threads::update_mempool_info::WeightFee};
+
+/// Fee rates in sat/vB, with the field names of the mempool.space `/api/v1/fees/recommended`
+/// endpoint so that wallets supporting it can use this one
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendedFees {
+    /// To be included in the next block
+    pub fastest_fee: u64,
+
+    /// Within 3 blocks
+    pub half_hour_fee: u64,
+
+    /// Within 6 blocks
+    pub hour_fee: u64,
+
+    /// The lowest rate in the mempool
+    pub economy_fee: u64,
+}
+
+impl RecommendedFees {
+    /// Rates are rounded up and at least 1 sat/vB. When the mempool doesn't fill the blocks of a
+    /// target, the lowest rate in the mempool is enough to be included.
+    pub fn new(template: &BlockTemplate) -> Self {
+        let round = |wf: &WeightFee| (wf.rate_sat_over_vb().ceil() as u64).max(1);
+        let economy_fee = template
+            .by_rate
+            .last()
+            .map(|e| round(&(&e.wf).into()))
+            .unwrap_or(1);
+        // estimates for the `FEE_ESTIMATE_TARGETS` of 1, 3 and 6 blocks
+        let [fastest, half_hour, hour] = &template.fee_estimates;
+        let estimate = |e: &Option<WeightFee>| e.as_ref().map_or(economy_fee, round);
+
+        // a longer target never needs a higher rate than a shorter one
+        let hour_fee = estimate(hour).max(economy_fee);
+        let half_hour_fee = estimate(half_hour).max(hour_fee);
+        let fastest_fee = estimate(fastest).max(half_hour_fee);
+        RecommendedFees {
+            fastest_fee,
+            half_hour_fee,
+            hour_fee,
+            economy_fee,
+        }
+    }
+}
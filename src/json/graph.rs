@@ -0,0 +1,117 @@
+use bitcoin::{Transaction, Txid};
+use serde::Serialize;
+
+use super::outspends::OutSpend;
+
+/// A transaction and its immediate neighbors: the transactions it spends from and the known
+/// transactions spending its outputs
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct TxGraph {
+    /// Distinct txids of the graph, the first one is the requested transaction
+    pub nodes: Vec<Txid>,
+
+    /// Value flowing from an output of a transaction to an input of another
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub from: Txid,
+    pub vout: u32,
+    pub to: Txid,
+    pub vin: u32,
+}
+
+impl TxGraph {
+    /// Coinbase inputs and outputs not spent or with unknown spending transaction have no edge
+    pub fn new(txid: Txid, tx: &Transaction, outspends: &[OutSpend]) -> Self {
+        let mut nodes = vec![txid];
+        let mut edges = vec![];
+        let mut add = |edge: Edge, node: Txid| {
+            if !nodes.contains(&node) {
+                nodes.push(node);
+            }
+            edges.push(edge);
+        };
+
+        if !tx.is_coinbase() {
+            for (vin, input) in tx.input.iter().enumerate() {
+                let edge = Edge {
+                    from: input.previous_output.txid,
+                    vout: input.previous_output.vout,
+                    to: txid,
+                    vin: vin as u32,
+                };
+                add(edge, input.previous_output.txid);
+            }
+        }
+        for (vout, outspend) in outspends.iter().enumerate() {
+            if let (Some(spending), Some(vin)) = (outspend.txid, outspend.vin) {
+                let edge = Edge {
+                    from: txid,
+                    vout: vout as u32,
+                    to: spending,
+                    vin,
+                };
+                add(edge, spending);
+            }
+        }
+
+        TxGraph { nodes, edges }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Edge, TxGraph};
+    use crate::json::outspends::OutSpend;
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, transaction::Version, Amount, OutPoint, ScriptBuf,
+        Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    };
+
+    #[test]
+    fn test_tx_graph() {
+        let txid = Txid::from_byte_array([1; 32]);
+        let parent = Txid::from_byte_array([2; 32]);
+        let child = Txid::from_byte_array([3; 32]);
+        let input = |vout| TxIn {
+            previous_output: OutPoint::new(parent, vout),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        let output = TxOut {
+            value: Amount::ONE_SAT,
+            script_pubkey: ScriptBuf::new(),
+        };
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![input(0), input(1)],
+            output: vec![output.clone(), output],
+        };
+        let outspends = vec![
+            OutSpend::unspent(),
+            OutSpend {
+                spent: Some(true),
+                txid: Some(child),
+                vin: Some(4),
+                height: None,
+            },
+        ];
+
+        let graph = TxGraph::new(txid, &tx, &outspends);
+        assert_eq!(graph.nodes, vec![txid, parent, child]);
+        assert_eq!(graph.edges.len(), 3);
+        assert_eq!(
+            graph.edges[2],
+            Edge {
+                from: txid,
+                vout: 1,
+                to: child,
+                vin: 4
+            }
+        );
+    }
+}
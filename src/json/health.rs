@@ -0,0 +1,22 @@
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Health {
+    pub blocks: u32,
+
+    /// bitcoind is not in initial block download
+    pub synced: bool,
+    pub bootstrap_done: bool,
+    pub tx_cache_len: usize,
+    pub tx_cache_full: bool,
+
+    /// `None` if the address index is not enabled
+    pub address_index_caught_up: Option<bool>,
+}
+
+impl Health {
+    /// Whether this instance is ready to serve traffic
+    pub fn is_ready(&self) -> bool {
+        self.synced && self.bootstrap_done
+    }
+}
@@ -0,0 +1,38 @@
+use bitcoin::Network;
+use serde::Serialize;
+
+use crate::{pages::explorer_name, NetworkExt};
+
+/// Minimal web app manifest, so that the explorer can be installed as a PWA
+#[derive(Serialize)]
+pub struct Manifest {
+    pub name: &'static str,
+    pub short_name: &'static str,
+    pub start_url: String,
+    pub display: &'static str,
+    pub icons: Vec<Icon>,
+}
+
+#[derive(Serialize)]
+pub struct Icon {
+    pub src: &'static str,
+    pub sizes: &'static str,
+    #[serde(rename = "type")]
+    pub mime_type: &'static str,
+}
+
+impl Manifest {
+    pub fn for_network(network: Network) -> Self {
+        Manifest {
+            name: explorer_name(network),
+            short_name: "FBBE",
+            start_url: network.as_url_path().to_string(),
+            display: "standalone",
+            icons: vec![Icon {
+                src: "/favicon.ico",
+                sizes: "16x16 24x24 32x32 48x48 64x64",
+                mime_type: "image/vnd.microsoft.icon",
+            }],
+        }
+    }
+}
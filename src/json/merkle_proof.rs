@@ -0,0 +1,99 @@
+use bitcoin::hashes::{sha256d, Hash, HashEngine};
+use bitcoin::{BlockHash, TxMerkleNode, Txid};
+use serde::Serialize;
+
+/// Proof that a transaction is included in a block, made of the hashes needed to compute the
+/// merkle root from the txid
+#[derive(Serialize)]
+pub struct MerkleProof {
+    pub block_hash: BlockHash,
+    pub block_height: u32,
+
+    /// Position of the transaction in the block
+    pub pos: usize,
+
+    /// Sibling hashes from the leaves up to the root
+    pub merkle: Vec<TxMerkleNode>,
+}
+
+impl MerkleProof {
+    /// `None` if `txid` is not in `txids`, which are all the txids of the block in order
+    pub fn new(
+        block_hash: BlockHash,
+        block_height: u32,
+        txids: &[Txid],
+        txid: Txid,
+    ) -> Option<Self> {
+        let pos = txids.iter().position(|t| *t == txid)?;
+        Some(MerkleProof {
+            block_hash,
+            block_height,
+            pos,
+            merkle: merkle_branch(txids, pos),
+        })
+    }
+}
+
+/// At every level the last hash is paired with itself when the number of hashes is odd
+fn merkle_branch(txids: &[Txid], mut pos: usize) -> Vec<TxMerkleNode> {
+    let mut level: Vec<sha256d::Hash> = txids.iter().map(|t| t.to_raw_hash()).collect();
+    let mut branch = vec![];
+    while level.len() > 1 {
+        let sibling = (pos ^ 1).min(level.len() - 1);
+        branch.push(TxMerkleNode::from_raw_hash(level[sibling]));
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut engine = sha256d::Hash::engine();
+                engine.input(pair[0].as_ref());
+                engine.input(pair.get(1).unwrap_or(&pair[0]).as_ref());
+                sha256d::Hash::from_engine(engine)
+            })
+            .collect();
+        pos /= 2;
+    }
+    branch
+}
+
+#[cfg(test)]
+mod test {
+    use super::merkle_branch;
+    use bitcoin::hashes::{sha256d, Hash, HashEngine};
+    use bitcoin::{merkle_tree::calculate_root, TxMerkleNode, Txid};
+
+    #[test]
+    fn test_merkle_branch() {
+        for len in 1..10u8 {
+            let txids: Vec<Txid> = (0..len).map(|i| Txid::from_byte_array([i; 32])).collect();
+            let root: TxMerkleNode = calculate_root(
+                txids
+                    .iter()
+                    .map(|t| TxMerkleNode::from_raw_hash(t.to_raw_hash())),
+            )
+            .unwrap();
+
+            for (pos, txid) in txids.iter().enumerate() {
+                let branch = merkle_branch(&txids, pos);
+                let mut current = txid.to_raw_hash();
+                let mut index = pos;
+                for sibling in branch {
+                    let mut engine = sha256d::Hash::engine();
+                    if index % 2 == 0 {
+                        engine.input(current.as_ref());
+                        engine.input(sibling.as_ref());
+                    } else {
+                        engine.input(sibling.as_ref());
+                        engine.input(current.as_ref());
+                    }
+                    current = sha256d::Hash::from_engine(engine);
+                    index /= 2;
+                }
+                assert_eq!(
+                    TxMerkleNode::from_raw_hash(current),
+                    root,
+                    "len:{len} pos:{pos}"
+                );
+            }
+        }
+    }
+}
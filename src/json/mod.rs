@@ -0,0 +1,25 @@
+use hyper::{header::CONTENT_TYPE, http::response::Builder, Body, Response};
+use mime::APPLICATION_JSON;
+use serde::Serialize;
+
+use crate::error::Error;
+
+pub mod address;
+pub mod block;
+pub mod cache;
+pub mod fees;
+pub mod graph;
+pub mod health;
+pub mod manifest;
+pub mod merkle_proof;
+pub mod op_return;
+pub mod outspends;
+pub mod tx;
+
+/// Serialize `value` as the body of a JSON response built from `builder`.
+pub fn response<T: Serialize>(builder: Builder, value: &T) -> Result<Response<Body>, Error> {
+    let body = serde_json::to_vec(value)?;
+    Ok(builder
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(body.into())?)
+}
@@ -0,0 +1,24 @@
+use bitcoin::{BlockHash, OutPoint};
+use serde::Serialize;
+
+use crate::threads::index_addresses::OpReturnSeen;
+
+/// An OP_RETURN output pushing the searched data
+#[derive(Serialize)]
+pub struct OpReturn {
+    pub outpoint: OutPoint,
+    pub block_hash: BlockHash,
+    pub height: u32,
+    pub time: u32,
+}
+
+impl From<&OpReturnSeen> for OpReturn {
+    fn from(value: &OpReturnSeen) -> Self {
+        OpReturn {
+            outpoint: value.out_point,
+            block_hash: value.block_hash,
+            height: value.height_time.height,
+            time: value.height_time.time,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use bitcoin::Txid;
+use serde::Serialize;
+
+use crate::threads::index_addresses::Height;
+
+/// Spending status of a transaction output, the response is an array with one of these for every
+/// output of the transaction
+#[derive(Serialize)]
+pub struct OutSpend {
+    /// Null if it cannot be determined, like when the address index is not enabled
+    pub spent: Option<bool>,
+
+    /// The spending transaction, missing if unspent or if the spending transaction is unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txid: Option<Txid>,
+
+    /// The spending input of `txid`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vin: Option<u32>,
+
+    /// Height of the block containing the spending transaction, missing if unspent or if the
+    /// spending transaction is unconfirmed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<Height>,
+}
+
+impl OutSpend {
+    pub fn unspent() -> Self {
+        OutSpend {
+            spent: Some(false),
+            txid: None,
+            vin: None,
+            height: None,
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use bitcoin::hex::DisplayHex;
+use bitcoin::{Address, BlockHash, OutPoint, ScriptBuf, Transaction, TxOut};
+use serde::Serialize;
+
+use crate::{
+    network,
+    pages::tx::{script_type, OutputStatus},
+    rpc::headers::HeightTime,
+    threads::index_addresses::Height,
+};
+
+#[derive(Serialize)]
+pub struct Tx {
+    pub txid: bitcoin::Txid,
+    pub version: i32,
+    pub lock_time: u32,
+    pub size: usize,
+    pub weight: u64,
+    pub vsize: usize,
+
+    /// `None` for coinbase or when some previous output is missing
+    pub fee: Option<u64>,
+    pub status: Status,
+    pub inputs: Vec<Input>,
+    pub outputs: Vec<Output>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Status {
+    Confirmed {
+        block_hash: BlockHash,
+        height: u32,
+        time: u32,
+
+        /// Changes with every new block, thus responses including it are not cached
+        confirmations: u32,
+    },
+    Unconfirmed,
+    UserProvided,
+}
+
+#[derive(Serialize)]
+pub struct Input {
+    /// `None` for the coinbase input
+    pub outpoint: Option<OutPoint>,
+    pub prevout: Prevout,
+    pub script_sig: ScriptBuf,
+    pub sequence: u32,
+    pub witness: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Prevout {
+    Coinbase,
+
+    /// The previous output could not be found, for example in a user provided tx
+    NotExist,
+    Found(Output),
+}
+
+#[derive(Serialize)]
+pub struct Output {
+    pub value: u64,
+    pub script_pubkey: ScriptBuf,
+    pub script_type: Option<String>,
+    pub address: Option<String>,
+
+    /// `None` when describing a previous output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spent: Option<Spent>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Spent {
+    Confirmed {
+        height: Height,
+    },
+    Unconfirmed {
+        txid: bitcoin::Txid,
+        vin: u32,
+    },
+
+    /// Spent according to the node, but the spending tx is unknown
+    Spent,
+    Unspent,
+    Unknown,
+}
+
+impl Tx {
+    pub fn new(
+        txid: bitcoin::Txid,
+        tx: &Transaction,
+        height_time: Option<(BlockHash, HeightTime)>,
+        tip_height: u32,
+        prevouts: &[TxOut],
+        output_status: &[OutputStatus],
+        user_provided: bool,
+    ) -> Self {
+        let sum_outputs: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
+        let missing_prevout = prevouts.iter().any(|p| p.value.to_sat() == u64::MAX);
+        let fee = (!tx.is_coinbase() && !missing_prevout).then(|| {
+            let sum_inputs: u64 = prevouts.iter().map(|o| o.value.to_sat()).sum();
+            sum_inputs.saturating_sub(sum_outputs)
+        });
+
+        let status = match height_time {
+            Some((block_hash, height_time)) => Status::Confirmed {
+                block_hash,
+                height: height_time.height,
+                time: height_time.time,
+                confirmations: tip_height.saturating_sub(height_time.height) + 1,
+            },
+            None if user_provided => Status::UserProvided,
+            None => Status::Unconfirmed,
+        };
+
+        let inputs = tx
+            .input
+            .iter()
+            .zip(prevouts.iter())
+            .map(|(input, previous_output)| {
+                let is_coinbase = input.previous_output == OutPoint::null();
+                let prevout = if is_coinbase {
+                    Prevout::Coinbase
+                } else if previous_output.value.to_sat() == u64::MAX {
+                    Prevout::NotExist
+                } else {
+                    Prevout::Found(Output::new(previous_output, None))
+                };
+                Input {
+                    outpoint: (!is_coinbase).then_some(input.previous_output),
+                    prevout,
+                    script_sig: input.script_sig.clone(),
+                    sequence: input.sequence.0,
+                    witness: input
+                        .witness
+                        .iter()
+                        .map(|e| e.to_lower_hex_string())
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let outputs = tx
+            .output
+            .iter()
+            .zip(output_status.iter())
+            .map(|(output, status)| Output::new(output, Some(status.into())))
+            .collect();
+
+        Tx {
+            txid,
+            version: tx.version.0,
+            lock_time: tx.lock_time.to_consensus_u32(),
+            size: tx.total_size(),
+            weight: tx.weight().to_wu(),
+            vsize: tx.vsize(),
+            fee,
+            status,
+            inputs,
+            outputs,
+        }
+    }
+}
+
+impl Output {
+    fn new(tx_out: &TxOut, spent: Option<Spent>) -> Self {
+        Output {
+            value: tx_out.value.to_sat(),
+            script_pubkey: tx_out.script_pubkey.clone(),
+            script_type: script_type(&tx_out.script_pubkey),
+            address: Address::from_script(&tx_out.script_pubkey, network())
+                .ok()
+                .map(|a| a.to_string()),
+            spent,
+        }
+    }
+}
+
+impl From<&OutputStatus> for Spent {
+    fn from(value: &OutputStatus) -> Self {
+        match value {
+            OutputStatus::ConfirmedSpent(height) => Spent::Confirmed { height: *height },
+            OutputStatus::UnconfirmedSpent(s) => Spent::Unconfirmed {
+                txid: *s.txid(),
+                vin: s.vin(),
+            },
+            OutputStatus::Spent => Spent::Spent,
+            OutputStatus::Unspent => Spent::Unspent,
+            OutputStatus::Unknown => Spent::Unknown,
+        }
+    }
+}
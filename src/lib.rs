@@ -1,18 +1,22 @@
 pub use crate::error::Error;
-use crate::globals::{init_globals, network};
-use crate::route::route_infallible;
+use crate::globals::{init_globals, main_network, network, served_networks, spawn, with_network};
+use crate::route::{route_networks, RemoteIp, Served};
 use crate::state::SharedState;
-use crate::threads::bootstrap_state::bootstrap_state_infallible;
+use crate::threads::bootstrap_state::{bootstrap_state_infallible, Warm};
 use crate::threads::index_addresses::{index_addresses_infallible, Database};
 use crate::threads::update_chain_info::update_chain_info_infallible;
 use crate::threads::update_mempool_info::update_mempool;
 use bitcoin::{Network, Txid};
 use clap::Parser;
-use globals::networks;
+use futures::future;
+use globals::{networks, root_network};
+use hyper::server::accept::{self, Accept};
+use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
-use hyper::Server;
+use hyper::{Body, Request, Server};
 use lazy_static::lazy_static;
-use network_parse::NetworkParse;
+use max_connections::Limited;
+use network_parse::{NetworkAddr, NetworkParse};
 use prometheus::{
     register_counter_vec, register_histogram_vec, register_int_counter_vec, CounterVec,
     HistogramVec, IntCounterVec,
@@ -21,28 +25,48 @@ use serde::Deserialize;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::fmt::Display;
+use std::future::Future;
+use std::io;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::path::PathBuf;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{ready, Poll};
 use threads::zmq::update_tx_zmq_infallible;
-use tokio::time::sleep;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::time::{sleep, Sleep};
 
 mod base_text_decorator;
+mod compress;
 mod error;
+mod feed;
 mod globals;
+mod invoice;
+mod json;
+mod max_connections;
 mod network_parse;
+mod op_return;
 mod pages;
+mod pools;
+mod rate_limit;
 mod render;
 mod req;
 mod route;
 mod rpc;
+mod server_timing;
 mod state;
+mod subsidy;
 mod threads;
+mod ws;
+mod xpub;
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Arguments {
-    /// Number of bytes kept in memory for caching transactions, use reasonable per-network default if missing
+    /// Number of bytes kept in memory for caching transactions, use reasonable per-network default if missing.
+    /// Split evenly between the served networks.
     #[arg(long, env)]
     tx_cache_byte_size: Option<usize>,
 
@@ -56,28 +80,69 @@ pub struct Arguments {
     #[arg(short, long, default_value = "10", env)]
     pub fetch_parallelism: usize,
 
+    /// Maximum number of open client connections, further ones wait in the listen backlog until
+    /// others are closed. Like the connections to bitcoind they count in the open files limit,
+    /// see `fetch_parallelism`. Unlimited if missing.
+    #[arg(long, env)]
+    pub max_connections: Option<NonZeroUsize>,
+
+    /// Close client connections after every response instead of keeping them open for the
+    /// following requests, so that idle clients don't hold `max_connections` slots
+    #[arg(long, env)]
+    pub no_http_keepalive: bool,
+
+    /// Close client connections not sending the request headers within these seconds, including
+    /// the idle time of kept alive connections. Defaults to 30 with `max_connections`, so that
+    /// idle or slow clients don't hold the slots forever, no timeout otherwise
+    #[arg(long, env)]
+    pub http_header_read_timeout_secs: Option<u64>,
+
     /// default to "127.0.0.1:<port>" where port depend on the network used, eg 8332 for mainnnet.
     #[arg(short, long, env)]
     pub bitcoind_addr: Option<SocketAddr>,
 
     /// default value: bitcoin
     ///
-    /// other possible values: testnet, signet
+    /// other possible values: testnet, testnet4, signet
     #[arg(short, long, env)]
     pub network: Option<NetworkParse>,
 
-    /// The socket address this service will bind on. Default value depends on the network:
+    /// The socket address this service will bind on, IPv6 addresses are accepted, eg "[::1]:3000".
+    /// Default value depends on the network:
     /// * mainnet: "127.0.0.1:3000"
     /// * testnet: "127.0.0.1:3001"
     /// * signet:  "127.0.0.1:3002"
     /// * regtest: "127.0.0.1:3003"
+    /// * testnet4: "127.0.0.1:3004"
 
     #[arg(short, long, env)]
     pub local_addr: Option<SocketAddr>,
 
+    /// Path of a unix domain socket to serve on instead of `local_addr`, useful behind a reverse
+    /// proxy on the same machine. A stale socket at the path is replaced.
+    #[arg(long, env, conflicts_with = "local_addr")]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Trust the `X-Forwarded-For` header set by a reverse proxy, the client ip in the logs is
+    /// taken from its last entry. Don't enable if the service is directly exposed, since clients
+    /// could set the header to anything.
+    #[arg(long, env)]
+    pub trust_forwarded_for: bool,
+
+    /// Requests per second allowed to every client ip, exceeding requests get a 429. The client
+    /// ip is taken from `X-Forwarded-For` if `trust_forwarded_for`, from the connection otherwise,
+    /// thus with `unix_socket` it requires `trust_forwarded_for`. IPv6 clients are limited by /64.
+    /// `/metrics` and `/health` are not limited. Unlimited if missing.
+    #[arg(long, env, value_parser = parse_rate_limit_per_sec)]
+    pub rate_limit_per_sec: Option<f64>,
+
+    /// Requests a client can make at once before being limited to `rate_limit_per_sec`
+    #[arg(long, default_value = "20", env, requires = "rate_limit_per_sec")]
+    pub rate_limit_burst: u32,
+
     /// If the setup involve multiple networks this must be set accordingly.
     /// An header with a link to all the network is generated.
-    /// Links are prepended the network if it isn't mainet (eg `/testnet/t/xxx...`)
+    /// Links are prepended the network if it isn't the `root_network` (eg `/testnet/t/xxx...`)
     /// Note the routes are still working without the network, it is duty of a frontend to redirect the
     /// path to appropriate port. eg.
     ///
@@ -93,15 +158,165 @@ pub struct Arguments {
     #[arg(short, long, env)]
     pub other_network: Vec<Network>,
 
+    /// The network whose pages are at `/`, without the network path prefix, the other networks
+    /// are at `/{network}/`. It must be served by this process. When not set it's mainnet if
+    /// served, requests without a prefix go to the main network otherwise.
+    #[arg(long, env)]
+    pub root_network: Option<Network>,
+
+    /// Other networks served by this same process, in the form `network=bitcoind_address`, eg.
+    /// `testnet=127.0.0.1:18332`. Requests are routed according to the network path prefix (eg.
+    /// `/testnet/t/xxx...`) so that a frontend redirect is not needed.
+    /// The address index, ZMQ and broadcasting are available only for the main network.
+    #[arg(long, env)]
+    pub other_bitcoind_addr: Vec<NetworkAddr>,
+
     #[arg(short, long, env)]
     pub addr_index_path: Option<PathBuf>,
 
+    /// JSON file with notes about transactions shown in their page, merged with the embedded
+    /// well-known transactions and overriding them. Same format of the embedded file, an array of
+    /// `{"txid": "...", "c": "note", "network": "signet"}`, where a missing network means mainnet.
+    #[arg(long, env)]
+    pub known_txs_path: Option<PathBuf>,
+
+    /// Index also the data pushed in OP_RETURN outputs, searchable at `/op-return/{hex}`. Grows
+    /// the address index, blocks indexed before enabling it are not indexed again.
+    #[arg(long, env, requires = "addr_index_path")]
+    pub index_op_return: bool,
+
+    /// Show in the address page the other addresses spent together with it, which are likely of
+    /// the same owner according to the common-input-ownership heuristic. Every spending tx and its
+    /// prevouts must be fetched and it eases chain analysis, thus it's disabled by default.
+    #[arg(long, env, requires = "addr_index_path")]
+    pub related_addresses: bool,
+
     /// Bitcoind ZMQ pub raw tx socket address
     #[arg(short, long, env)]
     pub zmq_rawtx: Option<SocketAddr>,
+
+    /// Bitcoind JSON-RPC credentials in the form `user:password`, needed only by the calls the
+    /// REST interface doesn't offer, like broadcasting transactions with `POST /tx`
+    #[arg(long, env, conflicts_with = "rpc_user")]
+    pub rpc_user_password: Option<String>,
+
+    /// Bitcoind JSON-RPC user, alternative to `rpc_user_password` together with `rpc_password`
+    #[arg(long, env, requires = "rpc_password")]
+    pub rpc_user: Option<String>,
+
+    /// Bitcoind JSON-RPC password of `rpc_user`
+    #[arg(long, env, requires = "rpc_user")]
+    pub rpc_password: Option<String>,
+
+    /// Bitcoind JSON-RPC cookie file, alternative to `rpc_user_password`
+    #[arg(long, env)]
+    pub rpc_cookie_file: Option<PathBuf>,
+
+    /// Seconds to wait for bitcoind to answer a call, after which the request fails with a 504
+    #[arg(long, default_value = "30", env)]
+    pub rpc_timeout_secs: u64,
+
+    /// How many times a REST call is retried when bitcoind is temporarily unavailable (status
+    /// 503), eg. while connecting a block. The delay between retries doubles at every attempt,
+    /// starting from 100 milliseconds
+    #[arg(long, default_value = "2", env)]
+    pub rpc_retries: u32,
+
+    /// Public host name of this service (eg `fbbe.info`), needed to produce absolute links in the
+    /// Atom feed of recent blocks at `/feed.xml`, which is disabled if missing (except for
+    /// requests to `onion_host`)
+    #[arg(long, env)]
+    pub dns_host: Option<String>,
+
+    /// Onion host name of this service (eg `xyz...xyz.onion`), absolute links of requests made
+    /// to this host (according to the `Host` header) use it instead of `dns_host`, so that Tor
+    /// users are not pointed to the clearnet
+    #[arg(long, env)]
+    pub onion_host: Option<String>,
+
+    /// Text added to the footer of html pages, eg. the name of the operator of this instance.
+    /// Html is escaped.
+    #[arg(long, env)]
+    pub footer_notice: Option<String>,
+
+    /// Makes `footer_notice` a link to this http(s) url, eg. a contact page
+    #[arg(long, env, requires = "footer_notice", value_parser = parse_http_url)]
+    pub footer_notice_url: Option<url::Url>,
+
+    /// Remove the links to the contact page and to the upstream source code from the footer
+    #[arg(long, env)]
+    pub no_upstream_links: bool,
+
+    /// If set, the value of the `Access-Control-Allow-Origin` header returned by API-style
+    /// responses (json, bin, hex, metrics, health) and by errors, eg. `*`. Html pages are
+    /// unaffected.
+    #[arg(long, env)]
+    pub cors_allow_origin: Option<hyper::header::HeaderValue>,
+
+    /// Add to html responses the `Server-Timing` header, with the time spent calling bitcoind
+    /// and rendering, shown by the browser developer tools
+    #[arg(long, env)]
+    pub server_timing: bool,
+
+    /// Maximum length in bytes of a transaction provided by the user, either as hex in the url
+    /// (`/txhex/...`, `/?s=...`) or in the body of a `POST /tx`. Bigger inputs are rejected before
+    /// decoding them.
+    #[arg(long, default_value = "4000000", env)]
+    pub max_request_body_size: usize,
+
+    /// Maximum number of addresses in a `POST /addresses` request, which accepts a json array of
+    /// addresses and returns their history, needs the address index
+    #[arg(long, default_value = "100", env)]
+    pub max_batch_addresses: usize,
+
+    /// Requests taking longer than these milliseconds are logged at warn level, with the kind of
+    /// resource requested and the response length
+    #[arg(long, default_value = "1000", env)]
+    pub slow_request_ms: u64,
+
+    /// Show in the page of a confirmed transaction how its fee rate compares with the others in
+    /// the same block. Expensive: the first view of a block fetches the previous outputs of all
+    /// its transactions.
+    #[arg(long, env)]
+    pub block_fee_percentile: bool,
+
+    /// Token enabling the admin endpoints, which are disabled if missing. Requests must have the
+    /// header `Authorization: Bearer <token>`.
+    ///
+    /// `POST /admin/cache?tx_cache_byte_size=N&txid_blockhash_len=M` resizes the caches (both
+    /// params are optional) and returns their current state.
+    #[arg(long, env)]
+    pub admin_token: Option<String>,
+
+    /// Number of inputs and outputs per page of a transaction, and of transactions per page of a
+    /// block. Between 1 and 1000.
+    #[arg(long, default_value = "10", env, value_parser = parse_rows_per_page)]
+    pub rows_per_page: usize,
+
+    /// How the tx cache is filled at startup
+    #[arg(long, value_enum, default_value = "recent-blocks", env)]
+    pub warm: Warm,
+
+    /// Confirmations from which the pages of blocks and transactions are cached for
+    /// `cache_deep_secs`, with less confirmations the cache time grows with them up to 3 hours
+    #[arg(long, default_value = "4", env)]
+    pub cache_deep_confirmations: u32,
+
+    /// Cache time in seconds of the pages of deeply confirmed blocks and transactions, and upper
+    /// bound of every other cache time of them. Default to one month, one minute on regtest
+    /// where reorgs are common.
+    #[arg(long, env)]
+    pub cache_deep_secs: Option<u32>,
 }
 
 impl Arguments {
+    pub fn cache_deep_secs(&self) -> u32 {
+        self.cache_deep_secs.unwrap_or(match network() {
+            Network::Regtest => 60,
+            _ => 60 * 60 * 24 * 30, // one month
+        })
+    }
+
     pub fn txid_blockhash_len(&self) -> usize {
         self.txid_blockhash_len
             .unwrap_or_else(|| match self.network.as_ref() {
@@ -124,23 +339,193 @@ impl Arguments {
                 None => 100_000_000,
             })
     }
+
+    pub fn http_header_read_timeout_secs(&self) -> Option<u64> {
+        self.http_header_read_timeout_secs
+            .or(self.max_connections.map(|_| 30))
+    }
 }
 
 pub async fn inner_main(mut args: Arguments) -> Result<(), Error> {
     init_globals(&mut args);
 
-    let addr = args.local_addr.take().unwrap_or_else(|| match network() {
-        Network::Bitcoin => create_local_socket(3000),
-        Network::Testnet => create_local_socket(3001),
-        Network::Signet => create_local_socket(3002),
-        Network::Regtest => create_local_socket(3003),
-        _ => panic!("non existing network"),
-    });
+    let addr = args
+        .local_addr
+        .take()
+        .unwrap_or_else(|| match main_network() {
+            Network::Bitcoin => create_local_socket(3000),
+            Network::Testnet => create_local_socket(3001),
+            Network::Signet => create_local_socket(3002),
+            Network::Regtest => create_local_socket(3003),
+            Network::Testnet4 => create_local_socket(3004),
+            _ => panic!("non existing network"),
+        });
     let args = args;
-    let zmq_rawtx = args.zmq_rawtx;
 
     log::debug!("local address {:?}", addr);
 
+    if let Some(root_network) = args.root_network {
+        if !served_networks().contains(&root_network) {
+            return Err(Error::RootNetworkNotServed(root_network));
+        }
+    }
+
+    if args.rate_limit_per_sec.is_some() && !args.trust_forwarded_for {
+        if args.unix_socket.is_some() {
+            return Err(Error::RateLimitWithoutClientIp);
+        }
+        if addr.ip().is_loopback() {
+            log::warn!("rate limiting on a loopback address without `--trust-forwarded-for`, all the clients of a local reverse proxy share the same limit");
+        }
+    }
+
+    // the networks are started concurrently, one failing to start is not served
+    let starting = served_networks().iter().map(|network| {
+        let mut args = args.clone();
+        if *network != main_network() {
+            // the address index and zmq are configured for the main network only
+            args.addr_index_path = None;
+            args.zmq_rawtx = None;
+        }
+        // the configured size is for the whole process
+        args.tx_cache_byte_size = Some(args.tx_cache_byte_size() / served_networks().len());
+        with_network(*network, start_network(args))
+    });
+    let started = future::join_all(starting).await;
+
+    let mut served = vec![];
+    for (network, started) in served_networks().iter().zip(started) {
+        match started {
+            Ok((shared_state, db)) => served.push((*network, shared_state, db)),
+            Err(e) if *network == main_network() => return Err(e),
+            Err(e) => log::error!("cannot start {network}, it is not served: {e:?}"),
+        }
+    }
+    let served = Arc::new(served);
+
+    if let Some(rate_limiter) = globals::rate_limiter() {
+        #[allow(clippy::let_underscore_future)]
+        let _ = tokio::spawn(async move {
+            loop {
+                sleep(RATE_LIMIT_PRUNE_INTERVAL).await;
+                rate_limiter.prune(std::time::Instant::now());
+            }
+        });
+    }
+
+    let result = match args.unix_socket.as_ref() {
+        Some(path) => {
+            remove_stale_socket(path)?;
+            let listener = UnixListener::bind(path)?;
+            log::info!("Listening on unix socket {}", path.display());
+            serve(unix_incoming(listener), served, |_| None, &args).await
+        }
+        None => {
+            let incoming = hyper::server::conn::AddrIncoming::bind(&addr)?;
+            log::info!("Listening on http://{}", addr);
+            serve(
+                incoming,
+                served,
+                |conn: &AddrStream| Some(conn.remote_addr().ip()),
+                &args,
+            )
+            .await
+        }
+    };
+
+    if let Err(e) = result {
+        log::error!("server error: {}", e);
+    }
+
+    log::info!("waiting background tasks to stop");
+    globals::shutdown(SHUTDOWN_MAX_WAIT).await;
+    Ok(())
+}
+
+/// Serve http connections accepted from `incoming` until CTRL+C is pressed, `remote_ip` gives
+/// the ip of the client of a connection, if any
+async fn serve<I>(
+    incoming: I,
+    served: Arc<Served>,
+    remote_ip: fn(&I::Conn) -> Option<IpAddr>,
+    args: &Arguments,
+) -> Result<(), hyper::Error>
+where
+    I: Accept + Send + 'static,
+    I::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    I::Conn: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let incoming = max_connections::limit(incoming, args.max_connections.map(NonZeroUsize::get));
+    let make_service = make_service_fn(move |conn: &Limited<I::Conn>| {
+        let served = served.clone();
+        let remote_ip = RemoteIp(remote_ip(&conn.conn));
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |mut req: Request<Body>| {
+                let served = served.clone();
+                req.extensions_mut().insert(remote_ip);
+                route_networks(req, served)
+            }))
+        }
+    });
+
+    let mut builder = Server::builder(incoming).http1_keepalive(!args.no_http_keepalive);
+    if let Some(secs) = args.http_header_read_timeout_secs() {
+        builder = builder.http1_header_read_timeout(std::time::Duration::from_secs(secs));
+    }
+    builder
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+}
+
+/// Binding fails if the path exists, a socket left by a previous run is removed but other files
+/// are kept
+fn remove_stale_socket(path: &Path) -> Result<(), Error> {
+    use std::os::unix::fs::FileTypeExt;
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => Ok(std::fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+/// Connections accepted from `listener`. Like `AddrIncoming`, errors of a single connection are
+/// skipped while other errors, like too many open files, are logged and accepting is retried
+/// after a pause instead of stopping the server
+fn unix_incoming(listener: UnixListener) -> impl Accept<Conn = UnixStream, Error = io::Error> {
+    let mut pause: Option<Pin<Box<Sleep>>> = None;
+    accept::poll_fn(move |cx| loop {
+        if let Some(paused) = pause.as_mut() {
+            ready!(paused.as_mut().poll(cx));
+            pause = None;
+        }
+        match ready!(listener.poll_accept(cx)) {
+            Ok((stream, _)) => return Poll::Ready(Some(Ok(stream))),
+            Err(e) if is_connection_error(&e) => log::debug!("accepted connection errored: {e}"),
+            Err(e) => {
+                log::error!("accept error: {e}, retrying in {ACCEPT_ERROR_PAUSE:?}");
+                pause = Some(Box::pin(sleep(ACCEPT_ERROR_PAUSE)));
+            }
+        }
+    })
+}
+
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// Wait the node of the current `network()` is synced, then build the state and spawn the
+/// background tasks keeping it updated
+async fn start_network(
+    args: Arguments,
+) -> Result<(Arc<SharedState>, Option<Arc<Database>>), Error> {
+    let zmq_rawtx = args.zmq_rawtx;
+
     let mut chain_info;
     loop {
         chain_info = match rpc::chaininfo::call().await {
@@ -173,7 +558,7 @@ pub async fn inner_main(mut args: Arguments) -> Result<(), Error> {
     let db = args
         .addr_index_path
         .as_ref()
-        .map(Database::new)
+        .map(|path| Database::new(path, args.index_op_return))
         .transpose()?
         .map(Arc::new);
 
@@ -183,23 +568,20 @@ pub async fn inner_main(mut args: Arguments) -> Result<(), Error> {
     let mempool_info = rpc::mempool::info().await?;
     log::info!("{:?}", mempool_info);
 
-    let content = include_str!("well-known-transactions.json");
-    let known_txs: Vec<KnownTx> = serde_json::from_str(content).unwrap();
-    let known_txs: HashMap<_, _> = known_txs.into_iter().map(|e| (e.txid, e.c)).collect();
-
-    let registry = prometheus::default_registry();
+    let known_txs = load_known_txs(args.known_txs_path.as_deref())?;
 
     let shared_state = Arc::new(SharedState::new(
         chain_info.clone(),
         args,
         mempool_info,
         known_txs,
-        registry,
+        pools::load(),
+        globals::registry(),
     ));
 
     // initialize cache with information from headers
     let shared_state_bootstrap = shared_state.clone();
-    let h = tokio::spawn(async move { bootstrap_state_infallible(shared_state_bootstrap).await });
+    let h = spawn(async move { bootstrap_state_infallible(shared_state_bootstrap).await });
 
     // keep chain info updated
     let shared_state_chain = shared_state.clone();
@@ -212,52 +594,38 @@ pub async fn inner_main(mut args: Arguments) -> Result<(), Error> {
     let db_clone = db.clone();
 
     #[allow(clippy::let_underscore_future)]
-    let _ = tokio::spawn(async move {
+    let _ = spawn(async move {
         h.await.unwrap();
         let db_clone2 = db_clone.clone();
         #[allow(clippy::let_underscore_future)]
-        let _ = tokio::spawn(async move {
+        let _ = spawn(async move {
             update_chain_info_infallible(shared_state_chain, chain_info_chain, db_clone2).await
         });
 
         if let Some(db) = db_clone {
-            let _ = tokio::spawn(async move {
+            let _ = spawn(async move {
                 index_addresses_infallible(db.clone(), shared_state_addresses).await
             });
         }
 
         if let Some(socket) = zmq_rawtx {
-            let _ =
-                tokio::spawn(
-                    async move { update_tx_zmq_infallible(&socket, shared_state_zmq).await },
-                );
+            let _ = spawn(async move { update_tx_zmq_infallible(&socket, shared_state_zmq).await });
         }
 
         update_mempool(shared_state_mempool).await;
     });
 
-    let make_service = make_service_fn(move |_| {
-        let shared_state = shared_state.clone();
-        let db = db.clone();
-
-        async move {
-            Ok::<_, Infallible>(service_fn(move |req| {
-                let shared_state = shared_state.clone();
-                let db = db.clone();
-                route_infallible(req, shared_state, db)
-            }))
-        }
-    });
+    Ok((shared_state, db))
+}
 
-    let server = Server::bind(&addr).serve(make_service);
+/// Maximum time waited, after the server stopped, for the database writes to finish
+const SHUTDOWN_MAX_WAIT: std::time::Duration = std::time::Duration::from_secs(30);
 
-    log::info!("Listening on http://{}", addr);
+/// Pause before accepting again after an error not related to a single connection
+const ACCEPT_ERROR_PAUSE: std::time::Duration = std::time::Duration::from_secs(1);
 
-    if let Err(e) = server.with_graceful_shutdown(shutdown_signal()).await {
-        log::error!("server error: {}", e);
-    }
-    Ok(())
-}
+/// How often the rate limiter forgets the clients idle long enough to have a full bucket
+const RATE_LIMIT_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
 
 async fn shutdown_signal() {
     // Wait for the CTRL+C signal
@@ -283,9 +651,7 @@ pub struct NetworkPath(Network);
 
 impl Display for NetworkPath {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Network::Bitcoin = self.0 {
-            write!(f, "/")
-        } else if networks().len() == 1 {
+        if self.0 == root_network() || networks().len() == 1 {
             write!(f, "/")
         } else {
             write!(f, "/{}/", self.0)
@@ -303,6 +669,28 @@ impl NetworkExt for Network {
     }
 }
 
+fn parse_http_url(s: &str) -> Result<url::Url, String> {
+    let url = url::Url::parse(s).map_err(|e| e.to_string())?;
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        scheme => Err(format!("scheme {scheme} not allowed, use http or https")),
+    }
+}
+
+fn parse_rows_per_page(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>().map_err(|e| e.to_string())? {
+        rows @ 1..=1000 => Ok(rows),
+        rows => Err(format!("{rows} is not between 1 and 1000")),
+    }
+}
+
+fn parse_rate_limit_per_sec(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>().map_err(|e| e.to_string())? {
+        per_sec if per_sec.is_finite() && per_sec > 0.0 => Ok(per_sec),
+        per_sec => Err(format!("{per_sec} is not a positive number")),
+    }
+}
+
 pub fn create_local_socket(port: u16) -> SocketAddr {
     SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port)
 }
@@ -311,6 +699,26 @@ pub fn create_local_socket(port: u16) -> SocketAddr {
 struct KnownTx {
     c: String,
     txid: Txid,
+
+    /// Mainnet if missing
+    network: Option<Network>,
+}
+
+/// The embedded well-known txs and the ones in the file at `path` of the current `network()`, the
+/// latter take precedence
+fn load_known_txs(path: Option<&Path>) -> Result<HashMap<Txid, String>, Error> {
+    let embedded: Vec<KnownTx> =
+        serde_json::from_str(include_str!("well-known-transactions.json"))?;
+    let from_file: Vec<KnownTx> = match path {
+        Some(path) => serde_json::from_slice(&std::fs::read(path)?)?,
+        None => vec![],
+    };
+    Ok(embedded
+        .into_iter()
+        .chain(from_file)
+        .filter(|e| e.network.unwrap_or(Network::Bitcoin) == network())
+        .map(|e| (e.txid, e.c))
+        .collect())
 }
 
 lazy_static! {
@@ -332,6 +740,18 @@ lazy_static! {
         &["method", "content"]
     )
     .unwrap();
+    pub(crate) static ref NODE_REST_HISTOGRAM: HistogramVec = register_histogram_vec!(
+        "fbbe_rpc_request_to_node_duration_seconds",
+        "The latencies in seconds of the RPC requests made to the node.",
+        &["method", "content"]
+    )
+    .unwrap();
+    pub(crate) static ref REORG_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "fbbe_reorgs",
+        "Number of chain reorganizations, by number of blocks disconnected",
+        &["network", "depth"]
+    )
+    .unwrap();
     static ref CACHE_COUNTER: IntCounterVec = register_int_counter_vec!(
         "fbbe_cache_counter",
         "Hit/Miss of FBBE caches",
@@ -0,0 +1,137 @@
+//! Cap the number of open client connections.
+//!
+//! Every accepted connection holds a permit of a semaphore, if there is a limit, released when
+//! the connection is dropped. When there are no permits left new connections are not accepted
+//! and wait in the listen backlog of the OS.
+
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+};
+
+use hyper::server::accept::{self, Accept};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+
+type Acquire = Pin<Box<dyn Future<Output = OwnedSemaphorePermit> + Send>>;
+
+/// A connection counted in the limit until dropped
+pub(crate) struct Limited<C> {
+    pub conn: C,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// Accept connections from `incoming` as long as less than `max` are open, unlimited if `None`
+pub(crate) fn limit<I>(
+    incoming: I,
+    max: Option<usize>,
+) -> impl Accept<Conn = Limited<I::Conn>, Error = I::Error>
+where
+    I: Accept + Send + 'static,
+{
+    let mut incoming = Box::pin(incoming);
+    let semaphore = max.map(|max| Arc::new(Semaphore::new(max)));
+    let mut acquire: Option<Acquire> = None;
+    let mut permit = None;
+
+    accept::poll_fn(move |cx| {
+        if let (Some(semaphore), None) = (semaphore.as_ref(), permit.as_ref()) {
+            let fut = acquire.get_or_insert_with(|| {
+                let semaphore = semaphore.clone();
+                Box::pin(async move {
+                    semaphore
+                        .acquire_owned()
+                        .await
+                        .expect("the semaphore is never closed")
+                })
+            });
+            permit = Some(ready!(fut.as_mut().poll(cx)));
+            acquire = None;
+        }
+        let conn = ready!(incoming.as_mut().poll_accept(cx));
+        Poll::Ready(conn.map(|conn| {
+            conn.map(|conn| Limited {
+                conn,
+                _permit: permit.take(),
+            })
+        }))
+    })
+}
+
+impl<C: AsyncRead + Unpin> AsyncRead for Limited<C> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.conn).poll_read(cx, buf)
+    }
+}
+
+impl<C: AsyncWrite + Unpin> AsyncWrite for Limited<C> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.conn).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.conn).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.conn).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.conn).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.conn.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{convert::Infallible, task::Poll};
+
+    use futures::future::poll_fn;
+    use hyper::server::accept::{self, Accept};
+
+    use super::limit;
+
+    #[tokio::test]
+    async fn test_limit() {
+        let mut next = 0u32;
+        let incoming = accept::poll_fn(move |_| {
+            next += 1;
+            Poll::Ready(Some(Ok::<_, Infallible>(next)))
+        });
+        let mut limited = Box::pin(limit(incoming, Some(2)));
+
+        let first = poll_fn(|cx| limited.as_mut().poll_accept(cx)).await;
+        let first = first.unwrap().unwrap();
+        let second = poll_fn(|cx| limited.as_mut().poll_accept(cx)).await;
+        let second = second.unwrap().unwrap();
+        assert_eq!((first.conn, second.conn), (1, 2));
+
+        // the third waits until a connection is dropped
+        let third = poll_fn(|cx| limited.as_mut().poll_accept(cx));
+        tokio::pin!(third);
+        assert!(futures::poll!(third.as_mut()).is_pending());
+        drop(first);
+        assert_eq!(third.await.unwrap().unwrap().conn, 3);
+    }
+}
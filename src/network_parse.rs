@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{net::SocketAddr, str::FromStr};
 
 use bitcoin::Network;
 
@@ -15,6 +15,7 @@ impl FromStr for NetworkParse {
         let network = match s {
             "bitcoin" | "mainnet" | "main" => NetworkParse(Bitcoin),
             "testnet" | "test" => NetworkParse(Testnet),
+            "testnet4" => NetworkParse(Testnet4),
             "signet" => NetworkParse(Signet),
             "regtest" => NetworkParse(Regtest),
             _ => return Err(Error::NetworkParseError(s.to_string())),
@@ -38,3 +39,27 @@ impl AsRef<Network> for NetworkParse {
         &self.0
     }
 }
+
+/// A network and the address of the bitcoind node serving it, in the form
+/// `testnet=127.0.0.1:18332`
+#[derive(Clone, Debug)]
+pub struct NetworkAddr {
+    pub(crate) network: Network,
+    pub(crate) addr: SocketAddr,
+}
+
+impl FromStr for NetworkAddr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (network, addr) = s
+            .split_once('=')
+            .ok_or_else(|| Error::NetworkAddrParseError(s.to_string()))?;
+        Ok(NetworkAddr {
+            network: NetworkParse::from_str(network)?.0,
+            addr: addr
+                .parse()
+                .map_err(|_| Error::NetworkAddrParseError(s.to_string()))?,
+        })
+    }
+}
@@ -0,0 +1,235 @@
+//! Recognize well-known protocols carried in OP_RETURN outputs.
+//!
+//! Every entry of [`REGISTRY`] is tried in order, the first returning something labels the
+//! output. Outputs not recognized are shown as hex and utf8 pushes.
+
+use bitcoin::blockdata::opcodes::all::{OP_PUSHNUM_13, OP_RETURN};
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::Script;
+
+/// A recognized OP_RETURN output
+#[derive(Debug, PartialEq, Eq)]
+pub struct Decoded {
+    pub label: &'static str,
+
+    /// Short summary of the parsed content, as name and value
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// A protocol matcher, returns the parsed fields if the output belongs to the protocol
+type Matcher = fn(&Script) -> Option<Vec<(&'static str, String)>>;
+
+const REGISTRY: &[(&str, Matcher)] = &[
+    ("Runestone", runestone),
+    ("Segwit witness commitment", witness_commitment),
+    ("Omni Layer", omni),
+    ("Counterparty", counterparty),
+    ("Stacks", stacks),
+    ("Hash commitment, eg. OpenTimestamps", hash_commitment),
+];
+
+/// The label and the fields of the first protocol in the registry matching `script`
+pub fn decode(script: &Script) -> Option<Decoded> {
+    if !script.is_op_return() {
+        return None;
+    }
+    REGISTRY
+        .iter()
+        .find_map(|&(label, matcher)| matcher(script).map(|fields| Decoded { label, fields }))
+}
+
+/// The data pushed after the opcodes in `skip`, `None` if there are other opcodes
+fn pushes(script: &Script, skip: usize) -> Option<Vec<&[u8]>> {
+    script
+        .instructions()
+        .skip(skip)
+        .map(|i| match i {
+            Ok(Instruction::PushBytes(data)) => Some(data.as_bytes()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn payload(script: &Script, skip: usize) -> Option<Vec<u8>> {
+    pushes(script, skip).map(|p| p.concat())
+}
+
+fn runestone(script: &Script) -> Option<Vec<(&'static str, String)>> {
+    let mut instructions = script.instructions();
+    if instructions.next() != Some(Ok(Instruction::Op(OP_RETURN)))
+        || instructions.next() != Some(Ok(Instruction::Op(OP_PUSHNUM_13)))
+    {
+        return None;
+    }
+    let integers = leb128_integers(&payload(script, 2)?)?;
+
+    // tag-value pairs, until the body tag (0) which is followed by edicts of 4 integers each
+    let mut etching = false;
+    let mut mint = None;
+    let mut edicts = 0;
+    for (i, pair) in integers.chunks(2).enumerate() {
+        match pair {
+            [0, ..] => {
+                edicts = (integers.len() - 2 * i - 1) / 4;
+                break;
+            }
+            [2, flags] => etching |= flags & 1 == 1,
+            [20, value] => match mint {
+                None => mint = Some((*value, 0)),
+                Some((block, _)) => mint = Some((block, *value)),
+            },
+            _ => (),
+        }
+    }
+
+    let mut fields = vec![];
+    if etching {
+        fields.push(("Etching", "yes".to_string()));
+    }
+    if let Some((block, tx)) = mint {
+        fields.push(("Mint", format!("{block}:{tx}")));
+    }
+    fields.push(("Edicts", edicts.to_string()));
+    Some(fields)
+}
+
+fn witness_commitment(script: &Script) -> Option<Vec<(&'static str, String)>> {
+    let pushes = pushes(script, 1)?;
+    match pushes.as_slice() {
+        [data] if data.len() >= 36 && data.starts_with(&[0xaa, 0x21, 0xa9, 0xed]) => Some(vec![]),
+        _ => None,
+    }
+}
+
+fn omni(script: &Script) -> Option<Vec<(&'static str, String)>> {
+    let payload = payload(script, 1)?;
+    let data = payload.strip_prefix(b"omni")?;
+    let version = u16::from_be_bytes(data.get(0..2)?.try_into().ok()?);
+    let kind = u16::from_be_bytes(data.get(2..4)?.try_into().ok()?);
+    let mut fields = vec![("Version", version.to_string()), ("Type", kind.to_string())];
+    if kind == 0 {
+        // simple send
+        let property = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?);
+        let amount = u64::from_be_bytes(data.get(8..16)?.try_into().ok()?);
+        fields.push(("Property", property.to_string()));
+        fields.push(("Amount", amount.to_string()));
+    }
+    Some(fields)
+}
+
+/// Only unobfuscated messages are recognized, most of them are encrypted with the txid of the
+/// first input
+fn counterparty(script: &Script) -> Option<Vec<(&'static str, String)>> {
+    payload(script, 1)?.starts_with(b"CNTRPRTY").then(Vec::new)
+}
+
+fn stacks(script: &Script) -> Option<Vec<(&'static str, String)>> {
+    let payload = payload(script, 1)?;
+    let network = match payload.get(0..2)? {
+        b"X2" => "mainnet",
+        b"T2" => "testnet",
+        _ => return None,
+    };
+    let operation = match payload.get(2)? {
+        b'[' => "Block commit",
+        b'^' => "Leader key register",
+        b'p' => "Pre stx",
+        b'x' => "Stack stx",
+        b'$' => "Transfer stx",
+        b'#' => "Delegate stx",
+        _ => return None,
+    };
+    Some(vec![
+        ("Network", network.to_string()),
+        ("Operation", operation.to_string()),
+    ])
+}
+
+/// A single 32 bytes push, could be any hash but it's the way OpenTimestamps calendars commit
+fn hash_commitment(script: &Script) -> Option<Vec<(&'static str, String)>> {
+    match pushes(script, 1)?.as_slice() {
+        [data] if data.len() == 32 => Some(vec![]),
+        _ => None,
+    }
+}
+
+/// `None` if the last integer is truncated or an integer overflows
+fn leb128_integers(mut data: &[u8]) -> Option<Vec<u128>> {
+    let mut result = vec![];
+    while !data.is_empty() {
+        let mut value = 0u128;
+        let mut i = 0;
+        loop {
+            let byte = *data.get(i)?;
+            if i >= 19 {
+                return None;
+            }
+            value |= ((byte & 0x7f) as u128) << (7 * i);
+            i += 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        result.push(value);
+        data = &data[i..];
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, leb128_integers};
+    use bitcoin::ScriptBuf;
+
+    fn decode_hex(hex: &str) -> Option<(&'static str, Vec<(&'static str, String)>)> {
+        decode(&ScriptBuf::from_hex(hex).unwrap()).map(|d| (d.label, d.fields))
+    }
+
+    #[test]
+    fn test_leb128() {
+        assert_eq!(leb128_integers(&[0x00, 0x7f]), Some(vec![0, 127]));
+        assert_eq!(leb128_integers(&[0xe5, 0x8e, 0x26]), Some(vec![624485]));
+        assert_eq!(leb128_integers(&[0x80]), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        // not an op return
+        assert_eq!(
+            decode_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+            None
+        );
+
+        // unknown
+        assert_eq!(decode_hex("6a0b68656c6c6f20776f726c64"), None);
+
+        let (label, fields) = decode_hex(
+            "6a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9",
+        )
+        .unwrap();
+        assert_eq!(label, "Segwit witness commitment");
+        assert!(fields.is_empty());
+
+        // simple send of 10 USDT (property 31)
+        let (label, fields) = decode_hex("6a146f6d6e69000000000000001f000000003b9aca00").unwrap();
+        assert_eq!(label, "Omni Layer");
+        assert_eq!(fields[2], ("Property", "31".to_string()));
+        assert_eq!(fields[3], ("Amount", "1000000000".to_string()));
+
+        // mint of rune 840000:3, no edicts
+        let (label, fields) = decode_hex("6a5d0614c0a2331403").unwrap();
+        assert_eq!(label, "Runestone");
+        assert_eq!(
+            fields,
+            vec![
+                ("Mint", "840000:3".to_string()),
+                ("Edicts", "0".to_string())
+            ]
+        );
+
+        let (label, _) =
+            decode_hex("6a20e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+                .unwrap();
+        assert_eq!(label, "Hash commitment, eg. OpenTimestamps");
+    }
+}
@@ -6,8 +6,11 @@ use maud::{html, Markup};
 use qr_code::QrCode;
 
 use crate::{
-    error::Error, render::Html, req::ParsedRequest, route::convert_text_html_string,
-    threads::index_addresses::AddressSeen,
+    error::Error,
+    render::{AmountRow, Html, Unit},
+    req::ParsedRequest,
+    route::convert_text_html_string,
+    threads::index_addresses::{possibly_truncated, AddressSeen, HEIGHTS_LIMIT},
 };
 
 use super::html_page;
@@ -17,6 +20,7 @@ pub fn page(
     parsed: &ParsedRequest,
     query: &Option<String>,
     address_seen: Vec<AddressSeen>,
+    related: Option<Vec<Address>>,
 ) -> Result<Markup, Error> {
     let address_type = address
         .address_type()
@@ -42,16 +46,6 @@ pub fn page(
     };
 
     let script_pubkey = address.script_pubkey();
-    let txids_len = address_seen.len();
-
-    // TODO the spent part
-    //  eg 1 transaction output (1 spent)
-    //  eg 1 transaction output
-    //  eg 3 transaction outputs (1 spent)
-
-    // TODO add truncated at the end
-
-    // TODO paging to most recent 10 funding
 
     let content = html! {
         section {
@@ -77,36 +71,83 @@ pub fn page(
                 }
             }
 
-            @if !address_seen.is_empty() {
+            (seen_section(address_seen, parsed.unit))
+
+            @if let Some(related) = related {
                 hgroup {
-                    h2 { (txids_len) " transaction output" @if txids_len == 1 { "" } @else { "s" }  }
-                    p { "only confirmed, most recent funding first" }
+                    h2 { "Related addresses" }
+                    p { "Heuristic: inputs spent together are assumed of the same owner, wrong for CoinJoins and PayJoins. Only the first spending transactions are considered." }
                 }
-
-                table class="striped" {
-                    tbody {
-                        @for txid in address_seen {
-                            tr {
-                                td {
-                                    (txid)
+                @if related.is_empty() {
+                    p { "None found" }
+                } @else {
+                    table class="striped" {
+                        tbody {
+                            @for related in related {
+                                tr {
+                                    td { (related.html()) }
                                 }
                             }
                         }
                     }
-                    @if txids_len > 9 {
-                        tfoot {
-                            tr {
-                                td { "possibly truncated"  }
+                }
+            }
+        }
+    };
 
+    Ok(html_page("Address", content, parsed))
+}
+
+/// Totals and the list of the outputs funding a script, shared by the address and the script page
+pub(super) fn seen_section(address_seen: Vec<AddressSeen>, unit: Unit) -> Markup {
+    let txids_len = address_seen.len();
+    let truncated = possibly_truncated(address_seen.iter().map(|s| s.funding.height_time.height));
+    let received: u64 = address_seen.iter().map(|s| s.funding.value).sum();
+    let spent: u64 = address_seen
+        .iter()
+        .filter(|s| s.spent_height.is_some())
+        .map(|s| s.funding.value)
+        .sum();
+
+    html! {
+        @if truncated {
+            p { "Only the most recent " (HEIGHTS_LIMIT) " blocks funding it are shown, the totals are unknown" }
+        } @else if !address_seen.is_empty() {
+            table class="striped" {
+                tbody {
+                    (AmountRow::new_with_sat("Total received", received, unit))
+                    (AmountRow::new_with_sat("Total spent", spent, unit))
+                    (AmountRow::new_with_sat("Balance", received - spent, unit))
+                }
+            }
+        }
+
+        @if !address_seen.is_empty() {
+            hgroup {
+                h2 { (txids_len) " transaction output" @if txids_len == 1 { "" } @else { "s" }  }
+                p { "only confirmed, most recent funding first" }
+            }
+
+            table class="striped" {
+                tbody {
+                    @for txid in address_seen {
+                        tr {
+                            td {
+                                (txid)
                             }
                         }
                     }
                 }
+                @if truncated {
+                    tfoot {
+                        tr {
+                            td { "possibly truncated" }
+                        }
+                    }
+                }
             }
         }
-    };
-
-    Ok(html_page("Address", content, parsed))
+    }
 }
 
 /// Converts `input` in base64 and returns a data url
@@ -132,12 +173,37 @@ fn create_bmp_base64_qr(message: &str) -> Result<String, Error> {
 pub fn text_page(address: &Address, page: &str, col: u16) -> Result<String, Error> {
     let mut s = convert_text_html_string(page, col);
     s.push('\n');
-    s.push_str(&create_string_qr(&address.to_qr_uri())?);
+    match create_string_qr(&address.to_qr_uri(), col)? {
+        Some(qr) => s.push_str(&qr),
+        None => s.push_str(&format!(
+            "QR code omitted, it doesn't fit in {col} columns\n"
+        )),
+    }
     Ok(s)
 }
-/// Creates QR containing `message` and encode it in data url
-pub(crate) fn create_string_qr(message: &str) -> Result<String, Error> {
+
+/// Creates QR containing `message` as text, `None` if wider than `col` even with the quiet zone
+/// reduced to the minimum. A wrapped QR can't be scanned.
+pub(crate) fn create_string_qr(message: &str, col: u16) -> Result<Option<String>, Error> {
     let qr = QrCode::new(message.as_bytes())?;
 
-    Ok(qr.to_string(true, 2))
+    // every module is one column wide, two modules share a line
+    Ok([2u8, 1]
+        .into_iter()
+        .find(|border| qr.width() + 2 * *border as usize <= col as usize)
+        .map(|border| qr.to_string(true, border)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::create_string_qr;
+
+    #[test]
+    fn test_create_string_qr() {
+        let uri = "bitcoin:BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4";
+        let qr = create_string_qr(uri, 80).unwrap().unwrap();
+        assert!(qr.lines().all(|l| l.chars().count() <= 80));
+
+        assert_eq!(create_string_qr(uri, 20).unwrap(), None);
+    }
 }
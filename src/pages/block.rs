@@ -1,40 +1,99 @@
 use crate::{
     error::Error,
+    globals::rows_per_page,
     network,
     pages::{html_page, size_rows},
-    render::{self, Html, Plural},
+    render::{self, AmountRow, GroupedHex, Html, Plural, ScriptSig},
     req::ParsedRequest,
-    rpc::block::BlockNoTxDetails,
+    rpc::{block::BlockNoTxDetails, ts_to_date_time_utc},
+    subsidy::subsidy,
     NetworkExt,
 };
+use bitcoin::{
+    blockdata::script::Instruction,
+    consensus::{deserialize, deserialize_partial},
+    ScriptBuf, Transaction, Weight, Witness,
+};
 use maud::{html, Markup};
 
-const PER_PAGE: usize = 10;
+/// Start of the push in the witness commitment output carrying the signet solution, BIP325
+const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+
+/// The signet challenge of the network and the solution in a block satisfying it
+pub struct Signet {
+    /// `None` if the node doesn't report it
+    pub challenge: Option<ScriptBuf>,
 
+    /// `None` if the block doesn't contain it, as the genesis
+    pub solution: Option<(ScriptBuf, Witness)>,
+}
+
+impl Signet {
+    pub fn new(challenge_hex: Option<&str>, coinbase: &Transaction) -> Self {
+        Signet {
+            challenge: challenge_hex.and_then(|c| ScriptBuf::from_hex(c).ok()),
+            solution: signet_solution(coinbase),
+        }
+    }
+}
+
+/// The script sig and the witness in the signet push of the last witness commitment output
+fn signet_solution(coinbase: &Transaction) -> Option<(ScriptBuf, Witness)> {
+    let commitment = coinbase.output.iter().rev().find(|o| {
+        let script = o.script_pubkey.as_bytes();
+        script.len() >= 38 && script.starts_with(&[0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed])
+    })?;
+    let data = commitment
+        .script_pubkey
+        .instructions()
+        .find_map(|i| match i {
+            Ok(Instruction::PushBytes(data)) => data.as_bytes().strip_prefix(&SIGNET_HEADER),
+            _ => None,
+        })?;
+    let (script_sig, consumed) = deserialize_partial::<ScriptBuf>(data).ok()?;
+    let witness: Witness = deserialize(&data[consumed..]).ok()?;
+    Some((script_sig, witness))
+}
+
+/// `coinbase_total` is the sum of the coinbase transaction outputs in satoshi, `median_time_past`
+/// is `None` if unknown
 pub fn page(
     block: &BlockNoTxDetails,
     page: usize,
     parsed: &ParsedRequest,
+    coinbase_total: u64,
+    median_time_past: Option<u32>,
+    miner: Option<&str>,
+    signet: Option<Signet>,
 ) -> Result<Markup, Error> {
-    let from_tx = page * PER_PAGE;
+    let subsidy = subsidy(block.height, network());
+    // what the miner claimed over the subsidy, a lower bound of the fees paid by the txs since
+    // miners may claim less than allowed
+    let claimed_fees = coinbase_total.saturating_sub(subsidy);
+
+    let per_page = rows_per_page();
+    let from_tx = page * per_page;
     if from_tx >= block.tx.len() {
         return Err(Error::InvalidPageNumber);
     }
-    let to_tx = block.tx.len().min(from_tx + PER_PAGE);
+    let to_tx = block.tx.len().min(from_tx + per_page);
+    let fullness = block.weight as f64 * 100.0 / Weight::MAX_BLOCK.to_wu() as f64;
     let network_url_path = network().as_url_path();
-    let txids = block.tx.iter().skip(from_tx).take(PER_PAGE).enumerate();
+    let txids = block.tx.iter().skip(from_tx).take(per_page).enumerate();
     let translate = |i: usize| i + from_tx;
     let transaction_plural = Plural::new("transaction", block.tx.len());
 
-    let prev_txs = (page > 0).then(|| format!("{}b/{}/{}", network_url_path, block.hash, page - 1));
+    let unit = parsed.unit.link_query();
+    let prev_txs =
+        (page > 0).then(|| format!("{}b/{}/{}{unit}", network_url_path, block.hash, page - 1));
     let next_txs = (to_tx != block.tx.len())
-        .then(|| format!("{}b/{}/{}", network_url_path, block.hash, page + 1));
+        .then(|| format!("{}b/{}/{}{unit}", network_url_path, block.hash, page + 1));
     let separator_txs = (prev_txs.is_some() && next_txs.is_some()).then_some(" | ");
 
     let current_block = if page == 0 {
         html! { (block.height) }
     } else {
-        let block_link = format!("{}b/{}", network().as_url_path(), block.hash);
+        let block_link = format!("{}b/{}{unit}", network().as_url_path(), block.hash);
         html! {a href=(block_link) {(block.height)}}
     };
 
@@ -51,7 +110,35 @@ pub fn page(
                         th { "Timestamp" }
                         td class="right" { (block.date_time_utc()) }
                     }
+                    @if let Some(miner) = miner {
+                        tr {
+                            th { "Mined by" }
+                            td class="right" { (miner) }
+                        }
+                    }
+                    @if block.height > 0 {
+                        tr {
+                            th { "Median time past" }
+                            td class="right" {
+                                @if let Some(median_time_past) = median_time_past {
+                                    (ts_to_date_time_utc(median_time_past))
+                                } @else {
+                                    "unknown"
+                                }
+                            }
+                        }
+                    }
                     (size_rows(block.size, block.weight))
+                    tr {
+                        th { "Fullness" }
+                        td class="right" {
+                            div class="bar" { span style=(format!("width:{:.1}%", fullness.min(100.0))) {} }
+                            (format!("{:.1}%", fullness))
+                        }
+                    }
+                    (AmountRow::new_with_sat("Subsidy", subsidy, parsed.unit))
+                    (AmountRow::new_with_sat("Fees claimed", claimed_fees, parsed.unit))
+                    (AmountRow::new_with_sat("Coinbase output total", coinbase_total, parsed.unit))
                 }
             }
 
@@ -96,7 +183,7 @@ pub fn page(
                     }
                     tr {
                         th { "Merkle root" }
-                        td class="right" { code { (block.merkleroot) } }
+                        td class="right" { code { (GroupedHex(&block.merkleroot)) } }
                     }
                     tr {
                         th { "Bits" }
@@ -110,6 +197,29 @@ pub fn page(
                         th { "Nonce" }
                         td class="right" { (block.nonce) }
                     }
+                    @if let Some(signet) = signet {
+                        @if let Some(challenge) = signet.challenge {
+                            tr {
+                                th { "Signet challenge" }
+                                td class="right" { (challenge.html()) }
+                            }
+                        }
+                        tr {
+                            th { "Signet solution" }
+                            td class="right" {
+                                @if let Some((script_sig, witness)) = signet.solution {
+                                    @if !script_sig.is_empty() {
+                                        p { (ScriptSig(&script_sig)) }
+                                    }
+                                    @if !witness.is_empty() {
+                                        p { (witness.html()) }
+                                    }
+                                } @else {
+                                    "none"
+                                }
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -117,3 +227,47 @@ pub fn page(
 
     Ok(html_page("Block", content, parsed))
 }
+
+#[cfg(test)]
+mod test {
+    use super::{signet_solution, SIGNET_HEADER};
+    use bitcoin::{
+        absolute::LockTime, consensus::serialize, opcodes::all::OP_RETURN, script::Builder,
+        script::PushBytesBuf, transaction::Version, Amount, ScriptBuf, Transaction, TxOut, Witness,
+    };
+
+    #[test]
+    fn test_signet_solution() {
+        let witness = Witness::from_slice(&[vec![0x30, 0x44], vec![]]);
+        let mut solution = SIGNET_HEADER.to_vec();
+        solution.extend(serialize(&ScriptBuf::new()));
+        solution.extend(serialize(&witness));
+
+        let mut commitment = vec![0xaa, 0x21, 0xa9, 0xed];
+        commitment.extend([0u8; 32]);
+        let script_pubkey = |pushes: Vec<Vec<u8>>| {
+            pushes
+                .into_iter()
+                .fold(Builder::new().push_opcode(OP_RETURN), |b, p| {
+                    b.push_slice(PushBytesBuf::try_from(p).unwrap())
+                })
+                .into_script()
+        };
+        let mut coinbase = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: script_pubkey(vec![commitment.clone()]),
+            }],
+        };
+        assert_eq!(signet_solution(&coinbase), None);
+
+        coinbase.output[0].script_pubkey = script_pubkey(vec![commitment, solution]);
+        assert_eq!(
+            signet_solution(&coinbase),
+            Some((ScriptBuf::new(), witness))
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{
+    network,
+    pages::html_page,
+    render::{Html, HumanBytes},
+    req::ParsedRequest,
+    rpc::block::BlockNoTxDetails,
+    NetworkExt,
+};
+use maud::{html, Markup};
+
+pub const PER_PAGE: u32 = 10;
+
+/// `blocks` are sorted by descending height, starting from the tip at page 0
+pub fn page(blocks: &[BlockNoTxDetails], page: usize, parsed: &ParsedRequest) -> Markup {
+    let network_url_path = network().as_url_path();
+    let last_height = blocks.last().map(|b| b.height).unwrap_or(0);
+
+    let unit = parsed.unit.link_query();
+    let prev_blocks = (page > 0).then(|| format!("{}blocks/{}{unit}", network_url_path, page - 1));
+    let next_blocks =
+        (last_height > 0).then(|| format!("{}blocks/{}{unit}", network_url_path, page + 1));
+    let separator_blocks = (prev_blocks.is_some() && next_blocks.is_some()).then_some(" | ");
+
+    let content = html! {
+        section {
+            hgroup {
+                h1 { "Blocks" }
+                p {
+                    @if let (Some(first), Some(last)) = (blocks.first(), blocks.last()) {
+                        "From " (first.height) " to " (last.height)
+                    }
+                }
+            }
+
+            p {
+                @if let Some(prev) = prev_blocks {
+                    a href=(prev) { "Prev" }
+                }
+                @if let Some(separator) = separator_blocks {
+                    (separator)
+                }
+                @if let Some(next) = next_blocks {
+                    a href=(next) { "Next" }
+                }
+            }
+
+            table class="striped" {
+                thead {
+                    tr {
+                        th { "Height" }
+                        th { "Hash" }
+                        th { "Timestamp" }
+                        th class="right" { "Txs" }
+                        th class="right" { "Size" }
+                    }
+                }
+                tbody {
+                    @for block in blocks {
+                        tr {
+                            th class="row-index" {
+                                (block.height)
+                            }
+                            td {
+                                (block.hash.html())
+                            }
+                            td {
+                                (block.date_time_utc())
+                            }
+                            td class="number" {
+                                (block.tx.len())
+                            }
+                            td class="number" {
+                                (HumanBytes::new(block.size as u32))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    html_page("Blocks", content, parsed)
+}
@@ -0,0 +1,268 @@
+use bitcoin::{Address, OutPoint, Transaction, TxOut, Txid};
+use maud::{html, Markup};
+
+use crate::{
+    network,
+    render::{Html, Plural, Unit},
+    req::ParsedRequest,
+    threads::update_mempool_info::WeightFee,
+};
+
+use super::{html_page, tx::script_type};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Change {
+    Unchanged,
+    Added,
+    Removed,
+
+    /// Same script, the value in the original tx is different
+    Value(u64),
+}
+
+/// Outputs of `new` paired with the first unpaired output of `old` with the same script, followed
+/// by the outputs of `old` remained unpaired
+fn diff_outputs<'a>(old: &'a [TxOut], new: &'a [TxOut]) -> Vec<(&'a TxOut, Change)> {
+    let mut paired = vec![false; old.len()];
+    let mut result = vec![];
+    for output in new {
+        let same_script =
+            (0..old.len()).find(|&i| !paired[i] && old[i].script_pubkey == output.script_pubkey);
+        let change = match same_script {
+            Some(i) => {
+                paired[i] = true;
+                if old[i].value == output.value {
+                    Change::Unchanged
+                } else {
+                    Change::Value(old[i].value.to_sat())
+                }
+            }
+            None => Change::Added,
+        };
+        result.push((output, change));
+    }
+    result.extend(
+        old.iter()
+            .zip(paired)
+            .filter(|(_, paired)| !paired)
+            .map(|(o, _)| (o, Change::Removed)),
+    );
+    result
+}
+
+/// Previous outputs spent by `new`, followed by the ones spent only by `old`
+fn diff_inputs(old: &Transaction, new: &Transaction) -> Vec<(OutPoint, Change)> {
+    let spent_by =
+        |tx: &Transaction, outpoint| tx.input.iter().any(|i| i.previous_output == outpoint);
+    let mut result: Vec<_> = new
+        .input
+        .iter()
+        .map(|i| {
+            let change = if spent_by(old, i.previous_output) {
+                Change::Unchanged
+            } else {
+                Change::Added
+            };
+            (i.previous_output, change)
+        })
+        .collect();
+    result.extend(
+        old.input
+            .iter()
+            .filter(|i| !spent_by(new, i.previous_output))
+            .map(|i| (i.previous_output, Change::Removed)),
+    );
+    result
+}
+
+/// Compare the `old` tx with the `new` one, usually its replacement. Fees are `None` if unknown,
+/// like for the coinbase.
+pub fn page(
+    old: (Txid, &Transaction, Option<WeightFee>),
+    new: (Txid, &Transaction, Option<WeightFee>),
+    parsed: &ParsedRequest,
+) -> Markup {
+    let unit = parsed.unit;
+    let (old_txid, old_tx, old_wf) = old;
+    let (new_txid, new_tx, new_wf) = new;
+    let fee_delta = match (old_wf.as_ref(), new_wf.as_ref()) {
+        (Some(old), Some(new)) if new.fee >= old.fee => {
+            Some(format!("+{}", unit.format((new.fee - old.fee) as u64)))
+        }
+        (Some(old), Some(new)) => Some(format!("-{}", unit.format((old.fee - new.fee) as u64))),
+        _ => None,
+    };
+    let conflicting = old_tx.input.iter().any(|i| {
+        new_tx
+            .input
+            .iter()
+            .any(|j| i.previous_output == j.previous_output)
+    });
+
+    let inputs = diff_inputs(old_tx, new_tx);
+    let outputs = diff_outputs(&old_tx.output, &new_tx.output);
+    let inputs_plural = Plural::new("input", inputs.len());
+    let outputs_plural = Plural::new("output", outputs.len());
+
+    let content = html! {
+        section {
+            hgroup {
+                h1 { "Transaction diff" }
+                p {
+                    @if conflicting {
+                        "The transactions spend some of the same outputs"
+                    } @else {
+                        "The transactions don't spend any of the same outputs, it's not a replacement"
+                    }
+                }
+            }
+
+            table class="striped" {
+                thead {
+                    tr {
+                        th { }
+                        th class="right" { "Original" }
+                        th class="right" { "Replacement" }
+                    }
+                }
+                tbody {
+                    tr {
+                        th { "Transaction" }
+                        td class="right" { (old_txid.html()) }
+                        td class="right" { (new_txid.html()) }
+                    }
+                    tr {
+                        th { "Virtual size" }
+                        td class="right" { (old_tx.vsize()) " vB" }
+                        td class="right" { (new_tx.vsize()) " vB" }
+                    }
+                    tr {
+                        th { "Fee" }
+                        @for wf in [old_wf.as_ref(), new_wf.as_ref()] {
+                            td class="right" {
+                                @if let Some(wf) = wf { (unit.format(wf.fee as u64)) } @else { "unknown" }
+                            }
+                        }
+                    }
+                    tr {
+                        th { "Fee rate (BTC/KvB)" }
+                        @for wf in [old_wf.as_ref(), new_wf.as_ref()] {
+                            td class="number" {
+                                @if let Some(wf) = wf { (wf) } @else { "unknown" }
+                            }
+                        }
+                    }
+                }
+                @if let Some(fee_delta) = fee_delta {
+                    tfoot {
+                        tr {
+                            th { "Fee delta" }
+                            td { }
+                            td class="right" { (fee_delta) }
+                        }
+                    }
+                }
+            }
+
+            h2 { (inputs.len()) " " (inputs_plural) }
+            table class="striped" {
+                tbody {
+                    @for (i, (outpoint, change)) in inputs.into_iter().enumerate() {
+                        tr {
+                            th class="row-index" { (i) }
+                            td { (outpoint.html()) }
+                            td class="right" { (change_label(&change)) }
+                        }
+                    }
+                }
+            }
+
+            h2 { (outputs.len()) " " (outputs_plural) }
+            table class="striped" {
+                tbody {
+                    @for (i, (output, change)) in outputs.into_iter().enumerate() {
+                        @let address = Address::from_script(&output.script_pubkey, network()).ok();
+                        tr {
+                            th class="row-index" { (i) }
+                            td {
+                                @if let Some(address) = address {
+                                    div {
+                                        "Address"
+                                        p { (address.html()) }
+                                    }
+                                }
+                                div {
+                                    "Script pubkey"
+                                    @if let Some(script_type) = script_type(&output.script_pubkey) {
+                                        " (" (script_type) ")"
+                                    }
+                                }
+                                p { (output.script_pubkey.html()) }
+                            }
+                            td class="right" {
+                                (change_value(output.value.to_sat(), &change, unit))
+                                br;
+                                small { (change_label(&change)) }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    html_page("Transaction diff", content, parsed)
+}
+
+fn change_value(value: u64, change: &Change, unit: Unit) -> Markup {
+    let value = unit.format(value);
+    html! {
+        @match change {
+            Change::Unchanged => (value),
+            Change::Added => ins { (value) },
+            Change::Removed => del { (value) },
+            Change::Value(old) => { del { (unit.format(*old)) } " → " ins { (value) } },
+        }
+    }
+}
+
+fn change_label(change: &Change) -> Markup {
+    html! {
+        @match change {
+            Change::Unchanged => "unchanged",
+            Change::Added => ins { "added" },
+            Change::Removed => del { "removed" },
+            Change::Value(_) => "changed",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff_outputs, Change};
+    use bitcoin::{Amount, ScriptBuf, TxOut};
+
+    #[test]
+    fn test_diff_outputs() {
+        let output = |script: u8, value: u64| TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: ScriptBuf::from_bytes(vec![script]),
+        };
+        let old = [output(1, 1000), output(2, 500), output(3, 100)];
+        // replacement reducing the change output 2, removing output 3 and adding output 4
+        let new = [output(2, 300), output(1, 1000), output(4, 50)];
+        let changes: Vec<_> = diff_outputs(&old, &new)
+            .into_iter()
+            .map(|(o, c)| (o.script_pubkey.as_bytes()[0], c))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (2, Change::Value(500)),
+                (1, Change::Unchanged),
+                (4, Change::Added),
+                (3, Change::Removed),
+            ]
+        );
+    }
+}
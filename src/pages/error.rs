@@ -0,0 +1,64 @@
+use super::html_page;
+use crate::{
+    error::Error,
+    network,
+    render::Unit,
+    req::{ParsedRequest, Resource, Theme},
+    route::ResponseType,
+    NetworkExt,
+};
+use maud::{html, Markup};
+
+/// Errors caused by the request rather than by a failure of this service or of the node, for
+/// these a styled page is shown to browsers
+pub fn is_user_facing(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::BadRequest
+            | Error::NotFound
+            | Error::InvalidPageNumber
+            | Error::HeightNotFound
+            | Error::AddressWrongNetwork { .. }
+            | Error::SearchPrefixTooShort(_)
+            | Error::SearchPrefixAmbiguous(_)
+            | Error::TxRejected(_)
+            | Error::Bip32(_)
+            | Error::XpubWrongNetwork(_)
+            | Error::InvoiceWrongNetwork(_)
+            | Error::AddressIndexMissing(_)
+            | Error::OpReturnIndexMissing
+    )
+}
+
+pub fn page(error: &Error, theme: Theme) -> Markup {
+    let title = match error {
+        Error::NotFound | Error::HeightNotFound => "Not found",
+        _ => "Bad request",
+    };
+    let content = html! {
+        section {
+            hgroup {
+                h1 { (title) }
+                p { (error) }
+            }
+
+            form action=(network().as_url_path()) {
+                label for="s" { "Search for tx id, block height or hash" }
+                input type="search" id="s" name="s" autofocus;
+            }
+
+            p { a href=(format!("{}{}", network().as_url_path(), Unit::current().link_query())) { "Back to the home page" } }
+        }
+    };
+
+    // `Head` has no text version, so the footer doesn't link to one
+    let parsed = ParsedRequest {
+        resource: Resource::Head,
+        response_type: ResponseType::Html,
+        unit: Unit::default(),
+        theme,
+        set_theme: None,
+        query: None,
+    };
+    html_page(title, content, &parsed)
+}
@@ -3,25 +3,36 @@ use std::time::Duration;
 use super::html_page;
 use crate::{
     network,
-    render::{Html, MempoolSection, SizeRow},
+    render::{Activity, AmountRow, Html, Plural, Retarget, SizeRow},
     req::ParsedRequest,
     rpc::{chaininfo::ChainInfo, headers::HeightTime},
-    state::BlockTemplate,
+    state::Reorg,
+    subsidy::supply,
+    NetworkExt,
 };
 use maud::{html, Markup, PreEscaped};
 
 const TWO_HOURS: Duration = Duration::from_secs(60 * 60 * 2);
 
+#[allow(clippy::too_many_arguments)]
 pub fn page(
     info: ChainInfo,
     height_time: HeightTime,
-    mempool_sec: MempoolSection,
+    mempool_sec: Markup,
     minutes_since_blocks: Option<String>,
     parsed: &ParsedRequest,
-    block_template: BlockTemplate,
+    block_template: Markup,
+    retarget: Option<Retarget>,
+    activity: Activity,
+    last_reorg: Option<Reorg>,
 ) -> Markup {
     let duration = height_time.since_now();
     let blockchain_size_row = SizeRow::new("Size on disk", info.size_on_disk);
+    let supply_row = AmountRow::new_with_sat(
+        "Estimated supply",
+        supply(info.blocks, network()),
+        parsed.unit,
+    );
     let content = html! {
         @if duration > TWO_HOURS {
             (PreEscaped("<!-- LAST BLOCK MORE THAN 2 HOURS AGO -->"))
@@ -37,7 +48,7 @@ pub fn page(
 
             @if !parsed.response_type.is_text() {
                 form {
-                    label for="s" { "Search for tx id, block height or hash" }
+                    label for="s" { "Search for tx id, block height or hash, address, bitcoin: URI or lightning invoice" }
                     input type="search" id="s" name="s" placeholder=(info.blocks) autofocus;
                 }
             }
@@ -46,7 +57,7 @@ pub fn page(
                 tbody {
                     tr {
                         th {
-                            "Block " (info.blocks)
+                            a href=(format!("{}blocks{}", network().as_url_path(), parsed.unit.link_query())) { "Block " (info.blocks) }
                         }
                         td class="right" {
                             (info.best_block_hash.html())
@@ -64,6 +75,25 @@ pub fn page(
                         }
                     }
 
+                    @if let Some(retarget) = retarget.as_ref() {
+                        (retarget)
+                    }
+
+                    (activity)
+
+                    @if let Some(reorg) = last_reorg {
+                        tr {
+                            th { "Last reorg" }
+                            td class="right" {
+                                (reorg.depth) " " (Plural::new("block", reorg.depth as usize)) " from height " (reorg.at.height)
+                                br;
+                                small { (reorg.at.date_time_utc()) }
+                            }
+                        }
+                    }
+
+                    (supply_row)
+
                     (blockchain_size_row)
 
                 }
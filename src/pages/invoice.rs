@@ -0,0 +1,93 @@
+use bitcoin::hex::DisplayHex;
+use maud::{html, Markup};
+
+use crate::{invoice::Invoice, render::AmountRow, req::ParsedRequest, rpc::ts_to_date_time_utc};
+
+use super::html_page;
+
+pub fn page(invoice: &Invoice, parsed: &ParsedRequest) -> Markup {
+    let network = match invoice.currency {
+        "bc" => "mainnet",
+        "tb" => "testnet",
+        "tbs" => "signet",
+        _ => "regtest",
+    };
+    let date = |ts: u64| {
+        u32::try_from(ts)
+            .map(ts_to_date_time_utc)
+            .unwrap_or_else(|_| "far future".to_string())
+    };
+    let created = date(invoice.timestamp);
+    let expires = date(invoice.timestamp.saturating_add(invoice.expiry));
+
+    let content = html! {
+        section {
+            hgroup {
+                h1 { "Lightning invoice" }
+                p { "Decoded BOLT11 invoice, the signature is not verified" }
+            }
+
+            table class="striped" {
+                tbody {
+                    tr {
+                        th { "Network" }
+                        td class="right" { (network) }
+                    }
+                    @match invoice.amount_msat {
+                        Some(msat) if msat % 1000 == 0 => {
+                            (AmountRow::new_with_sat("Amount", msat / 1000, parsed.unit))
+                        }
+                        Some(msat) => {
+                            tr {
+                                th { "Amount" }
+                                td class="right" { (msat) " msat" }
+                            }
+                        }
+                        None => {
+                            tr {
+                                th { "Amount" }
+                                td class="right" { "any" }
+                            }
+                        }
+                    }
+                    @if let Some(description) = invoice.description.as_ref() {
+                        tr {
+                            th { "Description" }
+                            td class="right" { (description) }
+                        }
+                    }
+                    @if let Some(description_hash) = invoice.description_hash.as_ref() {
+                        tr {
+                            th { "Description hash" }
+                            td class="right" { code { (description_hash.to_lower_hex_string()) } }
+                        }
+                    }
+                    tr {
+                        th { "Payment hash" }
+                        td class="right" { code { (invoice.payment_hash.to_lower_hex_string()) } }
+                    }
+                    @if let Some(payee) = invoice.payee.as_ref() {
+                        tr {
+                            th { "Payee" }
+                            td class="right" { code { (payee.to_lower_hex_string()) } }
+                        }
+                    }
+                    tr {
+                        th { "Created" }
+                        td class="right" { (created) }
+                    }
+                    tr {
+                        th { "Expires" }
+                        td class="right" { (expires) }
+                    }
+                }
+            }
+
+            h2 { "Invoice" }
+
+            code { (invoice) }
+        }
+    };
+
+    html_page("Lightning invoice", content, parsed)
+}
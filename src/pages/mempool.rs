@@ -0,0 +1,82 @@
+use crate::{
+    error::Error,
+    network,
+    pages::html_page,
+    render::{Html, Plural},
+    req::ParsedRequest,
+    threads::update_mempool_info::{TxidWeightFee, TxidWeightFeeCompact},
+    NetworkExt,
+};
+use maud::{html, Markup};
+
+const PER_PAGE: usize = 20;
+
+/// `by_rate` are the mempool txs with known fee, sorted by descending fee rate
+pub fn page(
+    by_rate: &[TxidWeightFeeCompact],
+    page: usize,
+    parsed: &ParsedRequest,
+) -> Result<Markup, Error> {
+    let from_tx = page * PER_PAGE;
+    if page > 0 && from_tx >= by_rate.len() {
+        return Err(Error::InvalidPageNumber);
+    }
+    let to_tx = by_rate.len().min(from_tx + PER_PAGE);
+    let network_url_path = network().as_url_path();
+    let txs = by_rate[from_tx..to_tx]
+        .iter()
+        .map(TxidWeightFee::from)
+        .enumerate();
+    let translate = |i: usize| i + from_tx;
+    let transaction_plural = Plural::new("transaction", by_rate.len());
+
+    let unit = parsed.unit.link_query();
+    let prev_txs = (page > 0).then(|| format!("{}mempool/{}{unit}", network_url_path, page - 1));
+    let next_txs =
+        (to_tx != by_rate.len()).then(|| format!("{}mempool/{}{unit}", network_url_path, page + 1));
+    let separator_txs = (prev_txs.is_some() && next_txs.is_some()).then_some(" | ");
+
+    let content = html! {
+        section {
+            hgroup {
+                h1 { "Mempool" }
+                p { (by_rate.len()) " " (transaction_plural) " sorted by fee rate" }
+            }
+
+            hgroup {
+                h2 { "From " (from_tx) " to " (to_tx) }
+                p {
+                    @if let Some(prev) = prev_txs {
+                        a href=(prev) { "Prev" }
+                    }
+                    @if let Some(separator) = separator_txs {
+                        (separator)
+                    }
+                    @if let Some(next) = next_txs {
+                        a href=(next) { "Next" }
+                    }
+                }
+            }
+
+            table class="striped" {
+                tbody {
+                    @for (i, tx) in txs {
+                        tr {
+                            th class="row-index" {
+                                (translate(i))
+                            }
+                            td {
+                                (tx.txid.html())
+                            }
+                            td class="number" {
+                                (tx.wf)
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(html_page("Mempool", content, parsed))
+}
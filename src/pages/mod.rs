@@ -1,10 +1,10 @@
 use std::collections::BTreeSet;
 
 use crate::{
-    globals::networks,
+    globals::{self, networks},
     network,
-    render::SizeRow,
-    req::{ParsedRequest, Resource},
+    render::{SizeRow, Unit},
+    req::{ParsedRequest, Resource, Theme},
     route::ResponseType,
     NetworkExt,
 };
@@ -13,9 +13,18 @@ use maud::{html, Markup, PreEscaped, DOCTYPE};
 
 pub mod address;
 pub mod block;
+pub mod blocks;
 pub mod contact;
+pub mod diff;
+pub mod error;
 pub mod home;
+pub mod invoice;
+pub mod mempool;
+pub mod op_return;
+pub mod psbt;
+pub mod script;
 pub mod tx;
+pub mod xpub;
 
 pub const NBSP: PreEscaped<&str> = PreEscaped("&nbsp;");
 
@@ -27,34 +36,41 @@ pub fn header(title: &str) -> Markup {
             meta name="viewport" content="width=device-width, initial-scale=1";
             meta name="description" content="A Fast Bitcoin Block Explorer: simple, bitcoin-only, cache-friendly, terminal-friendly, low-bandwith, no images, no javascript. With mainnet, testnet and signet.";
             link rel="stylesheet" href="/css/pico.min.css";
+            link rel="manifest" href=(format!("{}manifest.json", network().as_url_path()));
             style { (include_str!("../css/custom.min.css")) }
             title { "FBBE - "(title) }
         }
     }
 }
 
-fn nav_header(response_type: ResponseType) -> Markup {
-    let title = match network() {
+pub fn explorer_name(network: Network) -> &'static str {
+    match network {
         Network::Bitcoin => "Fast Bitcoin Block Explorer",
         Network::Testnet => "Fast Bitcoin Block Explorer (Testnet)",
+        Network::Testnet4 => "Fast Bitcoin Block Explorer (Testnet4)",
         Network::Signet => "Fast Bitcoin Block Explorer (Signet)",
         Network::Regtest => "Fast Bitcoin Block Explorer (Regtest)",
         _ => panic!("non existing network"),
-    };
+    }
+}
+
+fn nav_header(response_type: ResponseType) -> Markup {
+    let title = explorer_name(network());
 
     let mut other_networks: BTreeSet<_> = networks().iter().collect();
     other_networks.remove(&network());
+    let unit = Unit::current().link_query();
 
     html! {
         nav {
             ul {
-                li { a href=(network().as_url_path()) aria-current="page" { (title) } }
+                li { a href=(format!("{}{unit}", network().as_url_path())) aria-current="page" { (title) } }
             }
 
             @if !other_networks.is_empty() && !response_type.is_text() {
                 ul {
                     @for net in other_networks {
-                        li { a href=(net.as_url_path()) { (net.to_maiusc_string()) } }
+                        li { a href=(format!("{}{unit}", net.as_url_path())) { (net.to_maiusc_string()) } }
                     }
                 }
             }
@@ -65,7 +81,7 @@ fn nav_header(response_type: ResponseType) -> Markup {
 pub fn html_page(title: &str, content: Markup, parsed: &ParsedRequest) -> Markup {
     html! {
         (DOCTYPE)
-        html lang = "en" {
+        html lang = "en" data-theme=[parsed.theme.data_theme()] {
             (header(title))
             body {
                 div class="container" {
@@ -80,12 +96,26 @@ pub fn html_page(title: &str, content: Markup, parsed: &ParsedRequest) -> Markup
     }
 }
 
-/// A static footer.
+/// Link to the current page with `theme`, keeping the other query params
+fn theme_link(parsed: &ParsedRequest, theme: Theme) -> String {
+    let theme = format!("theme={}", theme.as_str());
+    let params: Vec<_> = parsed
+        .query
+        .as_deref()
+        .into_iter()
+        .chain(parsed.unit.param())
+        .chain(Some(theme.as_str()))
+        .collect();
+    format!("?{}", params.join("&"))
+}
+
+/// The footer, with the operator notice if configured, empty in text mode.
 pub fn footer(parsed: &ParsedRequest) -> Markup {
     if parsed.response_type.is_text() {
         return html! {};
     }
-    let base = network().as_url_path();
+    let base = format!("{}{}", network().as_url_path(), parsed.unit.link_query());
+    let config = globals::footer();
 
     let home = if let Resource::Home = parsed.resource {
         html! { a href=(base) aria-current="page" { "Home" } }
@@ -96,11 +126,32 @@ pub fn footer(parsed: &ParsedRequest) -> Markup {
         footer {
             div class="container" {
                 (home)
-                @if let Some(link) = parsed.resource.link() {
+                @if let Some(link) = parsed.resource.link(parsed.unit) {
                     " | " a href=(link) { "Text" }
                 }
-                " | " a href="/contact" { "Contact" }
-                " | " a href="https://github.com/RCasatta/fbbe" { "Source" }
+                @if config.upstream_links {
+                    " | " a href="/contact" { "Contact" }
+                    " | " a href="https://github.com/RCasatta/fbbe" { "Source" }
+                }
+                " | Theme "
+                @for (i, theme) in Theme::ALL.iter().enumerate() {
+                    @if i > 0 {
+                        " "
+                    }
+                    @if *theme == parsed.theme {
+                        a aria-current="page" { (theme.as_str()) }
+                    } @else {
+                        a href=(theme_link(parsed, *theme)) { (theme.as_str()) }
+                    }
+                }
+                @if let Some(notice) = config.notice.as_ref() {
+                    " | "
+                    @if let Some(url) = config.notice_url.as_ref() {
+                        a href=(url) { (notice) }
+                    } @else {
+                        (notice)
+                    }
+                }
 
             }
         }
@@ -0,0 +1,50 @@
+use bitcoin::hex::DisplayHex;
+use maud::{html, Markup};
+
+use crate::{
+    render::Html,
+    req::ParsedRequest,
+    threads::index_addresses::{possibly_truncated, OpReturnSeen},
+};
+
+use super::html_page;
+
+pub fn page(data: &[u8], parsed: &ParsedRequest, seen: Vec<OpReturnSeen>) -> Markup {
+    let len = seen.len();
+    let truncated = possibly_truncated(seen.iter().map(|s| s.height_time.height));
+    let content = html! {
+        section {
+            hgroup {
+                h1 { "OP_RETURN data" }
+                p { code { (data.to_lower_hex_string()) } }
+            }
+
+            hgroup {
+                h2 { (len) " transaction output" @if len == 1 { "" } @else { "s" } }
+                p { "only confirmed, most recent first" }
+            }
+
+            table class="striped" {
+                tbody {
+                    @for seen in seen {
+                        tr {
+                            td {
+                                div { "Committed at height " (seen.height_time.height) " @ " (seen.height_time.date_time_utc()) }
+                                p { (seen.out_point.html()) }
+                            }
+                        }
+                    }
+                }
+                @if truncated {
+                    tfoot {
+                        tr {
+                            td { "possibly truncated" }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    html_page("OP_RETURN data", content, parsed)
+}
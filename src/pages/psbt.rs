@@ -0,0 +1,245 @@
+use bitcoin::{
+    bip32::KeySource, consensus::encode::serialize_hex, psbt::Input, Address, Psbt, ScriptBuf,
+    Transaction, TxOut,
+};
+use maud::{html, Markup};
+
+use crate::{
+    network,
+    pages::{html_page, tx::script_type},
+    render::{AmountRow, Html, Plural, ScriptSig},
+    req::ParsedRequest,
+    NetworkExt,
+};
+
+/// A BIP32 derivation of a key, like `pubkey [d34db33f/84'/0'/0'/0/1]`
+struct Derivation {
+    key: String,
+    origin: String,
+}
+
+impl Derivation {
+    fn new(key: String, (fingerprint, path): &KeySource) -> Self {
+        let path = path.to_string();
+        let origin = format!("{fingerprint}{}", path.trim_start_matches('m'));
+        Derivation { key, origin }
+    }
+}
+
+/// The previous output spent by `input`, from the witness utxo or the full previous tx
+fn utxo<'a>(input: &'a Input, txin: &bitcoin::TxIn) -> Option<&'a TxOut> {
+    input.witness_utxo.as_ref().or_else(|| {
+        input
+            .non_witness_utxo
+            .as_ref()
+            .and_then(|tx| tx.output.get(txin.previous_output.vout as usize))
+    })
+}
+
+fn derivations(bip32: Vec<Derivation>) -> Markup {
+    html! {
+        @if !bip32.is_empty() {
+            div {
+                "BIP32 derivations"
+                @for d in bip32 {
+                    p { code { (d.key) } " " code { "[" (d.origin) "]" } }
+                }
+            }
+        }
+    }
+}
+
+fn script_rows(redeem_script: &Option<ScriptBuf>, witness_script: &Option<ScriptBuf>) -> Markup {
+    html! {
+        @if let Some(redeem_script) = redeem_script {
+            div {
+                "Redeem script"
+                p { (redeem_script.html()) }
+            }
+        }
+        @if let Some(witness_script) = witness_script {
+            div {
+                "Witness script"
+                p { (witness_script.html()) }
+            }
+        }
+    }
+}
+
+/// `extracted` is the final transaction, if the psbt is complete
+pub fn page(psbt: &Psbt, extracted: Option<&Transaction>, parsed: &ParsedRequest) -> Markup {
+    let tx = &psbt.unsigned_tx;
+    let network_url_path = network().as_url_path();
+    let fee = psbt.fee().ok();
+    let input_plural = Plural::new("input", tx.input.len());
+    let output_plural = Plural::new("output", tx.output.len());
+    let signed = psbt
+        .inputs
+        .iter()
+        .filter(|i| i.final_script_sig.is_some() || i.final_script_witness.is_some())
+        .count();
+
+    let content = html! {
+        section {
+            hgroup {
+                h1 { "PSBT" }
+                p { (tx.input.len()) " " (input_plural) ", " (tx.output.len()) " " (output_plural) }
+            }
+
+            table class="striped" {
+                tbody {
+                    tr {
+                        th { "Unsigned txid" }
+                        td class="right" { code { (tx.compute_txid()) } }
+                    }
+                    tr {
+                        th { "Version" }
+                        td class="right" { (psbt.version) }
+                    }
+                    @if let Some(fee) = fee {
+                        (AmountRow::new_with_sat("Fee", fee.to_sat(), parsed.unit))
+                    }
+                    tr {
+                        th { "Finalized inputs" }
+                        td class="right" { (signed) " of " (tx.input.len()) }
+                    }
+                    @if let Some(extracted) = extracted {
+                        tr {
+                            th { "Extracted transaction" }
+                            td class="right" {
+                                a href=(format!("{}txhex/{}{}", network_url_path, serialize_hex(extracted), parsed.unit.link_query())) {
+                                    code { (extracted.compute_txid()) }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            h2 { "Inputs" }
+
+            table class="striped" {
+                tbody {
+                    @for (i, (input, txin)) in psbt.inputs.iter().zip(tx.input.iter()).enumerate() {
+                        @let utxo = utxo(input, txin);
+                        @let bip32: Vec<_> = input.bip32_derivation.iter().map(|(k, s)| Derivation::new(k.to_string(), s))
+                            .chain(input.tap_key_origins.iter().map(|(k, (_, s))| Derivation::new(k.to_string(), s)))
+                            .collect();
+                        tr {
+                            th class="row-index" { (i) }
+                            td {
+                                div {
+                                    "Previous outpoint"
+                                    p { (txin.previous_output.html()) }
+                                }
+                                @if let Some(utxo) = utxo {
+                                    div {
+                                        "Previous output"
+                                        @if let Some(kind) = script_type(&utxo.script_pubkey) {
+                                            " (" (kind) ")"
+                                        }
+                                        p { (parsed.unit.format(utxo.value.to_sat())) }
+                                        @if let Ok(address) = Address::from_script(&utxo.script_pubkey, network()) {
+                                            p { (address.html()) }
+                                        }
+                                        p { (utxo.script_pubkey.html()) }
+                                    }
+                                } @else {
+                                    div {
+                                        "Previous output"
+                                        p { "Not provided" }
+                                    }
+                                }
+                                @if let Some(sighash_type) = input.sighash_type {
+                                    div {
+                                        "Sighash type"
+                                        p { code { (sighash_type) } }
+                                    }
+                                }
+                                @if !input.partial_sigs.is_empty() {
+                                    div {
+                                        "Partial signatures"
+                                        @for (pk, sig) in input.partial_sigs.iter() {
+                                            p { code { (pk) } " " code { (sig.sighash_type) } }
+                                        }
+                                    }
+                                }
+                                @if let Some(sig) = input.tap_key_sig.as_ref() {
+                                    div {
+                                        "Taproot key signature"
+                                        p { code { (sig.sighash_type) } }
+                                    }
+                                }
+                                @if !input.tap_script_sigs.is_empty() {
+                                    div {
+                                        "Taproot script signatures"
+                                        @for ((pk, leaf_hash), sig) in input.tap_script_sigs.iter() {
+                                            p { code { (pk) } " " code { (leaf_hash) } " " code { (sig.sighash_type) } }
+                                        }
+                                    }
+                                }
+                                (script_rows(&input.redeem_script, &input.witness_script))
+                                (derivations(bip32))
+                                @if let Some(final_script_sig) = input.final_script_sig.as_ref() {
+                                    div {
+                                        "Final script sig"
+                                        p { (ScriptSig(final_script_sig)) }
+                                    }
+                                }
+                                @if let Some(final_script_witness) = input.final_script_witness.as_ref() {
+                                    div {
+                                        "Final witness"
+                                        p { (final_script_witness.html()) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            h2 { "Outputs" }
+
+            table class="striped" {
+                tbody {
+                    @for (i, (output, txout)) in psbt.outputs.iter().zip(tx.output.iter()).enumerate() {
+                        @let bip32: Vec<_> = output.bip32_derivation.iter().map(|(k, s)| Derivation::new(k.to_string(), s))
+                            .chain(output.tap_key_origins.iter().map(|(k, (_, s))| Derivation::new(k.to_string(), s)))
+                            .collect();
+                        tr {
+                            th class="row-index" { (i) }
+                            td {
+                                div {
+                                    "Value"
+                                    p { (parsed.unit.format(txout.value.to_sat())) }
+                                }
+                                @if let Ok(address) = Address::from_script(&txout.script_pubkey, network()) {
+                                    div {
+                                        "Address"
+                                        p { (address.html()) }
+                                    }
+                                }
+                                div {
+                                    "Script pubkey"
+                                    @if let Some(kind) = script_type(&txout.script_pubkey) {
+                                        " (" (kind) ")"
+                                    }
+                                    p { (txout.script_pubkey.html()) }
+                                }
+                                (script_rows(&output.redeem_script, &output.witness_script))
+                                (derivations(bip32))
+                            }
+                        }
+                    }
+                }
+            }
+
+            @if !parsed.response_type.is_text() {
+                h2 { "Hex" }
+                code { (psbt.serialize_hex()) }
+            }
+        }
+    };
+
+    html_page("PSBT", content, parsed)
+}
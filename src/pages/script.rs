@@ -0,0 +1,46 @@
+use bitcoin::{Address, Script};
+use maud::{html, Markup};
+
+use crate::{
+    error::Error, network, pages::tx::script_type, render::Html, req::ParsedRequest,
+    threads::index_addresses::AddressSeen,
+};
+
+use super::{address::seen_section, html_page};
+
+pub fn page(
+    script: &Script,
+    parsed: &ParsedRequest,
+    address_seen: Vec<AddressSeen>,
+) -> Result<Markup, Error> {
+    let script_type = script_type(script).unwrap_or_else(|| "Unknown".to_owned());
+    let address = Address::from_script(script, network()).ok();
+
+    let content = html! {
+        section {
+            hgroup {
+                h1 { "Script" }
+                p { (script.html()) }
+            }
+
+            table class="striped" {
+                tbody {
+                    tr {
+                        th { "Type" }
+                        td { (script_type) }
+                    }
+                    @if let Some(address) = address {
+                        tr {
+                            th { "Address" }
+                            td { (address.html()) }
+                        }
+                    }
+                }
+            }
+
+            (seen_section(address_seen, parsed.unit))
+        }
+    };
+
+    Ok(html_page("Script", content, parsed))
+}
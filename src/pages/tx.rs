@@ -1,22 +1,28 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
 use std::str::from_utf8;
+use std::sync::Arc;
 
 use bitcoin::hex::DisplayHex;
+use bitcoin::taproot::ControlBlock;
 use bitcoin::Txid;
 use bitcoin::{
-    blockdata::script::Instruction,
+    absolute::LockTime,
+    blockdata::script::{read_scriptint, Instruction},
     consensus::{encode::serialize_hex, serialize},
-    Address, Amount, BlockHash, Denomination, OutPoint, Script, ScriptBuf, Transaction, TxOut,
+    Address, BlockHash, OutPoint, Script, ScriptBuf, Transaction, TxOut, Witness, XOnlyPublicKey,
 };
 use maud::{html, Markup};
 
 use crate::{
     error::Error,
-    network,
+    globals::rows_per_page,
+    network, op_return,
     pages::size_rows,
-    render::{self, AmountRow, Html, Plural},
+    render::{self, AmountRow, Html, Plural, ScriptSig, SizeRow, Unit},
     req::ParsedRequest,
-    rpc::headers::HeightTime,
-    state::{BlockTemplate, SpendPoint},
+    rpc::{headers::HeightTime, ts_to_date_time_utc},
+    state::{duplicate_coinbase, BlockTemplate, SpendPoint},
     threads::{
         index_addresses::Height,
         update_mempool_info::{TxidWeightFee, WeightFee},
@@ -26,11 +32,12 @@ use crate::{
 
 use super::html_page;
 
-pub const IO_PER_PAGE: usize = 10;
-
 pub enum OutputStatus {
     ConfirmedSpent(Height),
     UnconfirmedSpent(SpendPoint),
+
+    /// The node reports the output is not in the utxo set, but we don't know where it is spent
+    Spent,
     Unspent,
     Unknown,
 }
@@ -44,57 +51,105 @@ pub fn page(
     output_status: Vec<OutputStatus>,
     page: usize,
     mempool_fees: BlockTemplate,
+    ancestors: Option<WeightFee>,
+    block_fee_rates: Option<Arc<Vec<f64>>>,
+    coin_ages: &[Option<u32>],
+    block_neighbors: Option<BlockNeighbors>,
     parsed: &ParsedRequest,
     user_provided: bool,
     known_tx: Option<String>,
 ) -> Result<Markup, Error> {
     let network_url_path = network().as_url_path();
-    let start = page * IO_PER_PAGE;
+    let unit = parsed.unit.link_query();
+    let io_per_page = rows_per_page();
+    let start = page * io_per_page;
     if start >= tx.input.len() && start >= tx.output.len() {
         return Err(Error::InvalidPageNumber);
     }
 
-    let last_page_input = tx.input.len().saturating_sub(1) / IO_PER_PAGE;
-    let last_page_output = tx.output.len().saturating_sub(1) / IO_PER_PAGE;
+    let last_page_input = tx.input.len().saturating_sub(1) / io_per_page;
+    let last_page_output = tx.output.len().saturating_sub(1) / io_per_page;
     log::debug!("last page {last_page_input} {last_page_output}");
 
-    let input_start = start.min(last_page_input * IO_PER_PAGE);
-    let output_start = start.min(last_page_output * IO_PER_PAGE);
+    let input_start = input_range(tx, page).start;
+    let output_start = output_range(tx, page).start;
     log::debug!("from {input_start} {output_start}");
 
     let prev_input = (page > 0 && last_page_input != 0).then(|| {
         format!(
-            "{}t/{}/{}#inputs",
+            "{}t/{}/{}{unit}#inputs",
             network_url_path,
             txid,
             (last_page_input - 1).min(page - 1)
         )
     });
     let next_input = (page < last_page_input)
-        .then(|| format!("{}t/{}/{}#inputs", network_url_path, txid, page + 1));
+        .then(|| format!("{}t/{}/{}{unit}#inputs", network_url_path, txid, page + 1));
     let separator_input = (prev_input.is_some() && next_input.is_some()).then_some(" | ");
 
     let prev_output = (page > 0 && last_page_output != 0).then(|| {
         format!(
-            "{}t/{}/{}#outputs",
+            "{}t/{}/{}{unit}#outputs",
             network_url_path,
             txid,
             (last_page_output - 1).min(page - 1)
         )
     });
     let next_output = (page < last_page_output)
-        .then(|| format!("{}t/{}/{}#outputs", network_url_path, txid, page + 1));
+        .then(|| format!("{}t/{}/{}{unit}#outputs", network_url_path, txid, page + 1));
     let separator_output = (prev_output.is_some() && next_output.is_some()).then_some(" | ");
 
     let sum_outputs: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
     let sum_inputs: u64 = prevouts.iter().map(|o| o.value.to_sat()).sum();
     let fee = sum_inputs.saturating_sub(sum_outputs); // saturating never happens on confirmed/mempool-accepted tx, but we show also user made txs
 
+    let witness_discount = witness_discount(tx.total_size(), tx.weight().to_wu() as usize)
+        .map(|d| format!("{:.2}%", d * 100.0));
+
+    // value distribution is interesting only with multiple outputs, and it's omitted in text mode
+    // to not break narrow terminals
+    let show_share = tx.output.len() > 1 && sum_outputs > 0 && !parsed.response_type.is_text();
+    let largest_output = tx.output.iter().map(|o| o.value.to_sat()).max();
+    let mut value_counts = BTreeMap::new();
+    for output in tx.output.iter() {
+        *value_counts.entry(output.value.to_sat()).or_insert(0usize) += 1;
+    }
+    // useful for CoinJoin detection, ties are resolved in favour of the highest value
+    let equal_outputs = value_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .max_by_key(|(value, count)| (*count, *value));
+
+    let (version, version_tooltip) = version_str(tx.version.0);
+    // BIP68 relative lock times are enforced only from version 2
+    let relative_lock_inputs = if tx.version.0 >= 2 && !tx.is_coinbase() {
+        tx.input
+            .iter()
+            .filter(|i| i.sequence.is_relative_lock_time())
+            .count()
+    } else {
+        0
+    };
+
+    // the coinbase script sig contains the BIP34 height and arbitrary data like pool tags
+    let coinbase = tx.input.first().filter(|_| tx.is_coinbase()).map(|input| {
+        let script_sig = &input.script_sig;
+        let bip34_height = bip34_height(script_sig).filter(|h| match height_time.as_ref() {
+            Some((_, height_time)) => *h == height_time.height as u64,
+            None => true,
+        });
+        (
+            script_sig.as_bytes().to_lower_hex_string(),
+            coinbase_ascii(script_sig.as_bytes()),
+            bip34_height,
+        )
+    });
+
     let inputs = tx
         .input
         .iter()
         .skip(input_start)
-        .take(IO_PER_PAGE)
+        .take(io_per_page)
         .zip(prevouts.iter().skip(input_start))
         .enumerate()
         .map(|(i, (input, previous_output))| {
@@ -102,11 +157,19 @@ pub fn page(
             if po == &OutPoint::null() {
                 None
             } else {
-                let link = format!("{}t/{}#o{}", network().as_url_path(), po.txid, po.vout);
-                let amount = amount_str(previous_output.value.to_sat());
+                let link = format!(
+                    "{}t/{}{unit}#o{}",
+                    network().as_url_path(),
+                    po.txid,
+                    po.vout
+                );
+                let amount = amount_str(previous_output.value.to_sat(), parsed.unit);
                 let previous_script_pubkey = (previous_output.value.to_sat() != u64::MAX)
                     .then(|| previous_output.script_pubkey.clone());
                 let previous_script_pubkey_type = script_type(&previous_output.script_pubkey);
+                let previous_address = previous_script_pubkey
+                    .as_ref()
+                    .and_then(|s| Address::from_script(s, network()).ok());
                 let script_sig = (!input.script_sig.is_empty()).then(|| input.script_sig.clone());
                 let witness = input.witness.clone();
 
@@ -117,18 +180,29 @@ pub fn page(
                     .then(|| witness.last().map(|e| ScriptBuf::from(e.to_vec())))
                     .flatten();
 
+                let taproot_script_path = previous_script_pubkey
+                    .as_ref()
+                    .map(|s| s.is_p2tr())
+                    .unwrap_or(false)
+                    .then(|| taproot_script_path(&witness))
+                    .flatten();
+
                 let sequence = format!("0x{:x}", input.sequence);
+                let coin_age = coin_ages.get(i).copied().flatten();
                 Some((
                     i + input_start,
                     po,
                     amount,
                     link,
+                    previous_address,
                     previous_script_pubkey,
                     previous_script_pubkey_type,
                     script_sig,
                     witness,
                     p2wsh_witness_script,
+                    taproot_script_path,
                     sequence,
+                    coin_age,
                 ))
             }
         });
@@ -137,13 +211,13 @@ pub fn page(
         .output
         .iter()
         .skip(output_start)
-        .take(IO_PER_PAGE)
+        .take(io_per_page)
         .enumerate()
         .zip(
             output_status
                 .into_iter()
                 .skip(output_start)
-                .take(IO_PER_PAGE),
+                .take(io_per_page),
         )
         .map(|((i, output), spent_status)| {
             let address = Address::from_script(&output.script_pubkey, network()).ok();
@@ -151,43 +225,49 @@ pub fn page(
             let output_link = match spent_status {
                 OutputStatus::ConfirmedSpent(h) => {
                     let n = network().as_url_path();
-                    Some(format!("{n}o/{txid}:{i}/{h}"))
+                    Some(format!("{n}o/{txid}:{i}/{h}{unit}"))
                 }
                 OutputStatus::UnconfirmedSpent(s) => {
                     let n = network().as_url_path();
                     let txid = s.txid();
                     let vin = s.vin();
-                    Some(format!("{n}t/{txid}#i{vin}"))
+                    Some(format!("{n}t/{txid}{unit}#i{vin}"))
                 }
-                OutputStatus::Unspent => None,
-                OutputStatus::Unknown => None,
+                OutputStatus::Spent | OutputStatus::Unspent | OutputStatus::Unknown => None,
             };
-
-            let amount = amount_str(output.value.to_sat());
+            let spent = matches!(spent_status, OutputStatus::Spent);
+
+            let amount = amount_str(output.value.to_sat(), parsed.unit);
+            let share = show_share.then(|| {
+                format!(
+                    "{:.2}%",
+                    output.value.to_sat() as f64 * 100.0 / sum_outputs as f64
+                )
+            });
             let script_pubkey = output.script_pubkey.clone();
             let script_type = script_type(&output.script_pubkey);
+            let dust_limit =
+                dust_limit(&output.script_pubkey).filter(|&limit| output.value.to_sat() < limit);
 
-            let op_return_string = output
-                .script_pubkey
-                .is_op_return()
-                .then(|| {
-                    for instruction in output.script_pubkey.instructions().flatten() {
-                        if let Instruction::PushBytes(data) = instruction {
-                            return from_utf8(data.as_bytes()).ok();
-                        }
-                    }
-                    None
-                })
-                .flatten();
+            let op_return_pushes = if output.script_pubkey.is_op_return() {
+                op_return_pushes(&output.script_pubkey)
+            } else {
+                vec![]
+            };
+            let op_return = op_return::decode(&output.script_pubkey);
 
             (
                 i + output_start,
                 address,
                 amount,
                 output_link,
+                spent,
+                share,
                 script_pubkey,
                 script_type,
-                op_return_string,
+                dust_limit,
+                op_return_pushes,
+                op_return,
             )
         });
 
@@ -206,6 +286,7 @@ pub fn page(
         .any(|i| mempool_fees.mempool.contains(&i.previous_output.txid));
 
     let block_link = if let Some((block_hash, height_time)) = height_time {
+        let duplicate_blocks = duplicate_coinbase(&txid, network());
         html! {
             @if let Some(text) = known_tx {
                 th { "Note" }
@@ -226,6 +307,37 @@ pub fn page(
                 th { "Block " (height_time.height) }
                 td class="right" { (block_hash.html()) }
             }
+
+            @if let Some(neighbors) = block_neighbors {
+                tr {
+                    th { "Position in block " (neighbors.pos) }
+                    td class="right" {
+                        @if let Some(prev) = neighbors.prev {
+                            a href=(format!("{network_url_path}t/{prev}{unit}")) { "Prev tx" }
+                        }
+                        @if neighbors.prev.is_some() && neighbors.next.is_some() {
+                            " | "
+                        }
+                        @if let Some(next) = neighbors.next {
+                            a href=(format!("{network_url_path}t/{next}{unit}")) { "Next tx" }
+                        }
+                    }
+                }
+            }
+
+            @if let Some(blocks) = duplicate_blocks {
+                tr {
+                    th { "This txid appears in two blocks (BIP30)" }
+                    td class="right" {
+                        @for (i, (height, hash)) in blocks.iter().enumerate() {
+                            @if i > 0 {
+                                " and "
+                            }
+                            a href=(format!("{network_url_path}b/{hash}{unit}")) { (height) }
+                        }
+                    }
+                }
+            }
         }
     } else {
         html! {
@@ -242,6 +354,17 @@ pub fn page(
 
                 }
             }
+
+            tr {
+                th { "Replaceable (BIP125)" }
+                td class="right" {
+                    @if tx.is_explicitly_rbf() {
+                        "Yes"
+                    } @else {
+                        "No"
+                    }
+                }
+            }
         }
     };
 
@@ -251,6 +374,9 @@ pub fn page(
             (&bytes[..500].to_lower_hex_string())
             b { "...truncated, original size " (tx.total_size()) " bytes..." }
             (&bytes[tx.total_size()-500..].to_lower_hex_string())
+            @if !user_provided {
+                " " a href=(format!("{network_url_path}t/{txid}/hex")) { "full hex" }
+            }
 
         }
     } else {
@@ -261,6 +387,11 @@ pub fn page(
         weight: tx.weight(),
         fee: fee as usize,
     };
+    let package_wf = ancestors.map(|a| WeightFee {
+        weight: a.weight + wf.weight,
+        fee: a.fee + wf.fee,
+    });
+    let block_percentile = block_fee_rates.and_then(|r| share_below(&r, wf.rate_sat_over_vb()));
 
     let content = html! {
 
@@ -274,7 +405,19 @@ pub fn page(
                 tbody {
                     (block_link)
                     @if !tx.is_coinbase() && !prevouts.iter().any(|p| p.value.to_sat() == u64::MAX) {
-                        (fee_rows( wf, last_in_block))
+                        (fee_rows(wf, last_in_block, parsed.unit))
+                        @if let Some(package_wf) = package_wf {
+                            tr {
+                                th { "Effective fee rate (with ancestors)" }
+                                td class="number" { (package_wf) }
+                            }
+                        }
+                        @if let Some(block_percentile) = block_percentile {
+                            tr {
+                                th { "Fee rate higher than" }
+                                td class="right" { (format!("{block_percentile:.0}% of the block txs")) }
+                            }
+                        }
                     }
                 }
             }
@@ -297,7 +440,7 @@ pub fn page(
             table class="striped" {
                 tbody {
                     @for val in inputs {
-                        @if let Some((i, outpoint, amount, link, previous_script_pubkey, previous_script_pubkey_type, script_sig, witness, p2wsh_witness_script, sequence)) = val {
+                        @if let Some((i, outpoint, amount, link, previous_address, previous_script_pubkey, previous_script_pubkey_type, script_sig, witness, p2wsh_witness_script, taproot_script_path, sequence, coin_age)) = val {
 
                             tr id=(format!("i{i}")) {
                                 th class="row-index" {
@@ -314,6 +457,20 @@ pub fn page(
                                         p { (outpoint.html()) }
                                     }
 
+                                    @if let Some(coin_age) = coin_age {
+                                        div {
+                                            "Coin age"
+                                            p { (coin_age) " " (Plural::new("block", coin_age as usize)) }
+                                        }
+                                    }
+
+                                    @if let Some(previous_address) = previous_address {
+                                        div {
+                                            "Previous address"
+                                            p { (previous_address.html()) }
+                                        }
+                                    }
+
                                     @if let Some(previous_script_pubkey) = previous_script_pubkey {
                                         div {
                                             "Previous script pubkey"
@@ -330,16 +487,27 @@ pub fn page(
 
                                     @if let Some(script_sig) = script_sig {
                                         div { "Script sig"}
-                                        p { (script_sig.html()) }
+                                        p { (ScriptSig(&script_sig)) }
                                     }
                                     @if !witness.is_empty() {
                                         div { "Witness"}
-                                        p { (witness.html()) }
+                                        @let taproot = previous_script_pubkey.as_ref().map_or(false, |s| s.is_p2tr());
+                                        p { (render::Witness { witness: &witness, taproot }) }
                                     }
                                     @if let Some(p2wsh_witness_script) = p2wsh_witness_script {
                                         div { "P2wsh witness script"}
                                         p { (p2wsh_witness_script.html()) }
                                     }
+                                    @if let Some(taproot_script_path) = taproot_script_path {
+                                        div { "Taproot leaf script"}
+                                        p { (taproot_script_path.leaf_script.html()) }
+                                        div { "Taproot internal key"}
+                                        p { code { (taproot_script_path.internal_key) } }
+                                        @if let Some(annex) = taproot_script_path.annex {
+                                            div { "Taproot annex"}
+                                            p { code { (annex) } }
+                                        }
+                                    }
 
                                 }
                                 td class="number" {
@@ -348,9 +516,26 @@ pub fn page(
                             }
                         }
                         @else {
-                            td { "Coinbase" }
-                            td {}
-
+                            tr id="i0" {
+                                th class="row-index" { "0" }
+                                td {
+                                    @if !parsed.response_type.is_text() {
+                                        br;
+                                    }
+                                    div { "Coinbase" }
+                                    @if let Some((hex, ascii, bip34_height)) = coinbase.as_ref() {
+                                        @if let Some(bip34_height) = bip34_height {
+                                            div { "BIP34 height" }
+                                            p { code { (bip34_height) } }
+                                        }
+                                        div { "Script sig (hex)" }
+                                        p { code { (hex) } }
+                                        div { "Script sig (ascii)" }
+                                        p { code { (ascii) } }
+                                    }
+                                }
+                                td {}
+                            }
                         }
                     }
                 }
@@ -381,7 +566,7 @@ pub fn page(
             }
             table class="striped" {
                 tbody {
-                    @for (i, address, amount, output_link, script_pubkey, script_type, op_return_string) in outputs {
+                    @for (i, address, amount, output_link, spent, share, script_pubkey, script_type, dust_limit, op_return_pushes, op_return) in outputs {
                         tr id=(format!("o{i}")) {
                             th class="row-index" {
                                 (i)
@@ -404,19 +589,41 @@ pub fn page(
                                 }
                                 p { (script_pubkey.html()) }
 
-                                @if let Some(op_return_string) = op_return_string {
-                                    div { "Op return in utf8" }
-                                    p { code { (op_return_string) } }
+                                @if let Some(op_return) = op_return.as_ref() {
+                                    div { "Op return protocol" }
+                                    p { (op_return.label) }
+                                    @for (name, value) in op_return.fields.iter() {
+                                        div { (name) }
+                                        p { code { (value) } }
+                                    }
+                                }
+                                @for (hex, utf8) in op_return_pushes {
+                                    div { "Op return (hex)" }
+                                    p { code { (hex) } }
+                                    @if let (Some(utf8), None) = (utf8, op_return.as_ref()) {
+                                        div { "Op return in utf8" }
+                                        p { code { (utf8) } }
+                                    }
                                 }
                             }
                             td class="number" {
                                 @if let Some(output_link) = output_link {
                                     a data-tooltip="Spent" href=(output_link) { (amount) }
+                                } @else if spent {
+                                    em data-tooltip="Spent" style="font-style: normal" { (amount) }
                                 } @else if script_pubkey.is_op_return() {
                                     em data-tooltip="Provably unspendable" style="font-style: normal" { (amount) }
                                 } @else {
                                     em data-tooltip="Unspent" style="font-style: normal" { (amount) }
                                 }
+                                @if let Some(share) = share {
+                                    br;
+                                    small { (share) }
+                                }
+                                @if let Some(dust_limit) = dust_limit {
+                                    br;
+                                    small data-tooltip=(format!("Below the dust limit of {dust_limit} sat for this script type")) { "dust" }
+                                }
                             }
                         }
                     }
@@ -436,13 +643,40 @@ pub fn page(
             table class="striped" {
                 tbody {
                     (size_rows(tx.total_size(), tx.weight().to_wu() as usize))
+                    @if let Some(discount) = witness_discount {
+                        (SizeRow::new("Base size", tx.base_size() as u64))
+                        (SizeRow::new("Witness size", (tx.total_size() - tx.base_size()) as u64))
+                        tr {
+                            th { "Witness discount" }
+                            td class="right" { (discount) }
+                        }
+                    }
+                    @if tx.output.len() > 1 {
+                        @if let Some(largest_output) = largest_output {
+                            (AmountRow::new_with_sat("Largest output", largest_output, parsed.unit))
+                        }
+                        @if let Some((value, count)) = equal_outputs {
+                            tr {
+                                th { "Equal-valued outputs" }
+                                td class="right" { (count) " × " (amount_str(value, parsed.unit)) }
+                            }
+                        }
+                    }
                     tr {
                         th { "Version" }
-                        td class="right" { (tx.version) }
+                        td class="right" {
+                            span data-tooltip=[version_tooltip] { (version) }
+                            @if relative_lock_inputs > 0 {
+                                br;
+                                small { (relative_lock_inputs) " " (Plural::new("input", relative_lock_inputs)) " with relative lock time" }
+                            }
+                        }
                     }
                     tr {
                         th { "Lock time" }
-                        td class="right" { (tx.lock_time.to_consensus_u32()) }
+                        td class="right" {
+                            span data-tooltip=(tx.lock_time.to_consensus_u32()) { (lock_time_str(tx.lock_time)) }
+                        }
                     }
                 }
             }
@@ -457,20 +691,77 @@ pub fn page(
     Ok(html_page("Transaction", content, parsed))
 }
 
-fn amount_str(val: u64) -> String {
+/// Fraction of the size not paid thanks to the witness discount, `None` for non-segwit txs
+fn witness_discount(total_size: usize, weight: usize) -> Option<f64> {
+    let vsize = (weight + 3) / 4;
+    (total_size * 4 != weight).then(|| (total_size - vsize) as f64 / total_size as f64)
+}
+
+/// The version with the policy or consensus rules it enables and a longer explanation
+fn version_str(version: i32) -> (String, Option<&'static str>) {
+    match version {
+        i32::MIN..=1 => (version.to_string(), None),
+        3 => (
+            "3 (TRUC / ephemeral anchors)".to_string(),
+            Some("Topologically restricted until confirmation: small size and a single unconfirmed parent or child, see BIP431. BIP68 sequence locks are enabled like in version 2"),
+        ),
+        _ => (
+            format!("{version} (BIP68 sequence locks enabled)"),
+            Some("Inputs sequence can encode a relative lock time, see BIP68"),
+        ),
+    }
+}
+
+fn amount_str(val: u64, unit: Unit) -> String {
     if val == u64::MAX {
         "Not exist".to_owned()
     } else {
-        format!(
-            "{:.8}",
-            Amount::from_sat(val).to_float_in(Denomination::Bitcoin)
-        )
+        unit.format(val)
+    }
+}
+
+struct TaprootScriptPath {
+    leaf_script: ScriptBuf,
+    internal_key: XOnlyPublicKey,
+
+    /// The annex in hex, if present
+    annex: Option<String>,
+}
+
+/// Returns the details of a taproot script path spend, `None` if it's a key path spend or the
+/// control block is invalid
+fn taproot_script_path(witness: &Witness) -> Option<TaprootScriptPath> {
+    let annex = witness.taproot_annex();
+    let stack_len = witness.len() - annex.is_some() as usize;
+    if stack_len < 2 {
+        return None;
     }
+    let control_block = ControlBlock::decode(witness.taproot_control_block()?).ok()?;
+    Some(TaprootScriptPath {
+        leaf_script: witness.tapscript()?.to_owned(),
+        internal_key: control_block.internal_key,
+        annex: annex.map(|a| a.to_lower_hex_string()),
+    })
+}
+
+/// The inputs shown in `page`, the last page is repeated when there are more output pages
+pub fn input_range(tx: &Transaction, page: usize) -> Range<usize> {
+    let io_per_page = rows_per_page();
+    let last_page_input = tx.input.len().saturating_sub(1) / io_per_page;
+    let start = (page * io_per_page).min(last_page_input * io_per_page);
+    start..tx.input.len().min(start + io_per_page)
+}
+
+pub fn output_range(tx: &Transaction, page: usize) -> Range<usize> {
+    let io_per_page = rows_per_page();
+    let last_page_output = tx.output.len().saturating_sub(1) / io_per_page;
+    let start = (page * io_per_page).min(last_page_output * io_per_page);
+    start..tx.output.len().min(start + io_per_page)
 }
 
-pub fn fee_rows(wf: WeightFee, last_in_block: Option<TxidWeightFee>) -> Markup {
+pub fn fee_rows(wf: WeightFee, last_in_block: Option<TxidWeightFee>, unit: Unit) -> Markup {
     html! {
-        (AmountRow::new_with_sat("Fee", wf.fee as u64))
+        (AmountRow::new_with_sat("Fee", wf.fee as u64, unit))
 
         tr {
             th { "Fee rate (BTC/KvB)" }
@@ -485,6 +776,84 @@ pub fn fee_rows(wf: WeightFee, last_in_block: Option<TxidWeightFee>) -> Markup {
     }
 }
 
+/// Returns every data push of an op return script, in hex and in utf8 if the bytes are valid
+/// printable utf8
+fn op_return_pushes(script: &Script) -> Vec<(String, Option<&str>)> {
+    script
+        .instructions()
+        .flatten()
+        .filter_map(|instruction| match instruction {
+            Instruction::PushBytes(data) if !data.is_empty() => {
+                let bytes = data.as_bytes();
+                let utf8 = from_utf8(bytes)
+                    .ok()
+                    .filter(|s| !s.chars().any(char::is_control));
+                Some((bytes.to_lower_hex_string(), utf8))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Position of a tx in its block, with the txs before and after it
+#[derive(Debug, PartialEq, Eq)]
+pub struct BlockNeighbors {
+    pub pos: usize,
+    pub prev: Option<Txid>,
+    pub next: Option<Txid>,
+}
+
+impl BlockNeighbors {
+    /// `None` if `txid` is not in `block_txids`
+    pub fn new(block_txids: &[Txid], txid: Txid) -> Option<Self> {
+        let pos = block_txids.iter().position(|t| *t == txid)?;
+        Some(BlockNeighbors {
+            pos,
+            prev: pos.checked_sub(1).map(|i| block_txids[i]),
+            next: block_txids.get(pos + 1).copied(),
+        })
+    }
+}
+
+/// Percentage of `sorted_rates` strictly lower than `rate`, `None` if there are no rates
+fn share_below(sorted_rates: &[f64], rate: f64) -> Option<f64> {
+    if sorted_rates.is_empty() {
+        return None;
+    }
+    let below = sorted_rates.partition_point(|r| *r < rate);
+    Some(below as f64 * 100.0 / sorted_rates.len() as f64)
+}
+
+/// Values below 500_000_000 are block heights, otherwise unix timestamps
+fn lock_time_str(lock_time: LockTime) -> String {
+    match lock_time {
+        LockTime::Blocks(height) if height.to_consensus_u32() == 0 => "0 (no lock)".to_string(),
+        LockTime::Blocks(height) => format!("block height {height}"),
+        LockTime::Seconds(time) => ts_to_date_time_utc(time.to_consensus_u32()),
+    }
+}
+
+/// Printable ascii bytes are kept, others become dots, like in a hex dump
+fn coinbase_ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| match b {
+            0x20..=0x7e => *b as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+/// The height pushed as first element of the coinbase script sig, see BIP34
+fn bip34_height(script_sig: &Script) -> Option<u64> {
+    match script_sig.instructions_minimal().next()? {
+        Ok(Instruction::PushBytes(data)) => read_scriptint(data.as_bytes())
+            .ok()
+            .and_then(|h| u64::try_from(h).ok()),
+        _ => None,
+    }
+}
+
 pub fn script_type(script: &Script) -> Option<String> {
     let kind = if script.is_p2pk() {
         "p2pk"
@@ -509,3 +878,123 @@ pub fn script_type(script: &Script) -> Option<String> {
         Some(kind.to_string())
     }
 }
+
+/// The minimum value for an output with `script` to be relayed by nodes with the default dust
+/// relay fee of 3 sat/vB, `None` for op returns which are not subject to the limit.
+/// Eg. 546 sat for p2pkh, 294 for p2wpkh, 330 for p2tr
+fn dust_limit(script: &Script) -> Option<u64> {
+    if script.is_op_return() {
+        None
+    } else {
+        Some(script.minimal_non_dust().to_sat())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bitcoin::{absolute::LockTime, hashes::Hash, ScriptBuf, Txid};
+
+    use super::{
+        bip34_height, coinbase_ascii, dust_limit, lock_time_str, share_below, version_str,
+        witness_discount, BlockNeighbors,
+    };
+
+    #[test]
+    fn test_version_str() {
+        assert_eq!(version_str(1), ("1".to_string(), None));
+        assert!(version_str(2).0.contains("BIP68"));
+        assert!(version_str(3).0.contains("TRUC"));
+    }
+
+    #[test]
+    fn test_dust_limit() {
+        let dust_limit_hex = |hex: &str| dust_limit(&ScriptBuf::from_hex(hex).unwrap());
+        assert_eq!(
+            dust_limit_hex("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac"),
+            Some(546)
+        );
+        assert_eq!(
+            dust_limit_hex("a914751e76e8199196d454941c45d1b3a323f1433bd687"),
+            Some(540)
+        );
+        assert_eq!(
+            dust_limit_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+            Some(294)
+        );
+        assert_eq!(
+            dust_limit_hex("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"),
+            Some(330)
+        );
+        assert_eq!(dust_limit_hex("6a0b68656c6c6f20776f726c64"), None);
+    }
+
+    #[test]
+    fn test_coinbase_script_sig() {
+        // height 800_000 push followed by some data
+        let script_sig = ScriptBuf::from_hex("0300350c0120130909506f6f6c2f").unwrap();
+        assert_eq!(bip34_height(&script_sig), Some(800_000));
+        assert_eq!(coinbase_ascii(script_sig.as_bytes()), "..5.. ...Pool/");
+
+        assert_eq!(bip34_height(&ScriptBuf::new()), None);
+    }
+
+    #[test]
+    fn test_lock_time_str() {
+        assert_eq!(lock_time_str(LockTime::from_consensus(0)), "0 (no lock)");
+        assert_eq!(
+            lock_time_str(LockTime::from_consensus(800_000)),
+            "block height 800000"
+        );
+        assert_eq!(
+            lock_time_str(LockTime::from_consensus(1_668_757_983)),
+            "2022-11-18 07:53:03 UTC"
+        );
+    }
+
+    #[test]
+    fn test_share_below() {
+        assert_eq!(share_below(&[], 1.0), None);
+        let rates = [1.0, 2.0, 2.0, 5.0, 10.0];
+        assert_eq!(share_below(&rates, 0.5), Some(0.0));
+        assert_eq!(share_below(&rates, 2.0), Some(20.0));
+        assert_eq!(share_below(&rates, 3.0), Some(60.0));
+        assert_eq!(share_below(&rates, 11.0), Some(100.0));
+    }
+
+    #[test]
+    fn test_witness_discount() {
+        assert_eq!(witness_discount(200, 800), None);
+        // 100 bytes of witness, 25 vbytes
+        assert_eq!(witness_discount(200, 100 * 4 + 100), Some(0.375));
+    }
+
+    #[test]
+    fn test_block_neighbors() {
+        let txids: Vec<_> = (0u8..3).map(|i| Txid::from_byte_array([i; 32])).collect();
+        assert_eq!(
+            BlockNeighbors::new(&txids, Txid::all_zeros()).unwrap().pos,
+            0
+        );
+        assert_eq!(
+            BlockNeighbors::new(&txids[..1], txids[0]),
+            Some(BlockNeighbors {
+                pos: 0,
+                prev: None,
+                next: None
+            })
+        );
+        assert_eq!(
+            BlockNeighbors::new(&txids, txids[1]),
+            Some(BlockNeighbors {
+                pos: 1,
+                prev: Some(txids[0]),
+                next: Some(txids[2])
+            })
+        );
+        assert_eq!(BlockNeighbors::new(&txids, txids[2]).unwrap().next, None);
+        assert_eq!(
+            BlockNeighbors::new(&txids, Txid::from_byte_array([9; 32])),
+            None
+        );
+    }
+}
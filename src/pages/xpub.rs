@@ -0,0 +1,64 @@
+use bitcoin::Address;
+use maud::{html, Markup};
+
+use crate::{
+    render::{Html, Plural},
+    req::ParsedRequest,
+    xpub::{ExtendedKey, ScriptKind},
+};
+
+use super::html_page;
+
+/// A derived address with the number of blocks funding it according to the address index
+pub struct Derived {
+    pub path: String,
+    pub address: Address,
+    pub funding_count: usize,
+}
+
+pub fn page(key: &ExtendedKey, derived: &[Derived], parsed: &ParsedRequest) -> Markup {
+    let kind = match key.kind {
+        ScriptKind::P2pkh => "p2pkh",
+        ScriptKind::P2shP2wpkh => "p2sh-p2wpkh",
+        ScriptKind::P2wpkh => "p2wpkh",
+    };
+    let used = derived.iter().filter(|d| d.funding_count > 0).count();
+
+    let content = html! {
+        section {
+            hgroup {
+                h1 { "Extended public key" }
+                p { code { (key) } }
+            }
+
+            p { (derived.len()) " " (kind) " addresses derived, " (used) " used" }
+
+            table class="striped" {
+                thead {
+                    tr {
+                        th { "Path" }
+                        th { "Address" }
+                        th class="right" { "Funding" }
+                    }
+                }
+                tbody {
+                    @for d in derived {
+                        tr {
+                            th class="row-index" { (d.path) }
+                            td { (d.address.html()) }
+                            td class="number" {
+                                @if d.funding_count > 0 {
+                                    (d.funding_count) " " (Plural::new("block", d.funding_count))
+                                } @else {
+                                    "-"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    html_page("Extended public key", content, parsed)
+}
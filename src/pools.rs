@@ -0,0 +1,58 @@
+use bitcoin::Transaction;
+use serde::Deserialize;
+
+use crate::network;
+
+/// A mining pool, recognized by its tag in the coinbase script sig
+#[derive(Debug, Deserialize)]
+pub struct Pool {
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+/// Pools are mainnet only, other networks miners are not interesting
+pub fn load() -> Vec<Pool> {
+    if network() != bitcoin::Network::Bitcoin {
+        return vec![];
+    }
+    let content = include_str!("mining-pools.json");
+    serde_json::from_str(content).expect("valid json")
+}
+
+/// The name of the pool which mined `coinbase`, if known
+pub fn miner<'a>(pools: &'a [Pool], coinbase: &Transaction) -> Option<&'a str> {
+    let script_sig = coinbase.input.first()?.script_sig.as_bytes();
+    pools
+        .iter()
+        .find(|pool| {
+            pool.tags
+                .iter()
+                .any(|tag| contains(script_sig, tag.as_bytes()))
+        })
+        .map(|pool| pool.name.as_str())
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty() && haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{contains, Pool};
+
+    #[test]
+    fn test_pools_json() {
+        let pools: Vec<Pool> = serde_json::from_str(include_str!("mining-pools.json")).unwrap();
+        assert!(pools.iter().all(|p| !p.tags.is_empty()));
+    }
+
+    #[test]
+    fn test_contains() {
+        assert!(contains(
+            b"\x03\x00\x35\x0c/Foundry USA Pool/",
+            b"Foundry USA Pool"
+        ));
+        assert!(!contains(b"Foundry", b"Foundry USA Pool"));
+        assert!(!contains(b"Foundry", b""));
+    }
+}
@@ -0,0 +1,128 @@
+//! Token bucket rate limiter keyed by client ip.
+//!
+//! Every client has a bucket of `burst` tokens refilled at `per_sec` tokens per second, a request
+//! takes a token and is refused if the bucket is empty. Buckets of clients idle long enough to be
+//! full again are equivalent to missing ones and are removed by [`RateLimiter::prune`].
+//!
+//! IPv6 clients usually own a whole /64, they share the bucket of their /64 prefix.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv6Addr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+pub(crate) struct RateLimiter {
+    per_sec: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant, per_sec: f64, burst: f64) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_sec).min(burst);
+        self.last = now;
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new(per_sec: f64, burst: u32) -> Self {
+        RateLimiter {
+            per_sec,
+            burst: burst as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token from the bucket of `ip`, if empty returns how long to wait for the next one
+    pub(crate) fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().expect("not poisoned");
+        let bucket = buckets.entry(bucket_key(ip)).or_insert(Bucket {
+            tokens: self.burst,
+            last: now,
+        });
+        bucket.refill(now, self.per_sec, self.burst);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_sec,
+            ))
+        }
+    }
+
+    /// Remove the buckets full at `now`
+    pub(crate) fn prune(&self, now: Instant) {
+        let mut buckets = self.buckets.lock().expect("not poisoned");
+        buckets.retain(|_, bucket| {
+            bucket.refill(now, self.per_sec, self.burst);
+            bucket.tokens < self.burst
+        });
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.buckets.lock().expect("not poisoned").len()
+    }
+}
+
+/// The ip itself for IPv4, the /64 prefix for IPv6
+fn bucket_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(v6) => IpAddr::V6(Ipv6Addr::from(
+            u128::from(v6) & 0xffff_ffff_ffff_ffff_0000_0000_0000_0000,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RateLimiter;
+    use std::{
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2.0, 3);
+        let a = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let b = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check(a, now).is_ok());
+        }
+        assert_eq!(limiter.check(a, now), Err(Duration::from_millis(500)));
+        assert!(limiter.check(b, now).is_ok());
+
+        // same /64
+        let c = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 1));
+        let d = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0xffff, 0, 0, 2));
+        for _ in 0..3 {
+            assert!(limiter.check(c, now).is_ok());
+        }
+        assert!(limiter.check(d, now).is_err());
+
+        let later = now + Duration::from_millis(500);
+        assert!(limiter.check(a, later).is_ok());
+        assert!(limiter.check(a, later).is_err());
+
+        // b is full again after half a second, a and c after 1.5 seconds
+        limiter.prune(later);
+        assert_eq!(limiter.len(), 2);
+        limiter.prune(now + Duration::from_secs(1));
+        assert_eq!(limiter.len(), 2);
+        limiter.prune(now + Duration::from_secs(2));
+        assert_eq!(limiter.len(), 0);
+    }
+}
@@ -0,0 +1,134 @@
+use maud::{html, Render};
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Seconds in the window considered by [`Activity`]
+pub const ACTIVITY_WINDOW: u32 = 24 * 60 * 60;
+
+/// Recent network activity, as text so that it needs no images nor javascript
+pub struct Activity {
+    /// Number of blocks in the last 24 hours
+    blocks: usize,
+
+    /// Average seconds between the blocks in the last 24 hours, `None` with less than 2 blocks
+    avg_interval: Option<u32>,
+
+    /// Blocks found in every hour of the last 24, oldest first
+    blocks_per_hour: Vec<f64>,
+
+    /// Hourly samples of the median fee rate of the next block in sat/vB, oldest first
+    fee_trend: Vec<f64>,
+}
+
+impl Activity {
+    /// `block_times` are the timestamps of the most recent blocks, `now` is the current unix time
+    pub fn new(block_times: &[u32], now: u32, fee_trend: Vec<f64>) -> Self {
+        let window_start = now.saturating_sub(ACTIVITY_WINDOW);
+        let mut in_window: Vec<u32> = block_times
+            .iter()
+            .copied()
+            .filter(|t| *t > window_start)
+            .collect();
+        in_window.sort_unstable();
+
+        let avg_interval = match (in_window.first(), in_window.last()) {
+            (Some(first), Some(last)) if in_window.len() > 1 => {
+                Some((last - first) / (in_window.len() as u32 - 1))
+            }
+            _ => None,
+        };
+
+        let mut blocks_per_hour = vec![0.0; 24];
+        for t in in_window.iter() {
+            let hours_ago = (now.saturating_sub(*t) / 3600).min(23) as usize;
+            blocks_per_hour[23 - hours_ago] += 1.0;
+        }
+
+        Self {
+            blocks: in_window.len(),
+            avg_interval,
+            blocks_per_hour,
+            fee_trend,
+        }
+    }
+}
+
+/// Values as unicode bars scaled between the minimum and the maximum, like `▁▃█▅`
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    values
+        .iter()
+        .map(|v| {
+            if range > 0.0 {
+                let i = ((v - min) / range * (BARS.len() - 1) as f64).round() as usize;
+                BARS[i.min(BARS.len() - 1)]
+            } else {
+                BARS[0]
+            }
+        })
+        .collect()
+}
+
+impl Render for Activity {
+    fn render(&self) -> maud::Markup {
+        let fee_range = self
+            .fee_trend
+            .iter()
+            .copied()
+            .fold(None, |acc: Option<(f64, f64)>, v| match acc {
+                Some((min, max)) => Some((min.min(v), max.max(v))),
+                None => Some((v, v)),
+            });
+        html! {
+            tr {
+                th { "Blocks in the last 24h" }
+                td class="right" { (self.blocks) }
+            }
+            @if let Some(avg_interval) = self.avg_interval {
+                tr {
+                    th { "Average block interval" }
+                    td class="right" { (avg_interval / 60) "m " (avg_interval % 60) "s" }
+                }
+            }
+            tr {
+                th { "Blocks per hour" }
+                td class="right" { code { (sparkline(&self.blocks_per_hour)) } }
+            }
+            @if let Some((min, max)) = fee_range {
+                @if self.fee_trend.len() > 1 {
+                    tr {
+                        th { "Next block median fee trend" }
+                        td class="right" {
+                            code { (sparkline(&self.fee_trend)) }
+                            " " (format!("{min:.1}-{max:.1} sat/vB"))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sparkline, Activity};
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[]), "");
+        assert_eq!(sparkline(&[1.0, 1.0]), "▁▁");
+        assert_eq!(sparkline(&[0.0, 7.0, 3.5, 1.0]), "▁█▅▂");
+    }
+
+    #[test]
+    fn test_activity() {
+        let now = 100_000;
+        let activity = Activity::new(&[now - 600, now - 1800, now - 4000, 1], now, vec![]);
+        assert_eq!(activity.blocks, 3);
+        assert_eq!(activity.avg_interval, Some(1700));
+        assert_eq!(activity.blocks_per_hour[23], 2.0);
+        assert_eq!(activity.blocks_per_hour[22], 1.0);
+    }
+}
@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use super::Html;
+use super::{Html, Unit};
 use crate::{network, NetworkExt};
 use maud::{html, Render};
 
@@ -8,7 +8,8 @@ pub(crate) struct Address<'a>(&'a bitcoin::Address);
 struct Link<'a>(&'a bitcoin::Address);
 impl<'a> Display for Link<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}a/{}", network().as_url_path(), self.0)
+        let unit = Unit::current().link_query();
+        write!(f, "{}a/{}{unit}", network().as_url_path(), self.0)
     }
 }
 
@@ -4,15 +4,20 @@ use crate::{render::Html, threads::index_addresses::AddressSeen};
 
 impl Render for AddressSeen {
     fn render(&self) -> maud::Markup {
+        let funding = &self.funding.height_time;
         html! {
 
-            div { "Funding @ " (self.funding.height_time.date_time_utc())}
+            div { "Funding at height " (funding.height) " @ " (funding.date_time_utc())}
             p { (self.funding.out_point.html()) }
 
             @if let Some(spending) = self.spending.as_ref() {
-                div { "Spending @ " (spending.height_time.date_time_utc())}
-                    p { (spending) }
-                }
+                div { "Spent at height " (spending.height_time.height) " @ " (spending.height_time.date_time_utc()) " by" }
+                p { (spending) }
+            } @else if let Some(height) = self.spent_height {
+                div { "Spent at height " (height) }
+            } @else {
+                div { "Unspent" }
+            }
         }
     }
 }
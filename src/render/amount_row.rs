@@ -1,31 +1,93 @@
-use std::fmt::Display;
+use std::{fmt::Display, future::Future};
 
 use bitcoin::Denomination;
 use maud::{html, Render};
 
+/// The denomination used to render amounts, chosen with the `unit` query param
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Unit {
+    /// Bitcoin with 8 decimals, like `0.00012345`
+    #[default]
+    Btc,
+
+    /// Integer satoshis with thousands separators, like `12,345 sat`
+    Sat,
+}
+
+tokio::task_local! {
+    static UNIT: Unit;
+}
+
+impl Unit {
+    /// Run `f` with `self` as the [`Unit::current`], so that links rendered without the request
+    /// at hand keep it
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        UNIT.scope(self, f).await
+    }
+
+    /// The unit of the request being served, the default outside [`Unit::scope`]
+    pub fn current() -> Unit {
+        UNIT.try_with(|unit| *unit).unwrap_or_default()
+    }
+
+    /// The query param keeping this unit in the links, `None` for the default unit
+    pub fn param(self) -> Option<&'static str> {
+        match self {
+            Unit::Btc => None,
+            Unit::Sat => Some("unit=sat"),
+        }
+    }
+
+    /// Query string to append to links without one to keep this unit, eg. `?unit=sat`, empty for
+    /// the default unit
+    pub fn link_query(self) -> &'static str {
+        match self {
+            Unit::Btc => "",
+            Unit::Sat => "?unit=sat",
+        }
+    }
+
+    pub fn format(self, sat: u64) -> String {
+        match self {
+            Unit::Btc => format!(
+                "{:.8}",
+                bitcoin::Amount::from_sat(sat).to_float_in(Denomination::Bitcoin)
+            ),
+            Unit::Sat => format!("{} sat", thousands(sat)),
+        }
+    }
+}
+
+/// `1234567` becomes `1,234,567`
+fn thousands(val: u64) -> String {
+    let digits = val.to_string();
+    let mut result = String::with_capacity(digits.len() * 4 / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i != 0 && (digits.len() - i) % 3 == 0 {
+            result.push(',');
+        }
+        result.push(c);
+    }
+    result
+}
+
 pub struct AmountRow<'a> {
     title: &'a str,
     amount: Amount,
 }
 
-struct Amount(bitcoin::Amount);
+struct Amount(bitcoin::Amount, Unit);
 impl Display for Amount {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:.8}", self.0.to_float_in(Denomination::Bitcoin))
+        write!(f, "{}", self.1.format(self.0.to_sat()))
     }
 }
 
 impl<'a> AmountRow<'a> {
-    pub fn new_with_sat(title: &'a str, amount: u64) -> Self {
-        Self {
-            title,
-            amount: Amount(bitcoin::Amount::from_sat(amount)),
-        }
-    }
-    pub fn new_with_btc(title: &'a str, amount: f64) -> Self {
+    pub fn new_with_sat(title: &'a str, amount: u64, unit: Unit) -> Self {
         Self {
             title,
-            amount: Amount(bitcoin::Amount::from_float_in(amount, Denomination::Bitcoin).unwrap()),
+            amount: Amount(bitcoin::Amount::from_sat(amount), unit),
         }
     }
 }
@@ -40,3 +102,26 @@ impl<'a> Render for AmountRow<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Unit;
+
+    #[test]
+    fn test_unit_format() {
+        assert_eq!(Unit::Btc.format(12_345), "0.00012345");
+        assert_eq!(Unit::Sat.format(0), "0 sat");
+        assert_eq!(Unit::Sat.format(999), "999 sat");
+        assert_eq!(Unit::Sat.format(1_000), "1,000 sat");
+        assert_eq!(Unit::Sat.format(123_456_789), "123,456,789 sat");
+    }
+
+    #[tokio::test]
+    async fn test_unit_scope() {
+        assert_eq!(Unit::current(), Unit::Btc);
+        let current = Unit::Sat.scope(async { Unit::current() }).await;
+        assert_eq!(current, Unit::Sat);
+        assert_eq!(current.link_query(), "?unit=sat");
+        assert_eq!(Unit::Btc.link_query(), "");
+    }
+}
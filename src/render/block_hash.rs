@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use super::Html;
+use super::{GroupedHex, Html, Unit};
 use crate::{globals::network, NetworkExt};
 use maud::{html, Render};
 
@@ -9,7 +9,8 @@ pub struct BlockHash(pub bitcoin::BlockHash, pub bool);
 struct Link(bitcoin::BlockHash);
 impl Display for Link {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}b/{:x}", network().as_url_path(), self.0)
+        let unit = Unit::current().link_query();
+        write!(f, "{}b/{:x}{unit}", network().as_url_path(), self.0)
     }
 }
 
@@ -18,10 +19,10 @@ impl Render for BlockHash {
         if self.1 {
             let link = Link(self.0);
             html! {
-                a href=(link) { code { (self.0) } }
+                a href=(link) { code { (GroupedHex(self.0)) } }
             }
         } else {
-            html! { code { (self.0) } }
+            html! { code { (GroupedHex(self.0)) } }
         }
     }
 }
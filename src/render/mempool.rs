@@ -1,14 +1,17 @@
 use super::Html;
 use crate::{
-    render::{plural::Plural, AmountRow, SizeRow},
+    network,
+    render::{plural::Plural, AmountRow, SizeRow, Unit},
     rpc::mempool::MempoolInfo,
     state::BlockTemplate,
-    threads::update_mempool_info::WeightFee,
+    threads::update_mempool_info::{WeightFee, FEE_ESTIMATE_TARGETS},
+    NetworkExt,
 };
 use maud::{html, Render};
 
 pub struct MempoolSection {
     pub info: MempoolInfo,
+    pub unit: Unit,
 }
 
 impl Render for MempoolSection {
@@ -20,15 +23,22 @@ impl Render for MempoolSection {
             None
         };
 
+        let total_fee = bitcoin::Amount::from_btc(self.info.total_fee).map_or(0, |a| a.to_sat());
+        let mempool_link = format!(
+            "{}mempool{}",
+            network().as_url_path(),
+            self.unit.link_query()
+        );
+
         html! {
             hgroup {
-                h2 { "Mempool" }
+                h2 { a href=(mempool_link) { "Mempool" } }
                 p { (self.info.size) " " (transaction_s) }
             }
 
             table class="striped" {
                 tbody {
-                    (AmountRow::new_with_btc("Total fees (BTC)", self.info.total_fee))
+                    (AmountRow::new_with_sat("Total fees", total_fee, self.unit))
 
                     @if let Some(mempoolminfee) = mempoolminfee {
                         tr {
@@ -48,7 +58,31 @@ impl Render for MempoolSection {
 
 impl Render for BlockTemplate {
     fn render(&self) -> maud::Markup {
+        let fee_estimates: Vec<_> = FEE_ESTIMATE_TARGETS
+            .iter()
+            .zip(self.fee_estimates.iter())
+            .filter_map(|(blocks, estimate)| {
+                let target = match blocks {
+                    1 => "Next block".to_string(),
+                    n => format!("~{n} blocks"),
+                };
+                estimate.as_ref().map(|e| (target, e))
+            })
+            .collect();
         html! {
+            @if !fee_estimates.is_empty() {
+                h2 { "Fee estimation" }
+                table class="striped" {
+                    tbody {
+                        @for (target, estimate) in fee_estimates.iter() {
+                            tr {
+                                th { (target) }
+                                td class="number" { (estimate) }
+                            }
+                        }
+                    }
+                }
+            }
 
             @if let Some(transactions) = self.transactions.as_ref()  {
                 hgroup {
@@ -1,3 +1,8 @@
+use std::fmt::Display;
+
+use maud::{html, Render};
+
+mod activity;
 mod address;
 mod address_seen;
 mod amount_row;
@@ -6,19 +11,43 @@ mod human_bytes;
 mod mempool;
 mod outpoint;
 mod plural;
+mod retarget;
 mod script;
+mod signature;
 mod size_row;
 mod spending;
 mod txid;
 mod witness;
 
-pub use amount_row::AmountRow;
+pub use activity::{Activity, ACTIVITY_WINDOW};
+pub use amount_row::{AmountRow, Unit};
 pub use block_hash::BlockHash;
+pub use human_bytes::HumanBytes;
 pub use mempool::MempoolSection;
 pub use plural::Plural;
+pub use retarget::{Retarget, RETARGET_INTERVAL};
+pub use script::ScriptSig;
 pub use size_row::SizeRow;
 pub use txid::Txid;
+pub use witness::Witness;
 
 pub trait Html {
     fn html(&self) -> maud::Markup;
 }
+
+/// Renders a hex string in groups of 4 chars, so that CSS can space them while select-and-copy
+/// still yields the unbroken string
+pub struct GroupedHex<T>(pub T);
+
+impl<T: Display> Render for GroupedHex<T> {
+    fn render(&self) -> maud::Markup {
+        let hex = self.0.to_string();
+        html! {
+            span class="grouped" {
+                @for group in hex.as_bytes().chunks(4) {
+                    span { (std::str::from_utf8(group).expect("hex is ascii")) }
+                }
+            }
+        }
+    }
+}
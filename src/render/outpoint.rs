@@ -1,7 +1,10 @@
 use std::fmt::Display;
 
-use super::Html;
-use crate::{globals::network, pages::tx::IO_PER_PAGE, NetworkExt};
+use super::{Html, Unit};
+use crate::{
+    globals::{network, rows_per_page},
+    NetworkExt,
+};
 use maud::{html, Render};
 
 pub struct OutPoint(pub bitcoin::OutPoint);
@@ -10,11 +13,11 @@ struct Link<'a>(&'a bitcoin::OutPoint);
 impl<'a> Display for Link<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}t/{}", network().as_url_path(), self.0.txid)?;
-        let page = self.0.vout as usize / IO_PER_PAGE;
+        let page = self.0.vout as usize / rows_per_page();
         if page > 0 {
             write!(f, "/{}", page)?;
         }
-        write!(f, "#o{}", self.0.vout)
+        write!(f, "{}#o{}", Unit::current().link_query(), self.0.vout)
     }
 }
 
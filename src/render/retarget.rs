@@ -0,0 +1,50 @@
+use maud::{html, Render};
+
+/// Number of blocks between difficulty adjustments
+pub const RETARGET_INTERVAL: u32 = 2016;
+
+/// Seconds expected between blocks
+const TARGET_SPACING: u32 = 600;
+
+pub struct Retarget {
+    blocks_remaining: u32,
+
+    /// Estimated next difficulty and adjustment percentage, `None` at the first block of the period
+    estimate: Option<(f64, f64)>,
+}
+
+impl Retarget {
+    /// `period_start_time` is the timestamp of the first block of the current difficulty period
+    pub fn new(tip_height: u32, tip_time: u32, period_start_time: u32, difficulty: f64) -> Self {
+        let blocks_in_period = tip_height % RETARGET_INTERVAL;
+        let blocks_remaining = RETARGET_INTERVAL - blocks_in_period;
+        let actual = tip_time.saturating_sub(period_start_time);
+        let estimate = (blocks_in_period > 0 && actual > 0).then(|| {
+            let expected = (blocks_in_period * TARGET_SPACING) as f64;
+            // consensus rules limit the adjustment to a factor of 4 in both directions
+            let factor = (expected / actual as f64).clamp(0.25, 4.0);
+            (difficulty * factor, (factor - 1.0) * 100.0)
+        });
+        Self {
+            blocks_remaining,
+            estimate,
+        }
+    }
+}
+
+impl Render for Retarget {
+    fn render(&self) -> maud::Markup {
+        html! {
+            tr {
+                th { "Blocks until retarget" }
+                td class="right" { (self.blocks_remaining) }
+            }
+            @if let Some((difficulty, percentage)) = self.estimate {
+                tr {
+                    th { "Estimated next difficulty" }
+                    td class="right" { (format!("{difficulty:.0} ({percentage:+.2}%)")) }
+                }
+            }
+        }
+    }
+}
@@ -1,35 +1,183 @@
-use super::Html;
-use maud::{html, Render};
+use super::{signature::sighash, Html};
+use bitcoin::hex::FromHex;
+use maud::{html, Markup, Render};
 
 pub(crate) struct Script<'a>(&'a bitcoin::Script);
 
+/// A script sig, rendered like a [`Script`] with signature pushes annotated by their sighash type
+pub struct ScriptSig<'a>(pub &'a bitcoin::Script);
+
 impl<'a> Render for Script<'a> {
     fn render(&self) -> maud::Markup {
-        let asm = if self.0.is_empty() {
-            "<empty>".to_owned()
-        } else {
-            self.0.to_asm_string()
-        };
-        let pieces = asm.split(' ');
-        html! {
-            code {
-                @for (i, piece) in pieces.enumerate() {
-                    @if i != 0 {
+        render_asm(self.0, false)
+    }
+}
+
+impl<'a> Render for ScriptSig<'a> {
+    fn render(&self) -> maud::Markup {
+        render_asm(self.0, true)
+    }
+}
+
+fn render_asm(script: &bitcoin::Script, annotate_sighash: bool) -> Markup {
+    let asm = if script.is_empty() {
+        "<empty>".to_owned()
+    } else {
+        script.to_asm_string()
+    };
+    let pieces = asm.split(' ');
+    html! {
+        code {
+            @for (i, piece) in pieces.enumerate() {
+                @if i != 0 {
+                    " "
+                }
+                @if piece.starts_with("OP_") {
+                    span class="script" data-tooltip=[opcode_description(piece)] { (piece) }
+                } @else {
+                    (piece)
+                    @if let Some(sighash) = annotate_sighash.then(|| piece_sighash(piece)).flatten() {
                         " "
+                        span class="script" { "[" (sighash) "]" }
                     }
-                    @if piece.starts_with("OP_") {
-                        span class="script" { (piece) }
-                    } @else {
-                        (piece)
-                    }
-
                 }
 
             }
+
         }
     }
 }
 
+/// Short descriptions of the opcodes, as they appear in the asm
+const OPCODE_DESCRIPTIONS: &[(&str, &str)] = &[
+    ("OP_0", "pushes an empty array"),
+    ("OP_PUSHDATA1", "pushes the next bytes, length in 1 byte"),
+    ("OP_PUSHDATA2", "pushes the next bytes, length in 2 bytes"),
+    ("OP_PUSHDATA4", "pushes the next bytes, length in 4 bytes"),
+    ("OP_PUSHNUM_NEG1", "pushes -1"),
+    ("OP_NOP", "does nothing"),
+    ("OP_IF", "executes the following if the top item is true"),
+    (
+        "OP_NOTIF",
+        "executes the following if the top item is false",
+    ),
+    (
+        "OP_ELSE",
+        "executes the following if the previous branch was not executed",
+    ),
+    ("OP_ENDIF", "ends an if/else block"),
+    ("OP_VERIFY", "fails if the top item is false"),
+    (
+        "OP_RETURN",
+        "fails, marks the output as unspendable data carrier",
+    ),
+    ("OP_TOALTSTACK", "moves the top item to the alt stack"),
+    (
+        "OP_FROMALTSTACK",
+        "moves the top item of the alt stack to the stack",
+    ),
+    ("OP_IFDUP", "duplicates the top item if not zero"),
+    ("OP_DEPTH", "pushes the number of items in the stack"),
+    ("OP_DROP", "removes the top item"),
+    ("OP_2DROP", "removes the top two items"),
+    ("OP_DUP", "duplicates the top item"),
+    ("OP_2DUP", "duplicates the top two items"),
+    ("OP_3DUP", "duplicates the top three items"),
+    ("OP_NIP", "removes the second item"),
+    ("OP_OVER", "copies the second item to the top"),
+    ("OP_PICK", "copies the item n back to the top"),
+    ("OP_ROLL", "moves the item n back to the top"),
+    ("OP_ROT", "rotates the top three items"),
+    ("OP_SWAP", "swaps the top two items"),
+    ("OP_TUCK", "copies the top item before the second"),
+    ("OP_SIZE", "pushes the length of the top item"),
+    ("OP_EQUAL", "pushes true if the top two items are equal"),
+    ("OP_EQUALVERIFY", "fails if the top two items are not equal"),
+    ("OP_1ADD", "adds 1 to the top item"),
+    ("OP_1SUB", "subtracts 1 from the top item"),
+    ("OP_NOT", "flips the top item between 0 and 1"),
+    ("OP_0NOTEQUAL", "pushes true if the top item is not 0"),
+    ("OP_ADD", "adds the top two items"),
+    ("OP_SUB", "subtracts the top item from the second"),
+    ("OP_BOOLAND", "pushes true if both top items are true"),
+    (
+        "OP_BOOLOR",
+        "pushes true if any of the top two items is true",
+    ),
+    (
+        "OP_NUMEQUAL",
+        "pushes true if the top two numbers are equal",
+    ),
+    (
+        "OP_NUMEQUALVERIFY",
+        "fails if the top two numbers are not equal",
+    ),
+    (
+        "OP_LESSTHAN",
+        "pushes true if the second item is less than the top",
+    ),
+    (
+        "OP_GREATERTHAN",
+        "pushes true if the second item is greater than the top",
+    ),
+    ("OP_MIN", "pushes the smaller of the top two items"),
+    ("OP_MAX", "pushes the larger of the top two items"),
+    ("OP_WITHIN", "pushes true if a number is within a range"),
+    ("OP_RIPEMD160", "hashes the top item with ripemd160"),
+    ("OP_SHA1", "hashes the top item with sha1"),
+    ("OP_SHA256", "hashes the top item with sha256"),
+    (
+        "OP_HASH160",
+        "hashes the top item with sha256 then ripemd160",
+    ),
+    ("OP_HASH256", "hashes the top item with sha256 twice"),
+    (
+        "OP_CODESEPARATOR",
+        "signatures commit only to the script after this",
+    ),
+    ("OP_CHECKSIG", "checks a signature against a pubkey"),
+    (
+        "OP_CHECKSIGVERIFY",
+        "fails if the signature is not valid for the pubkey",
+    ),
+    ("OP_CHECKMULTISIG", "checks m signatures against n pubkeys"),
+    (
+        "OP_CHECKMULTISIGVERIFY",
+        "fails if the m signatures are not valid for the n pubkeys",
+    ),
+    (
+        "OP_CLTV",
+        "fails if the tx lock time is lower than the top item",
+    ),
+    (
+        "OP_CSV",
+        "fails if the input relative lock time is lower than the top item",
+    ),
+    (
+        "OP_CHECKSIGADD",
+        "adds 1 to a counter if a signature is valid for the pubkey",
+    ),
+];
+
+/// The description of `opcode`, `None` for the disabled or unknown ones
+fn opcode_description(opcode: &str) -> Option<&'static str> {
+    if opcode.starts_with("OP_PUSHBYTES_") {
+        Some("pushes the following bytes")
+    } else if opcode.starts_with("OP_PUSHNUM_") {
+        Some("pushes the number")
+    } else {
+        OPCODE_DESCRIPTIONS
+            .iter()
+            .find(|(name, _)| *name == opcode)
+            .map(|(_, description)| *description)
+    }
+}
+
+fn piece_sighash(piece: &str) -> Option<String> {
+    let data = Vec::<u8>::from_hex(piece).ok()?;
+    sighash(&data, false)
+}
+
 impl Html for bitcoin::Script {
     fn html(&self) -> maud::Markup {
         Script(self).render()
@@ -41,3 +189,29 @@ impl<'a> From<&'a bitcoin::Script> for Script<'a> {
         Script(a)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::opcode_description;
+    use crate::render::Html;
+    use bitcoin::ScriptBuf;
+
+    #[test]
+    fn test_opcode_description() {
+        assert_eq!(
+            opcode_description("OP_CHECKSIG"),
+            Some("checks a signature against a pubkey")
+        );
+        assert_eq!(
+            opcode_description("OP_PUSHBYTES_20"),
+            Some("pushes the following bytes")
+        );
+        assert_eq!(opcode_description("OP_CAT"), None);
+
+        let script = ScriptBuf::from_hex("51ac").unwrap();
+        assert_eq!(
+            script.html().into_string(),
+            "<code><span class=\"script\" data-tooltip=\"pushes the number\">OP_PUSHNUM_1</span> <span class=\"script\" data-tooltip=\"checks a signature against a pubkey\">OP_CHECKSIG</span></code>"
+        );
+    }
+}
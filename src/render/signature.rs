@@ -0,0 +1,81 @@
+use bitcoin::sighash::{EcdsaSighashType, TapSighashType};
+
+/// The sighash type of `data` if it looks like a signature, the signature itself is not parsed
+/// nor validated.
+///
+/// Ecdsa signatures are DER encoded (BIP66 bounds the size to 9..=73 bytes with the sighash byte),
+/// schnorr signatures are 64 bytes with implicit `SIGHASH_DEFAULT` or 65 bytes with an explicit
+/// sighash byte. Schnorr signatures exist only in taproot witnesses, so they are recognized
+/// only when `schnorr` is true, avoiding false positives like 65 bytes uncompressed public keys
+/// in script sigs.
+pub(crate) fn sighash(data: &[u8], schnorr: bool) -> Option<String> {
+    let (&last, der) = data.split_last()?;
+    if is_der(der) {
+        return EcdsaSighashType::from_standard(last as u32)
+            .ok()
+            .map(|s| s.to_string());
+    }
+    if !schnorr {
+        return None;
+    }
+    match data.len() {
+        64 => Some(TapSighashType::Default.to_string()),
+        65 => TapSighashType::from_consensus_u8(last)
+            .ok()
+            .filter(|s| *s != TapSighashType::Default)
+            .map(|s| s.to_string()),
+        _ => None,
+    }
+}
+
+/// Check the DER framing: a sequence of two integers, r and s, filling exactly the given bytes
+fn is_der(der: &[u8]) -> bool {
+    if !(8..=72).contains(&der.len()) || der[0] != 0x30 || der[1] as usize != der.len() - 2 {
+        return false;
+    }
+    let r_len = der[3] as usize;
+    if der[2] != 0x02 || r_len == 0 || 5 + r_len >= der.len() {
+        return false;
+    }
+    let s_len = der[5 + r_len] as usize;
+    der[4 + r_len] == 0x02 && s_len != 0 && 6 + r_len + s_len == der.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::sighash;
+    use bitcoin::hex::FromHex;
+
+    #[test]
+    fn test_sighash() {
+        let der = Vec::<u8>::from_hex("3045022100c12a7d54972f26d14cb311339b5122f8c187417dde1e8efb6841f55c34220ae0022066632c5cd4161efa3a2837764eee9eb84975dd54c2de2865e9752585c53e7cce01").unwrap();
+        assert_eq!(sighash(&der, false).as_deref(), Some("SIGHASH_ALL"));
+        assert_eq!(sighash(&der, true).as_deref(), Some("SIGHASH_ALL"));
+
+        let mut der_acp = der.clone();
+        *der_acp.last_mut().unwrap() = 0x81;
+        assert_eq!(
+            sighash(&der_acp, false).as_deref(),
+            Some("SIGHASH_ALL|SIGHASH_ANYONECANPAY")
+        );
+
+        let mut der_non_standard = der.clone();
+        *der_non_standard.last_mut().unwrap() = 0x04;
+        assert_eq!(sighash(&der_non_standard, false), None);
+        assert_eq!(sighash(&der[1..], false), None);
+
+        assert_eq!(sighash(&[0u8; 64], false), None);
+        assert_eq!(
+            sighash(&[0u8; 64], true).as_deref(),
+            Some("SIGHASH_DEFAULT")
+        );
+        let mut schnorr = [0u8; 65];
+        schnorr[64] = 0x03;
+        assert_eq!(sighash(&schnorr, true).as_deref(), Some("SIGHASH_SINGLE"));
+        schnorr[64] = 0x00;
+        assert_eq!(sighash(&schnorr, true), None);
+
+        assert_eq!(sighash(&[], true), None);
+        assert_eq!(sighash(&[0x02; 33], true), None);
+    }
+}
@@ -1,10 +1,32 @@
 use maud::{html, Render};
 
-use crate::{globals::network, threads::index_addresses::Spending, NetworkExt};
+use crate::{
+    globals::{network, rows_per_page},
+    render::Unit,
+    threads::index_addresses::Spending,
+    NetworkExt,
+};
 
 impl Render for Spending {
     fn render(&self) -> maud::Markup {
-        let link = format!("{}t/{}#i{}", network().as_url_path(), self.txid, self.vin); //FIXME, broken for more than 9 (ling with page)
+        let page = self.vin / rows_per_page();
+        let unit = Unit::current().link_query();
+        let link = if page > 0 {
+            format!(
+                "{}t/{}/{}{unit}#i{}",
+                network().as_url_path(),
+                self.txid,
+                page,
+                self.vin
+            )
+        } else {
+            format!(
+                "{}t/{}{unit}#i{}",
+                network().as_url_path(),
+                self.txid,
+                self.vin
+            )
+        };
 
         html! {
             a href=(link) {
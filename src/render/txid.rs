@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use super::Html;
+use super::{GroupedHex, Html, Unit};
 use crate::{globals::network, NetworkExt, NetworkPath};
 use maud::{html, Render};
 
@@ -9,7 +9,13 @@ pub struct Txid(bitcoin::Txid, bool);
 struct Link(NetworkPath, bitcoin::Txid);
 impl Display for Link {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}t/{:x}", self.0, self.1)
+        write!(
+            f,
+            "{}t/{:x}{}",
+            self.0,
+            self.1,
+            Unit::current().link_query()
+        )
     }
 }
 
@@ -20,11 +26,11 @@ impl Render for Txid {
             let link = Link(network_url_path, self.0);
 
             html! {
-                a href=(link) { code { span class="txid" { (self.0) } } }
+                a href=(link) { code { span class="txid" { (GroupedHex(self.0)) } } }
             }
         } else {
             html! {
-                code { span class="txid" { (self.0) } }
+                code { span class="txid" { (GroupedHex(self.0)) } }
             }
         }
     }
@@ -1,19 +1,26 @@
-use super::Html;
+use super::{plural::Plural, signature::sighash, Html};
 use bitcoin::hex::DisplayHex;
 use maud::{html, Render};
 
-pub(crate) struct Witness<'a>(&'a bitcoin::Witness);
+/// A witness, `taproot` if it spends a taproot output, where 64 and 65 bytes elements are
+/// annotated as schnorr signatures
+pub struct Witness<'a> {
+    pub witness: &'a bitcoin::Witness,
+    pub taproot: bool,
+}
 
 impl<'a> Render for Witness<'a> {
     fn render(&self) -> maud::Markup {
         // The following logic makes hex the witness elements, empty elements become "<empty>".
+        // Non-empty elements are followed by their size, like "(72 bytes)", and by the sighash
+        // type if they look like a signature, like "(72 bytes, SIGHASH_ALL)".
         // Moreover there is a deduplication logic where same consecutive elements like "00 00"
         // are shown as "00 2 times". This helps showing tx like
         // 73be398c4bdc43709db7398106609eea2a7841aaf3a4fa2000dc18184faa2a7e which contains
         // 500_001 empty push
         let mut witness = vec![];
         let mut count = 1;
-        let w = self.0.to_vec();
+        let w = self.witness.to_vec();
         log::debug!("witness: {w:?}");
 
         let mut iter = w.into_iter();
@@ -22,7 +29,7 @@ impl<'a> Render for Witness<'a> {
             let mut last = None;
             for current in iter {
                 if before != current {
-                    push(before, &mut witness, count);
+                    push(before, &mut witness, count, self.taproot);
                     count = 1;
                 } else {
                     count += 1;
@@ -33,9 +40,9 @@ impl<'a> Render for Witness<'a> {
             }
 
             if witness.is_empty() {
-                push(before, &mut witness, 1);
+                push(before, &mut witness, 1, self.taproot);
             } else if let Some(last) = last {
-                push(last, &mut witness, count);
+                push(last, &mut witness, count, self.taproot);
             }
         }
 
@@ -57,28 +64,36 @@ impl<'a> Render for Witness<'a> {
     }
 }
 
-fn push(data: Vec<u8>, witness: &mut Vec<String>, count: i32) {
+fn push(data: Vec<u8>, witness: &mut Vec<String>, count: i32, taproot: bool) {
     if count == 1 {
-        witness.push(hex_empty_long(&data));
+        witness.push(hex_empty_long(&data, taproot));
     } else {
-        witness.push(format!("{} {} times", hex_empty_long(&data), count));
+        witness.push(format!(
+            "{} {} times",
+            hex_empty_long(&data, taproot),
+            count
+        ));
     }
 }
 
 impl Html for bitcoin::Witness {
     fn html(&self) -> maud::Markup {
-        Witness(self).render()
+        Witness::from(self).render()
     }
 }
 
 impl<'a> From<&'a bitcoin::Witness> for Witness<'a> {
-    fn from(a: &'a bitcoin::Witness) -> Self {
-        Witness(a)
+    fn from(witness: &'a bitcoin::Witness) -> Self {
+        Witness {
+            witness,
+            taproot: false,
+        }
     }
 }
 
-/// convert in hex, unless is empty or too long
-fn hex_empty_long(val: &[u8]) -> String {
+/// convert in hex followed by the size and the sighash type of signatures, unless is empty or too
+/// long. Schnorr signatures are recognized only if `taproot`.
+fn hex_empty_long(val: &[u8], taproot: bool) -> String {
     if val.is_empty() {
         "<empty>".to_owned()
     } else if val.len() > 2000 {
@@ -91,6 +106,32 @@ fn hex_empty_long(val: &[u8]) -> String {
             val[len - 128..len].to_lower_hex_string()
         )
     } else {
-        val.to_lower_hex_string()
+        let len = val.len();
+        let sighash = sighash(val, taproot)
+            .map(|s| format!(", {s}"))
+            .unwrap_or_default();
+        format!(
+            "{} ({} {}{})",
+            val.to_lower_hex_string(),
+            len,
+            Plural::new("byte", len),
+            sighash
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::hex_empty_long;
+
+    #[test]
+    fn test_hex_empty_long() {
+        assert_eq!(hex_empty_long(&[], false), "<empty>");
+        assert_eq!(hex_empty_long(&[1], false), "01 (1 byte)");
+        assert_eq!(hex_empty_long(&[0xab; 2], false), "abab (2 bytes)");
+        assert!(hex_empty_long(&[0; 2001], false).contains("original size is 2001 bytes"));
+        assert!(hex_empty_long(&[0; 64], true).ends_with(" (64 bytes, SIGHASH_DEFAULT)"));
+        // like a 64 bytes p2wsh witness script
+        assert!(hex_empty_long(&[0; 64], false).ends_with(" (64 bytes)"));
     }
 }
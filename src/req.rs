@@ -2,19 +2,60 @@ use std::fmt::Display;
 use std::str::FromStr;
 
 use crate::globals::network;
+use crate::invoice::Invoice;
+use crate::render::Unit;
 use crate::threads::index_addresses::Height;
+use crate::xpub::ExtendedKey;
 use crate::NetworkExt;
 use crate::{error::Error, route::ResponseType};
 use bitcoin::address::NetworkUnchecked;
 use bitcoin::hashes::{sha256d, Hash};
-use bitcoin::{consensus::deserialize, Address, BlockHash, Transaction, Txid};
-use bitcoin::{OutPoint, Psbt};
-use hyper::{Body, Method, Request};
+use bitcoin::hex::{DisplayHex, FromHex};
+use bitcoin::{consensus::deserialize, Address, BlockHash, Network, Transaction, Txid};
+use bitcoin::{OutPoint, Psbt, ScriptBuf};
+use hyper::{
+    header::{ACCEPT, COOKIE},
+    Body, Method, Request,
+};
 
 #[derive(Debug, Clone)]
 pub struct ParsedRequest {
     pub resource: Resource,
     pub response_type: ResponseType,
+    pub unit: Unit,
+
+    /// From the `theme` query param or cookie
+    pub theme: Theme,
+
+    /// The `theme` query param, if given the response to html GET requests sets the cookie and
+    /// redirects to the url without the param
+    pub set_theme: Option<Theme>,
+
+    /// The query params other than `unit` and `theme`, if any
+    pub query: Option<String>,
+}
+
+impl ParsedRequest {
+    /// Responses meant to be consumed by programs rather than read by humans in a browser
+    pub fn is_api(&self) -> bool {
+        matches!(
+            self.response_type,
+            ResponseType::Json | ResponseType::Bytes | ResponseType::Hex
+        ) || matches!(
+            self.resource,
+            Resource::Metrics
+                | Resource::Health
+                | Resource::Preflight
+                | Resource::Outspends(_)
+                | Resource::TxGraph(_)
+                | Resource::MerkleProof(_)
+                | Resource::MempoolFees
+                | Resource::BlockHashByHeight(_)
+                | Resource::BlockHeader(_, _)
+                | Resource::Addresses
+                | Resource::AddressTxids(_)
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -23,52 +64,134 @@ pub enum Resource {
     Favicon,
     Css,
     Contact,
+
+    /// Web app manifest, to install the explorer as a PWA
+    Manifest,
+
     SearchHeight(u32),
     SearchBlock(BlockHash),
     SearchTx(Txid),
-    SearchAddress(Address),
+
+    /// An address with the params of the `bitcoin:` URI it was given in, if any
+    SearchAddress(Address, Option<String>),
     SearchFullTx(Transaction),
+
+    /// A PSBT given in the search box, redirected to [`Resource::Psbt`]
+    SearchPsbt(Psbt),
+
+    /// A lightning invoice given in the search box, redirected to [`Resource::Invoice`]
+    SearchInvoice(Invoice),
+
+    /// Lowercase hex prefix of a block hash or a txid
+    SearchPrefix(String),
+
+    /// Broadcast the transaction in the request body, hex or binary encoded
+    Broadcast,
+
+    /// CORS preflight `OPTIONS` request
+    Preflight,
+
+    /// Resize the caches, the new tx cache byte size and the new txid->block_hash len
+    AdminCache(Option<usize>, Option<usize>),
+
     Tx(Txid, usize),
+
+    /// Spending status of every output of the tx, always json
+    Outspends(Txid),
+
+    /// Txids of the tx, of the txs it spends and of the known txs spending it, always json
+    TxGraph(Txid),
+
+    /// Merkle inclusion proof of a confirmed transaction
+    MerkleProof(Txid),
     Block(BlockHash, usize),
+
+    /// Hash of the block at the height in the main chain, plain text or json like the bitcoind
+    /// REST interface, without the redirect of [`Resource::SearchHeight`]
+    BlockHashByHeight(u32),
+
+    /// Only the block header, binary or json according to the extension of the path, eg.
+    /// `/b/{hash}/header.bin`
+    BlockHeader(BlockHash, ResponseType),
+
+    /// Mempool transactions sorted by fee rate, paginated
+    Mempool(usize),
+
+    /// Recommended fee rates computed from the mempool, always json
+    MempoolFees,
+
+    /// Most recent blocks, paginated starting from the tip
+    Blocks(usize),
     TxOut(OutPoint, Height),
     Head,
     Robots,
     BlockToB(BlockHash),
     TxToT(Txid),
     Address(Address<NetworkUnchecked>, Option<String>),
+
+    /// Txids funding or spending from the address, one per line. The last line is `# truncated`
+    /// when the list may be incomplete: only the most recent blocks funding the address are
+    /// looked up and spending txs are known only for the most recent spending blocks
+    AddressTxids(Address<NetworkUnchecked>),
+
+    /// History of the addresses in the json array of the request body
+    Addresses,
+    Script(ScriptBuf),
+
+    /// Transactions with an OP_RETURN output pushing these bytes, needs `--index-op-return`
+    OpReturn(Vec<u8>),
+
+    /// Comparison of a tx with another one, usually its replacement
+    TxDiff(Txid, Txid),
     AddressToA(Address),
+
+    /// Addresses derived from an extended public key
+    Xpub(ExtendedKey),
     FullTx(Transaction),
+
+    /// A PSBT with its inputs and outputs metadata, hex encoded in the url
+    Psbt(Psbt),
+
+    /// A decoded BOLT11 lightning invoice
+    Invoice(Invoice),
     Metrics,
+    Health,
+    Feed,
+
+    /// Websocket pushing new tips and block template fees
+    WebSocket,
 }
 
-pub async fn parse(req: &Request<Body>) -> Result<ParsedRequest, Error> {
+/// Shorter prefixes would match too many blocks or transactions
+const MIN_SEARCH_PREFIX_LEN: usize = 8;
+
+/// `max_size` bounds the length of user provided transactions in the url
+pub async fn parse(req: &Request<Body>, max_size: usize) -> Result<ParsedRequest, Error> {
     let mut path: Vec<_> = req.uri().path().split('/').skip(1).take(5).collect();
     log::debug!("{:?}", path);
 
     if path.get(4).is_some() {
         return Err(Error::BadRequest);
     }
-    let response_type = match path.last() {
-        Some(&"text") => ResponseType::Text(parse_cols(req)),
-        Some(&"bin") => ResponseType::Bytes,
-        _ => ResponseType::Html,
-    };
+    let response_type = response_type(req);
     log::debug!("{:?}", response_type);
-    if let ResponseType::Text(_) | ResponseType::Bytes = response_type {
+    if suffix_response_type(req).is_some() {
         path.pop();
         if path.is_empty() {
             // home page corner case
             path.push("");
         }
     }
-    let query = req.uri().query();
+    let (query, unit, set_theme) = split_params(req.uri().query())?;
+    let query = query.as_deref();
     let is_head = req.method() == Method::HEAD;
     let method = if is_head { &Method::GET } else { req.method() };
 
     let mut resource = match (method, query, path.first(), path.get(1), path.get(2)) {
+        (&Method::OPTIONS, _, _, _, _) => Resource::Preflight,
         (&Method::GET, None, Some(&""), None, None) => Resource::Home,
         (&Method::GET, Some(query), None | Some(&""), None, None) => {
-            if query.contains('&') {
+            if query.contains('&') || query.len() > max_size {
                 return Err(Error::BadRequest);
             }
             let mut iter = query.split('=');
@@ -87,7 +210,15 @@ pub async fn parse(req: &Request<Body>) -> Result<ParsedRequest, Error> {
                             }
                         }
                         Err(_) => match Address::from_str(val) {
-                            Ok(address) => Resource::SearchAddress(address.assume_checked()),
+                            Ok(address) => Resource::SearchAddress(address.assume_checked(), None),
+                            Err(_)
+                                if val.len() < 64 && val.chars().all(|c| c.is_ascii_hexdigit()) =>
+                            {
+                                if val.len() < MIN_SEARCH_PREFIX_LEN {
+                                    return Err(Error::SearchPrefixTooShort(MIN_SEARCH_PREFIX_LEN));
+                                }
+                                Resource::SearchPrefix(val.to_ascii_lowercase())
+                            }
                             Err(_) => {
                                 match hex::decode(val)
                                     .map(|bytes| deserialize::<Transaction>(&bytes))
@@ -97,10 +228,7 @@ pub async fn parse(req: &Request<Body>) -> Result<ParsedRequest, Error> {
                                         let val = percent_encoding::percent_decode(val.as_bytes())
                                             .decode_utf8()
                                             .map_err(|_| Error::BadRequest)?;
-                                        let psbt = Psbt::from_str(val.as_ref())
-                                            .map_err(|_| Error::BadRequest)?;
-                                        let tx = psbt.extract_tx()?;
-                                        Resource::SearchFullTx(tx)
+                                        search_pasted(val.as_ref())?
                                     }
                                 }
                             }
@@ -115,8 +243,24 @@ pub async fn parse(req: &Request<Body>) -> Result<ParsedRequest, Error> {
         (&Method::GET, None, Some(&"robots.txt"), None, None) => Resource::Robots,
         (&Method::GET, None, Some(&"css"), Some(&"pico.min.css"), None) => Resource::Css,
         (&Method::GET, None, Some(&"contact"), None, None) => Resource::Contact,
+        (&Method::GET, None, Some(&"manifest.json"), None, None) => Resource::Manifest,
         (&Method::GET, None, Some(&"metrics"), None, None) => Resource::Metrics,
+        (&Method::GET, None, Some(&"health"), None, None) => Resource::Health,
+        (&Method::GET, None, Some(&"feed.xml"), None, None) => Resource::Feed,
+        (&Method::GET, None, Some(&"ws"), None, None) => Resource::WebSocket,
 
+        (&Method::GET, None, Some(&"t"), Some(txid), Some(&"outspends")) => {
+            let txid = Txid::from_str(txid)?;
+            Resource::Outspends(txid)
+        }
+        (&Method::GET, None, Some(&"t"), Some(txid), Some(&"graph")) => {
+            let txid = Txid::from_str(txid)?;
+            Resource::TxGraph(txid)
+        }
+        (&Method::GET, None, Some(&"t"), Some(txid), Some(&"merkle-proof")) => {
+            let txid = Txid::from_str(txid)?;
+            Resource::MerkleProof(txid)
+        }
         (&Method::GET, None, Some(&"t"), Some(txid), page) => {
             let txid = Txid::from_str(txid)?;
             let page = match page {
@@ -134,6 +278,16 @@ pub async fn parse(req: &Request<Body>) -> Result<ParsedRequest, Error> {
             let height: u32 = height.parse()?;
             Resource::SearchHeight(height)
         }
+        (&Method::GET, None, Some(&"blockhashbyheight"), Some(height), None) => {
+            let height: u32 = height.parse()?;
+            Resource::BlockHashByHeight(height)
+        }
+        (&Method::GET, None, Some(&"b"), Some(block_hash), Some(&"header.bin")) => {
+            Resource::BlockHeader(BlockHash::from_str(block_hash)?, ResponseType::Bytes)
+        }
+        (&Method::GET, None, Some(&"b"), Some(block_hash), Some(&"header.json")) => {
+            Resource::BlockHeader(BlockHash::from_str(block_hash)?, ResponseType::Json)
+        }
         (&Method::GET, None, Some(&"b"), Some(block_hash), page) => {
             let block_hash = BlockHash::from_str(block_hash)?;
             let page = match page {
@@ -142,10 +296,43 @@ pub async fn parse(req: &Request<Body>) -> Result<ParsedRequest, Error> {
             };
             Resource::Block(block_hash, page)
         }
+        (&Method::GET, None, Some(&"mempool"), Some(&"fees.json"), None) => Resource::MempoolFees,
+        (&Method::GET, None, Some(&"mempool"), page, None) => {
+            let page = match page {
+                Some(page) => page.parse::<usize>()?,
+                None => 0,
+            };
+            Resource::Mempool(page)
+        }
+        (&Method::GET, None, Some(&"blocks"), page, None) => {
+            let page = match page {
+                Some(page) => page.parse::<usize>()?,
+                None => 0,
+            };
+            Resource::Blocks(page)
+        }
+        (&Method::GET, None, Some(&"xpub"), Some(key), None) => Resource::Xpub(key.parse()?),
         (&Method::GET, query, Some(&"a"), Some(address), None) => {
             let address = Address::from_str(address)?;
             Resource::Address(address, query.map(ToString::to_string))
         }
+        (&Method::GET, None, Some(&"a"), Some(address), Some(&"txids")) => {
+            Resource::AddressTxids(Address::from_str(address)?)
+        }
+        (&Method::GET, None, Some(&"s"), Some(script), None) => {
+            let script = ScriptBuf::from_hex(script).map_err(|_| Error::BadRequest)?;
+            Resource::Script(script)
+        }
+        (&Method::GET, None, Some(&"op-return"), Some(data), None) => {
+            let data = Vec::<u8>::from_hex(data).map_err(|_| Error::BadRequest)?;
+            if data.is_empty() {
+                return Err(Error::BadRequest);
+            }
+            Resource::OpReturn(data)
+        }
+        (&Method::GET, None, Some(&"diff"), Some(old), Some(new)) => {
+            Resource::TxDiff(Txid::from_str(old)?, Txid::from_str(new)?)
+        }
         (&Method::GET, None, Some(&"block"), Some(block_hash), None) => {
             let block_hash = BlockHash::from_str(block_hash)?;
             Resource::BlockToB(block_hash)
@@ -155,10 +342,48 @@ pub async fn parse(req: &Request<Body>) -> Result<ParsedRequest, Error> {
             Resource::TxToT(txid)
         }
         (&Method::GET, None, Some(&"txhex"), Some(hex), None) => {
+            if hex.len() > max_size {
+                return Err(Error::BadRequest);
+            }
             let bytes = hex::decode(hex)?;
             let tx: Transaction = deserialize(&bytes)?;
             Resource::FullTx(tx)
         }
+        (&Method::GET, None, Some(&"psbt"), Some(hex), None) => {
+            if hex.len() > max_size {
+                return Err(Error::BadRequest);
+            }
+            let bytes = hex::decode(hex)?;
+            let psbt = Psbt::deserialize(&bytes).map_err(|_| Error::BadRequest)?;
+            Resource::Psbt(psbt)
+        }
+        (&Method::GET, None, Some(&"invoice"), Some(invoice), None) => {
+            if invoice.len() > max_size {
+                return Err(Error::BadRequest);
+            }
+            let invoice = Invoice::from_str(invoice).map_err(|_| Error::BadRequest)?;
+            Resource::Invoice(invoice)
+        }
+        (&Method::POST, None, Some(&"tx"), None, None) => Resource::Broadcast,
+        (&Method::POST, None, Some(&"addresses"), None, None) => Resource::Addresses,
+        (&Method::POST, query, Some(&"admin"), Some(&"cache"), None) => {
+            let mut tx_cache_byte_size = None;
+            let mut txid_blockhash_len = None;
+            for param in query
+                .unwrap_or_default()
+                .split('&')
+                .filter(|p| !p.is_empty())
+            {
+                let (key, val) = param.split_once('=').ok_or(Error::BadRequest)?;
+                let val = Some(val.parse().map_err(|_| Error::BadRequest)?);
+                match key {
+                    "tx_cache_byte_size" => tx_cache_byte_size = val,
+                    "txid_blockhash_len" => txid_blockhash_len = val,
+                    _ => return Err(Error::BadRequest),
+                }
+            }
+            Resource::AdminCache(tx_cache_byte_size, txid_blockhash_len)
+        }
         (&Method::GET, None, Some(&"address"), Some(address), None) => {
             let address = Address::from_str(address)?;
             Resource::AddressToA(address.assume_checked())
@@ -172,13 +397,159 @@ pub async fn parse(req: &Request<Body>) -> Result<ParsedRequest, Error> {
     Ok(ParsedRequest {
         resource,
         response_type,
+        unit,
+        theme: set_theme.or_else(|| theme_cookie(req)).unwrap_or_default(),
+        set_theme,
+        query: query.map(str::to_string),
     })
 }
 
-pub struct TextLink<'a>(&'a Resource);
+/// Search for a `bitcoin:` URI, a lightning invoice with or without the `lightning:` scheme or a
+/// base64 PSBT
+fn search_pasted(val: &str) -> Result<Resource, Error> {
+    // schemes are case insensitive, and QR codes often use uppercase
+    let lower = val.to_ascii_lowercase();
+    if lower.starts_with("bitcoin:") {
+        let uri = &val["bitcoin:".len()..];
+        let (address, params) = match uri.split_once('?') {
+            Some((address, params)) => {
+                let params = url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(url::form_urlencoded::parse(params.as_bytes()))
+                    .finish();
+                (address, Some(params).filter(|p| !p.is_empty()))
+            }
+            None => (uri, None),
+        };
+        let address = Address::from_str(address).map_err(|_| Error::BadRequest)?;
+        return Ok(Resource::SearchAddress(address.assume_checked(), params));
+    }
+    let invoice = lower.strip_prefix("lightning:").unwrap_or(&lower);
+    if invoice.starts_with("ln") {
+        let invoice = Invoice::from_str(invoice).map_err(|_| Error::BadRequest)?;
+        return Ok(Resource::SearchInvoice(invoice));
+    }
+    let psbt = Psbt::from_str(val).map_err(|_| Error::BadRequest)?;
+    Ok(Resource::SearchPsbt(psbt))
+}
+
+/// Remove the `unit` and `theme` params from `query`, returning the remaining query, if any, the
+/// unit and the theme
+fn split_params(query: Option<&str>) -> Result<(Option<String>, Unit, Option<Theme>), Error> {
+    let query = match query {
+        Some(query) => query,
+        None => return Ok((None, Unit::default(), None)),
+    };
+    let mut unit = Unit::default();
+    let mut theme = None;
+    let mut rest = vec![];
+    for param in query.split('&') {
+        if let Some(value) = param.strip_prefix("theme=") {
+            theme = Some(value.parse()?);
+            continue;
+        }
+        match param.strip_prefix("unit=") {
+            Some("btc") => unit = Unit::Btc,
+            Some("sat") => unit = Unit::Sat,
+            Some(_) => return Err(Error::BadRequest),
+            None => rest.push(param),
+        }
+    }
+    let rest = (!rest.is_empty()).then(|| rest.join("&"));
+    Ok((rest, unit, theme))
+}
+
+/// Color scheme of the html pages, by default pico.css follows the browser `prefers-color-scheme`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Auto,
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Auto, Theme::Dark, Theme::Light];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::Auto => "auto",
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        }
+    }
+
+    /// Value of the `data-theme` attribute of the html element, `None` follows the browser
+    pub fn data_theme(&self) -> Option<&'static str> {
+        match self {
+            Theme::Auto => None,
+            Theme::Dark | Theme::Light => Some(self.as_str()),
+        }
+    }
+
+    /// Remembers the choice for a year, the auto theme removes the cookie
+    pub fn set_cookie(&self) -> String {
+        match self {
+            Theme::Auto => format!("{THEME_COOKIE}=; Path=/; Max-Age=0; SameSite=Lax"),
+            _ => format!(
+                "{THEME_COOKIE}={}; Path=/; Max-Age=31536000; SameSite=Lax",
+                self.as_str()
+            ),
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Theme::ALL
+            .into_iter()
+            .find(|t| t.as_str() == s)
+            .ok_or(Error::BadRequest)
+    }
+}
+
+const THEME_COOKIE: &str = "theme";
+
+/// The theme in the request cookie, if any, unknown values are ignored
+pub fn theme_cookie(req: &Request<Body>) -> Option<Theme> {
+    req.headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(';'))
+        .filter_map(|c| c.trim().strip_prefix(THEME_COOKIE)?.strip_prefix('='))
+        .find_map(|value| value.parse().ok())
+}
+
+/// The unit in the `unit` query param, the default if missing or invalid
+pub fn unit_param(req: &Request<Body>) -> Unit {
+    split_params(req.uri().query()).map_or(Unit::default(), |(_, unit, _)| unit)
+}
+
+/// Relative url of the request without the `theme` param, relative so that it works also when a
+/// reverse proxy serves this instance under a path prefix, like `/testnet/`
+pub fn without_theme_param(req: &Request<Body>) -> String {
+    let uri = req.uri();
+    let last_segment = uri.path().rsplit('/').next().unwrap_or_default();
+    let query: Vec<_> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|p| !p.is_empty() && !p.starts_with("theme="))
+        .collect();
+    if query.is_empty() {
+        format!("./{last_segment}")
+    } else {
+        format!("./{last_segment}?{}", query.join("&"))
+    }
+}
+
+pub struct TextLink<'a>(&'a Resource, Unit);
 impl<'a> Display for TextLink<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let base = network().as_url_path();
+        let has_query = matches!(self.0, Resource::Address(_, Some(_)));
         match self.0 {
             Resource::Home => write!(f, "{}text", base),
 
@@ -196,6 +567,28 @@ impl<'a> Display for TextLink<'a> {
                     write!(f, "{base}b/{block_hash}/{pagination}/text")
                 }
             }
+            Resource::Mempool(pagination) => {
+                if *pagination == 0 {
+                    write!(f, "{base}mempool/text")
+                } else {
+                    write!(f, "{base}mempool/{pagination}/text")
+                }
+            }
+            Resource::Blocks(pagination) => {
+                if *pagination == 0 {
+                    write!(f, "{base}blocks/text")
+                } else {
+                    write!(f, "{base}blocks/{pagination}/text")
+                }
+            }
+            Resource::Script(script) => write!(f, "{base}s/{script:x}/text"),
+            Resource::OpReturn(data) => {
+                write!(f, "{base}op-return/{}/text", data.to_lower_hex_string())
+            }
+            Resource::TxDiff(old, new) => write!(f, "{base}diff/{old}/{new}/text"),
+            Resource::Xpub(key) => write!(f, "{base}xpub/{key}/text"),
+            Resource::Psbt(psbt) => write!(f, "{base}psbt/{}/text", psbt.serialize_hex()),
+            Resource::Invoice(invoice) => write!(f, "{base}invoice/{invoice}/text"),
             Resource::Address(address, query) => {
                 let address = address.clone().assume_checked(); // TODO clone is a performance penalty here
                 write!(f, "{base}a/{address}/text")?;
@@ -205,19 +598,148 @@ impl<'a> Display for TextLink<'a> {
                 Ok(())
             }
             _ => panic!("resource without text link"),
+        }?;
+        match self.1.param() {
+            Some(param) if has_query => write!(f, "&{param}"),
+            Some(param) => write!(f, "?{param}"),
+            None => Ok(()),
         }
     }
 }
+/// Check `address` is for `network`, otherwise the error says which networks are involved
+pub fn require_network(
+    address: &Address<NetworkUnchecked>,
+    network: Network,
+) -> Result<Address, Error> {
+    address
+        .clone()
+        .require_network(network)
+        .map_err(|_| Error::AddressWrongNetwork {
+            fbbe: network,
+            address: address.clone().assume_checked(),
+        })
+}
+
 impl Resource {
-    pub fn link(&self) -> Option<TextLink> {
+    /// Name of the resource variant, used as label in metrics and logs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Resource::Home => "Home",
+            Resource::Favicon => "Favicon",
+            Resource::Css => "Css",
+            Resource::Contact => "Contact",
+            Resource::Manifest => "Manifest",
+            Resource::SearchHeight(_) => "SearchHeight",
+            Resource::SearchBlock(_) => "SearchBlock",
+            Resource::SearchTx(_) => "SearchTx",
+            Resource::SearchAddress(_, _) => "SearchAddress",
+            Resource::SearchFullTx(_) => "SearchFullTx",
+            Resource::SearchPsbt(_) => "SearchPsbt",
+            Resource::SearchInvoice(_) => "SearchInvoice",
+            Resource::SearchPrefix(_) => "SearchPrefix",
+            Resource::Broadcast => "Broadcast",
+            Resource::Preflight => "Preflight",
+            Resource::AdminCache(_, _) => "AdminCache",
+            Resource::Tx(_, _) => "Tx",
+            Resource::Block(_, _) => "Block",
+            Resource::BlockHashByHeight(_) => "BlockHashByHeight",
+            Resource::BlockHeader(_, _) => "BlockHeader",
+            Resource::Mempool(_) => "Mempool",
+            Resource::MempoolFees => "MempoolFees",
+            Resource::Xpub(_) => "Xpub",
+            Resource::Blocks(_) => "Blocks",
+            Resource::Outspends(_) => "Outspends",
+            Resource::TxGraph(_) => "TxGraph",
+            Resource::MerkleProof(_) => "MerkleProof",
+            Resource::TxOut(_, _) => "TxOut",
+            Resource::Head => "Head",
+            Resource::Robots => "Robots",
+            Resource::Feed => "Feed",
+            Resource::WebSocket => "WebSocket",
+            Resource::BlockToB(_) => "BlockToB",
+            Resource::TxToT(_) => "TxToT",
+            Resource::Address(_, _) => "Address",
+            Resource::AddressTxids(_) => "AddressTxids",
+            Resource::Addresses => "Addresses",
+            Resource::AddressToA(_) => "AddressToA",
+            Resource::Script(_) => "Script",
+            Resource::OpReturn(_) => "OpReturn",
+            Resource::TxDiff(_, _) => "TxDiff",
+            Resource::FullTx(_) => "FullTx",
+            Resource::Psbt(_) => "Psbt",
+            Resource::Invoice(_) => "Invoice",
+            Resource::Metrics => "Metrics",
+            Resource::Health => "Health",
+        }
+    }
+
+    /// Link to the text version of the resource, keeping `unit`
+    pub fn link(&self, unit: Unit) -> Option<TextLink> {
         use Resource::*;
         match self {
-            Home | Tx(_, _) | Block(_, _) | Address(_, _) => Some(TextLink(self)),
+            Home
+            | Tx(_, _)
+            | Block(_, _)
+            | Mempool(_)
+            | Blocks(_)
+            | Address(_, _)
+            | Script(_)
+            | OpReturn(_)
+            | TxDiff(_, _)
+            | Xpub(_)
+            | Psbt(_)
+            | Invoice(_) => Some(TextLink(self, unit)),
             _ => None,
         }
     }
 }
 
+/// The response type is chosen with the last path segment, eg. `/t/{txid}/json`, or with the
+/// `Accept` header if there is no such segment, html otherwise
+pub fn response_type(req: &Request<Body>) -> ResponseType {
+    suffix_response_type(req)
+        .or_else(|| accept_response_type(req))
+        .unwrap_or(ResponseType::Html)
+}
+
+fn suffix_response_type(req: &Request<Body>) -> Option<ResponseType> {
+    match req.uri().path().rsplit('/').next() {
+        Some("text") => Some(ResponseType::Text(parse_cols(req))),
+        Some("bin") => Some(ResponseType::Bytes),
+        Some("json") => Some(ResponseType::Json),
+        Some("hex") => Some(ResponseType::Hex),
+        _ => None,
+    }
+}
+
+/// The media type of the `Accept` header we can produce with the highest quality value, the first
+/// listed if more have the same. Wildcards mean the default html.
+fn accept_response_type(req: &Request<Body>) -> Option<ResponseType> {
+    let accept = req.headers().get(ACCEPT)?.to_str().ok()?;
+    let mut best: Option<(f32, ResponseType)> = None;
+    for media in accept.split(',') {
+        let mut params = media.split(';');
+        let response_type = match params.next().unwrap_or("").trim() {
+            "text/html" | "text/*" | "*/*" => ResponseType::Html,
+            "text/plain" => ResponseType::Text(parse_cols(req)),
+            "application/json" => ResponseType::Json,
+            "application/octet-stream" => ResponseType::Bytes,
+            _ => continue,
+        };
+        let quality = match params.find_map(|p| p.trim().strip_prefix("q=")) {
+            Some(q) => match q.trim().parse::<f32>() {
+                Ok(q) => q,
+                Err(_) => continue,
+            },
+            None => 1.0,
+        };
+        if quality > 0.0 && best.map_or(true, |(b, _)| quality > b) {
+            best = Some((quality, response_type));
+        }
+    }
+    best.map(|(_, response_type)| response_type)
+}
+
 fn parse_cols(req: &Request<Body>) -> u16 {
     req.headers()
         .get("columns")
@@ -225,3 +747,122 @@ fn parse_cols(req: &Request<Body>) -> u16 {
         .and_then(|e| e.parse::<u16>().ok())
         .unwrap_or(80)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        require_network, response_type, search_pasted, split_params, theme_cookie,
+        without_theme_param, Resource, Theme,
+    };
+    use crate::{error::Error, render::Unit, route::ResponseType};
+    use bitcoin::{address::NetworkUnchecked, Address, Network};
+    use hyper::{Body, Request, StatusCode};
+
+    #[test]
+    fn test_require_network() {
+        let testnet: Address<NetworkUnchecked> = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"
+            .parse()
+            .unwrap();
+        assert!(require_network(&testnet, Network::Testnet).is_ok());
+        assert!(require_network(&testnet, Network::Signet).is_ok());
+
+        match require_network(&testnet, Network::Bitcoin) {
+            Err(Error::AddressWrongNetwork { fbbe, address }) => {
+                assert_eq!(fbbe, Network::Bitcoin);
+                assert_eq!(
+                    address.to_string(),
+                    "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"
+                );
+            }
+            _ => panic!("expected AddressWrongNetwork"),
+        }
+    }
+
+    #[test]
+    fn test_theme() {
+        let (rest, unit, theme) = split_params(Some("unit=sat&theme=dark&page=2")).unwrap();
+        assert_eq!(rest.as_deref(), Some("page=2"));
+        assert!(matches!(unit, Unit::Sat));
+        assert_eq!(theme, Some(Theme::Dark));
+        assert!(split_params(Some("theme=blue")).is_err());
+
+        let req = Request::get("/t/abc?theme=light&x=1")
+            .header("cookie", "a=b; theme=dark")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(theme_cookie(&req), Some(Theme::Dark));
+        assert_eq!(without_theme_param(&req), "./abc?x=1");
+
+        let req = Request::get("/?theme=auto").body(Body::empty()).unwrap();
+        assert_eq!(theme_cookie(&req), None);
+        assert_eq!(without_theme_param(&req), "./");
+    }
+
+    #[test]
+    fn test_response_type() {
+        let req = |path: &str, accept: &str| {
+            Request::get(path)
+                .header("accept", accept)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let browser = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+        assert!(matches!(
+            response_type(&req("/t/abc", browser)),
+            ResponseType::Html
+        ));
+        assert!(matches!(
+            response_type(&req("/t/abc", "*/*")),
+            ResponseType::Html
+        ));
+        assert!(matches!(
+            response_type(&req("/t/abc", "application/json")),
+            ResponseType::Json
+        ));
+        assert!(matches!(
+            response_type(&req("/t/abc", "text/plain; charset=utf-8")),
+            ResponseType::Text(80)
+        ));
+        assert!(matches!(
+            response_type(&req("/t/abc", "application/json;q=0.5, text/plain")),
+            ResponseType::Text(80)
+        ));
+        assert!(matches!(
+            response_type(&req("/t/abc", "text/html;q=0, application/json;q=0.1")),
+            ResponseType::Json
+        ));
+        // the suffix takes precedence
+        assert!(matches!(
+            response_type(&req("/t/abc/bin", "application/json")),
+            ResponseType::Bytes
+        ));
+
+        // the mempool page has no json, the client gets not acceptable instead of a server error
+        let err = Error::ContentTypeUnsupported(
+            response_type(&req("/mempool", "application/json")),
+            "/mempool".to_string(),
+        );
+        assert_eq!(StatusCode::from(err), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[test]
+    fn test_search_pasted() {
+        match search_pasted(
+            "BITCOIN:BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4?amount=0.1&label=Luke%20Jr",
+        ) {
+            Ok(Resource::SearchAddress(address, params)) => {
+                assert_eq!(
+                    address.to_string(),
+                    "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+                );
+                assert_eq!(params.as_deref(), Some("amount=0.1&label=Luke+Jr"));
+            }
+            _ => panic!("expected an address"),
+        }
+        assert!(matches!(
+            search_pasted("lightning:lnbc1qqqq"),
+            Err(Error::BadRequest)
+        ));
+        assert!(matches!(search_pasted("nothing"), Err(Error::BadRequest)));
+    }
+}
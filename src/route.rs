@@ -1,40 +1,85 @@
 use crate::{
     base_text_decorator::BaseTextDecorator,
+    compress::{compress, Encoding},
     error::Error,
+    feed,
+    globals::{rate_limiter, root_network, with_network},
+    json::{self, outspends::OutSpend},
     network,
     pages::{self, tx::OutputStatus},
-    render::MempoolSection,
+    pools,
+    render::{Activity, MempoolSection, Retarget, Unit, ACTIVITY_WINDOW, RETARGET_INTERVAL},
     req::{self, Resource},
-    rpc,
-    state::tx_output,
-    threads::index_addresses::{address_seen, Database},
-    NetworkExt, SharedState,
+    rpc::{self, chaininfo::ChainInfo, headers::HeightTime},
+    server_timing,
+    state::{outpoints_and_sum, tx_output, OutPointsAndSum},
+    threads::{
+        index_addresses::{
+            address_seen, op_return_seen, possibly_truncated, AddressSeen, Database, Height,
+        },
+        update_mempool_info::WeightFee,
+    },
+    ws, xpub, NetworkExt, SharedState,
 };
 use bitcoin::hex::DisplayHex;
-use bitcoin::{consensus::serialize, OutPoint, TxOut, Txid};
+use bitcoin::{
+    consensus::encode::serialize_hex, consensus::serialize, Address, BlockHash, Network, OutPoint,
+    TxIn, TxOut, Txid, Weight,
+};
 use bitcoin::{
     consensus::{deserialize, Encodable},
-    hashes::Hash,
+    hashes::{sha256, Hash},
 };
 use bitcoin_slices::{bsl, Visit, Visitor};
+use futures::prelude::*;
+use fxhash::{FxHashMap, FxHashSet};
 use hyper::{
     body::Bytes,
-    header::{CACHE_CONTROL, CONTENT_TYPE, IF_MODIFIED_SINCE, LAST_MODIFIED, LOCATION},
-    Body, Request, Response, StatusCode,
+    body::HttpBody,
+    header::{
+        HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+        ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ALLOW, AUTHORIZATION, CACHE_CONTROL,
+        CONTENT_ENCODING, CONTENT_TYPE, HOST, IF_MODIFIED_SINCE, LAST_MODIFIED, LOCATION,
+        RETRY_AFTER, SET_COOKIE, VARY,
+    },
+    Body, Method, Request, Response, StatusCode,
 };
+use maud::Render;
 use mime::{APPLICATION_OCTET_STREAM, TEXT_HTML_UTF_8, TEXT_PLAIN_UTF_8};
 use prometheus::Encoder;
-use std::{convert::Infallible, sync::Arc, time::Instant};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::Infallible,
+    net::IpAddr,
+    ops::Range,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 const CSS_LAST_MODIFIED: &str = "2022-10-03 07:53:03 UTC";
 const CONTACT_PAGE_LAST_MODIFIED: &str = "2022-12-16 07:53:03 UTC";
 const ROBOTS_LAST_MODIFIED: &str = "2023-01-17 07:53:03 UTC";
+const MANIFEST_LAST_MODIFIED: &str = "2026-10-16 07:53:03 UTC";
+
+/// Default mempool policy limit on the number of unconfirmed ancestors
+const MAX_ANCESTORS: usize = 25;
+
+/// Spending txs considered for the related addresses, since every one needs its prevouts
+const RELATED_ADDRESSES_MAX_TXS: usize = 10;
+
+/// Blocks fetched to find the spending txs of the outputs of a tx, the outputs spent in other
+/// blocks are returned without the spending tx
+const OUTSPENDS_MAX_BLOCKS: usize = 10;
 
 #[derive(Debug, Clone, Copy)]
 pub enum ResponseType {
     Text(u16),
     Html,
     Bytes,
+    Json,
+
+    /// Full hex of the serialized object as plain text
+    Hex,
 }
 
 impl ResponseType {
@@ -44,16 +89,50 @@ impl ResponseType {
 }
 
 pub async fn route(
-    req: Request<Body>,
+    mut req: Request<Body>,
     state: Arc<SharedState>,
     db: Option<Arc<Database>>,
 ) -> Result<Response<Body>, Error> {
     let now = Instant::now();
     // let _count = state.requests.fetch_add(1, Ordering::Relaxed);
-    let parsed_req = req::parse(&req).await?;
+
+    // checked before parsing, which may read the body
+    if let Some(rate_limiter) = rate_limiter() {
+        let exempt = matches!(req.uri().path(), "/metrics" | "/health");
+        if let (false, Some(ip)) = (exempt, client_ip(&req, state.args.trust_forwarded_for)) {
+            if let Err(wait) = rate_limiter.check(ip, now) {
+                let mut builder = Response::builder();
+                if let Some(origin) = state.args.cors_allow_origin.as_ref() {
+                    builder = builder.header(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                }
+                return Ok(builder
+                    .status(StatusCode::TOO_MANY_REQUESTS)
+                    .header(RETRY_AFTER, wait.as_secs_f64().ceil() as u64)
+                    .header(CACHE_CONTROL, "no-store")
+                    .body(Body::from("too many requests"))?);
+            }
+        }
+    }
+
+    let parsed_req = req::parse(&req, state.args.max_request_body_size).await?;
 
     handle_http_counter(&parsed_req);
 
+    // other requests ignore the param, api clients and forms must not be redirected
+    let is_html_get =
+        req.method() == Method::GET && matches!(parsed_req.response_type, ResponseType::Html);
+    if let (Some(theme), true) = (parsed_req.set_theme, is_html_get) {
+        return Ok(Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(LOCATION, req::without_theme_param(&req))
+            .header(SET_COOKIE, theme.set_cookie())
+            .header(CACHE_CONTROL, "no-store")
+            .body(Body::empty())?);
+    }
+
+    // computed while determining if the address page is modified, so that it's not asked twice
+    let mut address_seen_prefetched = None;
+
     // DETERMINE IF NOT MODIFIED
     if let Some(if_modified_since) = req.headers().get(IF_MODIFIED_SINCE) {
         log::trace!("{:?} if modified since {:?}", req.uri(), if_modified_since);
@@ -77,6 +156,22 @@ pub async fn route(
             }
             Resource::Css => Some(CSS_LAST_MODIFIED.to_string()),
             Resource::Contact => Some(CONTACT_PAGE_LAST_MODIFIED.to_string()),
+            Resource::Manifest => Some(MANIFEST_LAST_MODIFIED.to_string()),
+            Resource::Address(address, _) => {
+                // checked before asking the db, which is the expensive part
+                let address = req::require_network(address, network())?;
+                match db.as_ref() {
+                    Some(db) => {
+                        let seen =
+                            address_seen(&address.script_pubkey(), db.clone(), state.clone())
+                                .await?;
+                        let modified = address_last_modified(&seen, &state).await;
+                        address_seen_prefetched = Some(seen);
+                        modified
+                    }
+                    None => None,
+                }
+            }
 
             _ => None,
         };
@@ -91,24 +186,44 @@ pub async fn route(
         }
     }
 
-    let resp = match parsed_req.resource {
+    let mut resp = match parsed_req.resource {
         Resource::Home => {
             let chain_info = state.chain_info.lock().await.clone();
 
+            let mempool_info = state.mempool_info.lock().await.clone();
             let mempool_section = MempoolSection {
-                info: state.mempool_info.lock().await.clone(),
+                info: mempool_info,
+                unit: parsed_req.unit,
+            }
+            .render();
+            // the cached section has links for the default unit
+            let block_template_section = match parsed_req.unit {
+                Unit::Btc => state.block_template_section.lock().await.clone(),
+                Unit::Sat => state.mempool_fees.lock().await.render(),
             };
-            let fees = state.mempool_fees.lock().await.clone();
 
             let minute_since_blocks = state.minutes_since_block.lock().await.clone();
             let height_time = state.height_time(chain_info.best_block_hash).await?;
+            let retarget = match network() {
+                Network::Bitcoin | Network::Testnet | Network::Testnet4 => {
+                    retarget(&state, &chain_info, &height_time).await
+                }
+                _ => None,
+            };
+            let fee_trend: Vec<f64> = state.mempool_fees.lock().await.fee_trend.clone().into();
+            let activity = activity(&state, &height_time, fee_trend).await;
+            let last_reorg = *state.last_reorg.lock().await;
+            let _phase = server_timing::Timer::new("render");
             let page = pages::home::page(
                 chain_info,
                 height_time,
                 mempool_section,
                 minute_since_blocks,
                 &parsed_req,
-                fees,
+                block_template_section,
+                retarget,
+                activity,
+                last_reorg,
             )
             .into_string();
 
@@ -120,7 +235,7 @@ pub async fn route(
                 ResponseType::Html => builder
                     .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
                     .body(page.into())?,
-                ResponseType::Bytes => {
+                ResponseType::Bytes | ResponseType::Json | ResponseType::Hex => {
                     return Err(Error::ContentTypeUnsupported(
                         parsed_req.response_type,
                         req.uri().to_string(),
@@ -131,16 +246,226 @@ pub async fn route(
 
         Resource::Block(block_hash, page) => {
             let block = rpc::block::call_json(block_hash).await?;
-            let page = pages::block::page(&block, page, &parsed_req)?.into_string();
             let current_tip = state.chain_info.lock().await.clone();
             let block_confirmations = current_tip.blocks - block.height;
-            let cache_seconds = cache_time_from_confirmations(Some(block_confirmations));
-            let cache_control = format!("public, max-age={cache_seconds}");
+            let cache_seconds =
+                cache_time_from_confirmations(Some(block_confirmations), &state.args);
+            let cache_control = match parsed_req.response_type {
+                // contains the confirmations
+                ResponseType::Json => "no-store".to_string(),
+                _ => format!("public, max-age={cache_seconds}"),
+            };
 
             let builder = Response::builder()
                 .header(CACHE_CONTROL, cache_control) // cache examples https://developers.cloudflare.com/cache/about/cache-control/#examples
                 .header(LAST_MODIFIED, block.date_time_utc());
 
+            if let ResponseType::Json = parsed_req.response_type {
+                let block_json = json::block::Block::new(&block, current_tip.blocks);
+                json::response(builder, &block_json)?
+            } else {
+                let coinbase = state.coinbase(block_hash, block.tx[0]).await?;
+                let coinbase_total = outpoints_and_sum(coinbase.as_ref())
+                    .expect("invalid tx bytes")
+                    .sum;
+                let coinbase: bitcoin::Transaction = deserialize(coinbase.as_ref())?;
+                let miner = pools::miner(&state.pools, &coinbase);
+                let signet = (network() == Network::Signet).then(|| {
+                    pages::block::Signet::new(current_tip.signet_challenge.as_deref(), &coinbase)
+                });
+                let median_time_past = state.median_time_past(block.height).await;
+                let _phase = server_timing::Timer::new("render");
+                let page = pages::block::page(
+                    &block,
+                    page,
+                    &parsed_req,
+                    coinbase_total,
+                    median_time_past,
+                    miner,
+                    signet,
+                )?
+                .into_string();
+                match parsed_req.response_type {
+                    ResponseType::Text(col) => builder
+                        .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                        .body(convert_text_html(&page, col))?,
+                    ResponseType::Html => builder
+                        .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+                        .body(page.into())?,
+                    ResponseType::Bytes | ResponseType::Json | ResponseType::Hex => {
+                        return Err(Error::ContentTypeUnsupported(
+                            parsed_req.response_type,
+                            req.uri().to_string(),
+                        ))
+                    }
+                }
+            }
+        }
+
+        Resource::Outspends(txid) => {
+            let (ser_tx, _) = state.tx(txid, false).await?;
+            let tx: bitcoin::Transaction = deserialize(ser_tx.as_ref())?;
+            let all = 0..tx.output.len();
+            let output_status = output_status(&state, db, txid, &tx.output, Some(all)).await;
+            let outspends = outspends(&state, txid, output_status).await?;
+
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=10");
+            match parsed_req.response_type {
+                ResponseType::Html | ResponseType::Json => json::response(builder, &outspends)?,
+                ResponseType::Text(_) | ResponseType::Bytes | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+
+        Resource::TxGraph(txid) => {
+            let (ser_tx, _) = state.tx(txid, false).await?;
+            let tx: bitcoin::Transaction = deserialize(ser_tx.as_ref())?;
+            let output_status = output_status(&state, db, txid, &tx.output, None).await;
+            let outspends = outspends(&state, txid, output_status).await?;
+            let graph = json::graph::TxGraph::new(txid, &tx, &outspends);
+
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=10");
+            match parsed_req.response_type {
+                ResponseType::Html | ResponseType::Json => json::response(builder, &graph)?,
+                ResponseType::Text(_) | ResponseType::Bytes | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+
+        Resource::MerkleProof(txid) => {
+            let block_hash = state.tx(txid, true).await?.1.ok_or(Error::NotFound)?;
+            let block = rpc::block::call_json(block_hash).await?;
+            let proof =
+                json::merkle_proof::MerkleProof::new(block_hash, block.height, &block.tx, txid)
+                    .ok_or(Error::NotFound)?;
+
+            let current_tip = state.chain_info.lock().await.clone();
+            let cache_seconds = cache_time_from_confirmations(
+                Some(current_tip.blocks.saturating_sub(block.height)),
+                &state.args,
+            );
+            let builder = Response::builder()
+                .header(CACHE_CONTROL, format!("public, max-age={cache_seconds}"));
+            match parsed_req.response_type {
+                ResponseType::Html | ResponseType::Json => json::response(builder, &proof)?,
+                ResponseType::Text(_) | ResponseType::Bytes | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+
+        Resource::BlockHashByHeight(height) => {
+            let block_hash = state
+                .height_to_hash(height)
+                .await
+                .filter(|h| *h != BlockHash::all_zeros())
+                .ok_or(Error::HeightNotFound)?;
+
+            let current_tip = state.chain_info.lock().await.blocks;
+            let cache_seconds = cache_time_from_confirmations(
+                Some(current_tip.saturating_sub(height)),
+                &state.args,
+            );
+            let builder = Response::builder()
+                .header(CACHE_CONTROL, format!("public, max-age={cache_seconds}"));
+            match parsed_req.response_type {
+                ResponseType::Json => json::response(
+                    builder,
+                    &rpc::blockhashbyheight::BlockHashByHeight { block_hash },
+                )?,
+                ResponseType::Html | ResponseType::Text(_) => builder
+                    .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                    .body(block_hash.to_string().into())?,
+                ResponseType::Bytes | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+
+        Resource::BlockHeader(block_hash, response_type) => {
+            let header = rpc::headers::call_many(block_hash, 1)
+                .await?
+                .pop()
+                .ok_or(Error::NotFound)?;
+
+            // the header committed by a block hash never changes
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=31536000");
+            match response_type {
+                ResponseType::Json => {
+                    json::response(builder, &json::block::BlockHeader::from(&header))?
+                }
+                _ => builder
+                    .header(CONTENT_TYPE, APPLICATION_OCTET_STREAM.as_ref())
+                    .body(serialize(&header).into())?,
+            }
+        }
+
+        Resource::MempoolFees => {
+            let fees = json::fees::RecommendedFees::new(&*state.mempool_fees.lock().await);
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=10");
+            json::response(builder, &fees)?
+        }
+
+        Resource::Mempool(page) => {
+            let by_rate = state.mempool_fees.lock().await.by_rate.clone();
+            let page = pages::mempool::page(&by_rate, page, &parsed_req)?.into_string();
+
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=10");
+            match parsed_req.response_type {
+                ResponseType::Text(col) => builder
+                    .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                    .body(convert_text_html(&page, col))?,
+                ResponseType::Html => builder
+                    .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+                    .body(page.into())?,
+                ResponseType::Bytes | ResponseType::Json | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+
+        Resource::Blocks(page) => {
+            let tip_height = state.chain_info.lock().await.blocks;
+            let skip = page as u64 * pages::blocks::PER_PAGE as u64;
+            if page > 0 && skip > tip_height as u64 {
+                return Err(Error::InvalidPageNumber);
+            }
+            let from = tip_height - skip as u32;
+            let to = from.saturating_sub(pages::blocks::PER_PAGE - 1);
+            let mut block_hashes = vec![];
+            for height in (to..=from).rev() {
+                match state.height_to_hash(height).await {
+                    Some(block_hash) if block_hash != BlockHash::all_zeros() => {
+                        block_hashes.push(block_hash)
+                    }
+                    _ => return Err(Error::HeightNotFound),
+                }
+            }
+            let blocks: Vec<_> = stream::iter(block_hashes)
+                .map(rpc::block::call_json)
+                .buffered(state.args.fetch_parallelism)
+                .try_collect()
+                .await?;
+            let page = pages::blocks::page(&blocks, page, &parsed_req).into_string();
+
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=10");
             match parsed_req.response_type {
                 ResponseType::Text(col) => builder
                     .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
@@ -148,7 +473,7 @@ pub async fn route(
                 ResponseType::Html => builder
                     .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
                     .body(page.into())?,
-                ResponseType::Bytes => {
+                ResponseType::Bytes | ResponseType::Json | ResponseType::Hex => {
                     return Err(Error::ContentTypeUnsupported(
                         parsed_req.response_type,
                         req.uri().to_string(),
@@ -159,7 +484,9 @@ pub async fn route(
 
         Resource::Tx(txid, pagination) => {
             if pagination > 0 {
-                if let ResponseType::Bytes = parsed_req.response_type {
+                if let ResponseType::Bytes | ResponseType::Json | ResponseType::Hex =
+                    parsed_req.response_type
+                {
                     return Err(Error::BadRequest);
                 }
             }
@@ -173,40 +500,130 @@ pub async fn route(
             let current_tip = state.chain_info.lock().await.clone();
             let mempool_fees = state.mempool_fees.lock().await.clone();
             let known_tx = state.known_txs.get(&txid).cloned();
+            let ancestors = if ts.is_none() {
+                ancestors_weight_fee(&tx, &state, &mempool_fees.mempool).await
+            } else {
+                None
+            };
 
-            let output_status = output_status(&state, db, txid, tx.output.len()).await;
-            let page = pages::tx::page(
-                txid,
-                &tx,
-                ts,
-                &prevouts,
-                output_status,
-                pagination,
-                mempool_fees,
-                &parsed_req,
-                false,
-                known_tx,
-            )?
-            .into_string();
-            let cache_seconds =
-                cache_time_from_confirmations(ts.map(|t| current_tip.blocks - t.1.height));
+            // the node is asked only about the outputs shown
+            let node_fallback = match parsed_req.response_type {
+                ResponseType::Html | ResponseType::Text(_) => {
+                    Some(pages::tx::output_range(&tx, pagination))
+                }
+                ResponseType::Json => Some(0..tx.output.len()),
+                ResponseType::Bytes | ResponseType::Hex => None,
+            };
+            let output_status = output_status(&state, db, txid, &tx.output, node_fallback).await;
+            let block_fee_rates = match ts {
+                Some((block_hash, _))
+                    if state.args.block_fee_percentile
+                        && !tx.is_coinbase()
+                        && matches!(
+                            parsed_req.response_type,
+                            ResponseType::Html | ResponseType::Text(_)
+                        ) =>
+                {
+                    match block_fee_rates(&state, block_hash).await {
+                        Ok(rates) => Some(rates),
+                        Err(e) => {
+                            log::warn!("cannot compute fee rates of block {block_hash}: {e:?}");
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+            let coin_ages = match parsed_req.response_type {
+                ResponseType::Html | ResponseType::Text(_) => {
+                    // blocks between the previous tx and this one, or the next block if unconfirmed
+                    let reference = match ts {
+                        Some((_, height_time)) => height_time.height,
+                        None => current_tip.blocks + 1,
+                    };
+                    let range = pages::tx::input_range(&tx, pagination);
+                    prevout_heights(&state, &tx.input[range])
+                        .await
+                        .into_iter()
+                        .map(|h| h.map(|h| reference.saturating_sub(h)))
+                        .collect()
+                }
+                _ => vec![],
+            };
+            let block_neighbors = match (ts, parsed_req.response_type) {
+                (Some((block_hash, _)), ResponseType::Html | ResponseType::Text(_)) => {
+                    match state.block_txids(block_hash).await {
+                        Ok(txids) => pages::tx::BlockNeighbors::new(&txids, txid),
+                        Err(e) => {
+                            log::warn!("cannot get txids of block {block_hash}: {e:?}");
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+            let cache_seconds = cache_time_from_confirmations(
+                ts.map(|t| current_tip.blocks - t.1.height),
+                &state.args,
+            );
 
-            let cache_control = format!("public, max-age={cache_seconds}");
+            let cache_control = match parsed_req.response_type {
+                // contains the confirmations
+                ResponseType::Json => "no-store".to_string(),
+                _ => format!("public, max-age={cache_seconds}"),
+            };
             let mut builder = Response::builder().header(CACHE_CONTROL, cache_control);
             if let Some(ts) = ts {
                 builder = builder.header(LAST_MODIFIED, ts.1.date_time_utc());
             }
 
             match parsed_req.response_type {
-                ResponseType::Text(col) => builder
-                    .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
-                    .body(convert_text_html(&page, col))?,
-                ResponseType::Html => builder
-                    .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
-                    .body(page.into())?,
+                ResponseType::Json => {
+                    let tx_json = json::tx::Tx::new(
+                        txid,
+                        &tx,
+                        ts,
+                        current_tip.blocks,
+                        &prevouts,
+                        &output_status,
+                        false,
+                    );
+                    json::response(builder, &tx_json)?
+                }
                 ResponseType::Bytes => builder
                     .header(CONTENT_TYPE, APPLICATION_OCTET_STREAM.as_ref())
                     .body(Bytes::from(ser_tx.0).into())?,
+                ResponseType::Hex => builder
+                    .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                    .body(serialize_hex(&tx).into())?,
+                response_type => {
+                    let _phase = server_timing::Timer::new("render");
+                    let page = pages::tx::page(
+                        txid,
+                        &tx,
+                        ts,
+                        &prevouts,
+                        output_status,
+                        pagination,
+                        mempool_fees,
+                        ancestors,
+                        block_fee_rates,
+                        &coin_ages,
+                        block_neighbors,
+                        &parsed_req,
+                        false,
+                        known_tx,
+                    )?
+                    .into_string();
+                    match response_type {
+                        ResponseType::Text(col) => builder
+                            .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                            .body(convert_text_html(&page, col))?,
+                        _ => builder
+                            .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+                            .body(page.into())?,
+                    }
+                }
             }
         }
 
@@ -248,8 +665,9 @@ pub async fn route(
 
             let (txid, vin) = visitor.1.ok_or(Error::NotFound)?;
             let network = network().as_url_path();
+            let unit = parsed_req.unit.link_query();
             Response::builder()
-                .header(LOCATION, format!("{network}t/{txid}#i{vin}"))
+                .header(LOCATION, format!("{network}t/{txid}{unit}#i{vin}"))
                 .status(StatusCode::TEMPORARY_REDIRECT)
                 .body(Body::empty())?
         }
@@ -283,21 +701,135 @@ pub async fn route(
                 .body(Body::empty())?
         }
 
-        Resource::SearchAddress(address) => {
+        Resource::SearchPrefix(ref prefix) => {
             let network = network().as_url_path();
+            let block_hashes = state.block_hashes_with_prefix(prefix).await;
+            let txids = state.txids_with_prefix(prefix).await;
+            let location = match (&block_hashes[..], &txids[..]) {
+                ([hash], []) => format!("{network}b/{hash}"),
+                ([], [txid]) => format!("{network}t/{txid}"),
+                ([], []) => return Err(Error::NotFound),
+                _ => return Err(Error::SearchPrefixAmbiguous(prefix.clone())),
+            };
             Response::builder()
-                .header(LOCATION, format!("{network}a/{address}"))
+                .header(LOCATION, location)
+                .status(StatusCode::TEMPORARY_REDIRECT)
+                .body(Body::empty())?
+        }
+
+        Resource::Broadcast => {
+            let body = read_body(req.body_mut(), state.args.max_request_body_size).await?;
+            let bytes = match std::str::from_utf8(&body).map(str::trim) {
+                Ok(s) if s.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    hex::decode(s).map_err(|_| Error::BadRequest)?
+                }
+                _ => body.to_vec(),
+            };
+            let tx: bitcoin::Transaction = deserialize(&bytes).map_err(|_| Error::BadRequest)?;
+            let txid = rpc::sendrawtransaction::call(&tx).await?;
+            let network = network().as_url_path();
+
+            Response::builder()
+                .header(LOCATION, format!("{network}t/{txid}"))
+                .status(StatusCode::SEE_OTHER) // the client must follow with a GET
+                .body(Body::empty())?
+        }
+
+        Resource::Addresses => {
+            let db = db.ok_or(Error::AddressIndexMissing("batch address queries"))?;
+            let body = read_body(req.body_mut(), state.args.max_request_body_size).await?;
+            let addresses: Vec<String> =
+                serde_json::from_slice(&body).map_err(|_| Error::BadRequest)?;
+            if addresses.len() > state.args.max_batch_addresses {
+                return Err(Error::BadRequest);
+            }
+            let addresses = addresses
+                .iter()
+                .map(|a| {
+                    let address = a.parse().map_err(|_| Error::BadRequest)?;
+                    req::require_network(&address, network())
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let result: BTreeMap<_, _> = stream::iter(addresses.iter())
+                .map(|address| {
+                    let db = db.clone();
+                    let state = state.clone();
+                    async move {
+                        let seen = address_seen(&address.script_pubkey(), db, state).await?;
+                        Ok::<_, Error>((
+                            address.to_string(),
+                            json::address::Address::new(address, &seen),
+                        ))
+                    }
+                })
+                .buffered(state.args.fetch_parallelism)
+                .try_collect()
+                .await?;
+
+            json::response(Response::builder(), &result)?
+        }
+
+        Resource::AdminCache(tx_cache_byte_size, txid_blockhash_len) => {
+            let token = state.args.admin_token.as_ref().ok_or(Error::NotFound)?;
+            let authorized = req
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .map(|v| token_eq(v, token))
+                .unwrap_or(false);
+            if !authorized {
+                return Err(Error::AdminUnauthorized);
+            }
+            state
+                .resize_caches(tx_cache_byte_size, txid_blockhash_len)
+                .await?;
+            let builder = Response::builder().header(CACHE_CONTROL, "no-store");
+            json::response(builder, &state.cache_stats().await)?
+        }
+
+        Resource::SearchAddress(ref address, ref params) => {
+            let network = network().as_url_path();
+            let location = match params {
+                Some(params) => format!("{network}a/{address}?{params}"),
+                None => format!("{network}a/{address}"),
+            };
+            Response::builder()
+                .header(LOCATION, location)
                 .status(StatusCode::TEMPORARY_REDIRECT)
                 .body(Body::empty())?
         }
 
         Resource::Head => Response::new(Body::empty()),
 
-        Resource::Css => Response::builder()
-            .header(LAST_MODIFIED, CSS_LAST_MODIFIED)
-            .header(CACHE_CONTROL, "public, max-age=31536000")
-            .header(CONTENT_TYPE, "text/css; charset=utf-8")
-            .body(Body::from(include_str!("css/pico.min.css")))?,
+        Resource::Preflight => {
+            let mut builder = Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .header(ALLOW, "GET, HEAD, POST, OPTIONS");
+            if state.args.cors_allow_origin.is_some() {
+                builder = builder
+                    .header(ACCESS_CONTROL_ALLOW_METHODS, "GET, HEAD, POST, OPTIONS")
+                    .header(ACCESS_CONTROL_ALLOW_HEADERS, "columns, content-type")
+                    .header(ACCESS_CONTROL_MAX_AGE, "86400");
+            }
+            builder.body(Body::empty())?
+        }
+
+        Resource::Css => {
+            let builder = Response::builder()
+                .header(LAST_MODIFIED, CSS_LAST_MODIFIED)
+                .header(CACHE_CONTROL, "public, max-age=31536000")
+                .header(CONTENT_TYPE, "text/css; charset=utf-8")
+                .header(VARY, "Accept-Encoding");
+            match Encoding::from_request(&req) {
+                // compressed at build time with `just css-gz`
+                Some(Encoding::Gzip) => builder
+                    .header(CONTENT_ENCODING, "gzip")
+                    .body(Body::from(&include_bytes!("css/pico.min.css.gz")[..]))?,
+                _ => builder.body(Body::from(include_str!("css/pico.min.css")))?,
+            }
+        }
 
         Resource::Contact => Response::builder()
             .header(LAST_MODIFIED, CONTACT_PAGE_LAST_MODIFIED)
@@ -305,6 +837,15 @@ pub async fn route(
             .header(CONTENT_TYPE, "text/html; charset=utf-8")
             .body(Body::from(pages::contact::page(&parsed_req)?.into_string()))?,
 
+        Resource::Manifest => {
+            let manifest = json::manifest::Manifest::for_network(network());
+            Response::builder()
+                .header(LAST_MODIFIED, MANIFEST_LAST_MODIFIED)
+                .header(CACHE_CONTROL, "public, max-age=31536000")
+                .header(CONTENT_TYPE, "application/manifest+json")
+                .body(serde_json::to_vec(&manifest)?.into())?
+        }
+
         Resource::Favicon => Response::builder()
             .header(LAST_MODIFIED, CONTACT_PAGE_LAST_MODIFIED)
             .header(CACHE_CONTROL, "public, max-age=31536000")
@@ -316,6 +857,16 @@ pub async fn route(
             .header(CACHE_CONTROL, "public, max-age=3600")
             .header(CONTENT_TYPE, "text/plain")
             .body(Bytes::from_static(include_bytes!("robots.txt")).into())?,
+        Resource::Feed => {
+            let origin = public_origin(&req, &state.args).ok_or(Error::NotFound)?;
+            let feed = feed::page(&state, &origin).await?;
+            Response::builder()
+                .header(CACHE_CONTROL, "public, max-age=60")
+                .header(VARY, "Host")
+                .header(CONTENT_TYPE, "application/atom+xml; charset=utf-8")
+                .body(feed.into())?
+        }
+        Resource::WebSocket => ws::upgrade(&mut req, &state)?,
         Resource::BlockToB(block_hash) => {
             let network = network().as_url_path();
             Response::builder()
@@ -337,26 +888,198 @@ pub async fn route(
                 .status(StatusCode::TEMPORARY_REDIRECT)
                 .body(Body::empty())?
         }
+        Resource::Xpub(ref key) => {
+            let db = db.ok_or(Error::AddressIndexMissing("extended public keys"))?;
+            if !key.is_current_network() {
+                return Err(Error::XpubWrongNetwork(network()));
+            }
+            let mut derived = vec![];
+            for chain in 0..2 {
+                for index in 0..xpub::DERIVED_PER_CHAIN {
+                    let address = key.derive(chain, index, network())?;
+                    let funding_count = db.script_hash_heights(&address.script_pubkey()).len();
+                    derived.push(pages::xpub::Derived {
+                        path: format!("{chain}/{index}"),
+                        address,
+                        funding_count,
+                    });
+                }
+            }
+            let page = pages::xpub::page(key, &derived, &parsed_req).into_string();
+
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=60");
+            match parsed_req.response_type {
+                ResponseType::Text(col) => builder
+                    .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                    .body(convert_text_html(&page, col))?,
+                ResponseType::Html => builder
+                    .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+                    .body(page.into())?,
+                ResponseType::Bytes | ResponseType::Json | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+
         Resource::Address(ref address, ref query) => {
-            let address = address.clone().require_network(network())?;
+            let address = req::require_network(address, network())?;
+
+            let address_seen = match (address_seen_prefetched, db) {
+                (Some(seen), _) => seen,
+                (None, Some(db)) => {
+                    address_seen(&address.script_pubkey(), db, state.clone()).await?
+                }
+                (None, None) => vec![],
+            };
+            let mut builder = Response::builder().header(CACHE_CONTROL, "public, max-age=60");
+            if let Some(last_modified) = address_last_modified(&address_seen, &state).await {
+                builder = builder.header(LAST_MODIFIED, last_modified);
+            }
 
+            let related = match parsed_req.response_type {
+                ResponseType::Html | ResponseType::Text(_) if state.args.related_addresses => {
+                    match related_addresses(&address, &address_seen, &state).await {
+                        Ok(related) => Some(related),
+                        Err(e) => {
+                            log::warn!("cannot compute addresses related to {address}: {e:?}");
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+
+            match parsed_req.response_type {
+                ResponseType::Json => {
+                    let address_json = json::address::Address::new(&address, &address_seen);
+                    json::response(builder, &address_json)?
+                }
+                ResponseType::Bytes => builder
+                    .header(CONTENT_TYPE, APPLICATION_OCTET_STREAM.as_ref())
+                    .body(funding_outpoints_bytes(&address_seen).into())?,
+                ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+                ResponseType::Text(col) => {
+                    let page =
+                        pages::address::page(&address, &parsed_req, query, address_seen, related)?
+                            .into_string();
+                    builder
+                        .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                        .body(pages::address::text_page(&address, &page, col)?.into())?
+                }
+                ResponseType::Html => {
+                    let page =
+                        pages::address::page(&address, &parsed_req, query, address_seen, related)?
+                            .into_string();
+                    builder
+                        .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+                        .body(page.into())?
+                }
+            }
+        }
+        Resource::AddressTxids(ref address) => {
+            let address = req::require_network(address, network())?;
+            let db = db.ok_or(Error::AddressIndexMissing("address txids"))?;
+            let address_seen = address_seen(&address.script_pubkey(), db, state.clone()).await?;
+
+            Response::builder()
+                .header(CACHE_CONTROL, "public, max-age=60")
+                .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                .body(address_txids(&address_seen).into())?
+        }
+        Resource::Script(ref script) => {
             let address_seen = if let Some(db) = db {
-                address_seen(&address, db, state.clone()).await?
+                address_seen(script, db, state.clone()).await?
             } else {
                 vec![]
             };
-            let page =
-                pages::address::page(&address, &parsed_req, query, address_seen)?.into_string();
             let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=60");
 
+            let page = pages::script::page(script, &parsed_req, address_seen)?.into_string();
             match parsed_req.response_type {
                 ResponseType::Text(col) => builder
                     .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
-                    .body(pages::address::text_page(&address, &page, col)?.into())?,
+                    .body(convert_text_html(&page, col))?,
                 ResponseType::Html => builder
                     .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
                     .body(page.into())?,
-                ResponseType::Bytes => {
+                ResponseType::Bytes | ResponseType::Json | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+        Resource::OpReturn(ref data) => {
+            let db = db
+                .filter(|db| db.index_op_return())
+                .ok_or(Error::OpReturnIndexMissing)?;
+            let seen = op_return_seen(data, db, state.clone()).await?;
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=60");
+
+            match parsed_req.response_type {
+                ResponseType::Json => {
+                    let seen: Vec<_> = seen.iter().map(json::op_return::OpReturn::from).collect();
+                    json::response(builder, &seen)?
+                }
+                ResponseType::Text(col) => {
+                    let page = pages::op_return::page(data, &parsed_req, seen).into_string();
+                    builder
+                        .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                        .body(convert_text_html(&page, col))?
+                }
+                ResponseType::Html => {
+                    let page = pages::op_return::page(data, &parsed_req, seen).into_string();
+                    builder
+                        .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+                        .body(page.into())?
+                }
+                ResponseType::Bytes | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+        Resource::TxDiff(old_txid, new_txid) => {
+            // the node usually forgets the replaced tx, unless it's cached
+            let (old_ser, _) = match state.tx(old_txid, false).await {
+                Err(Error::RpcTx(status, _)) if status == StatusCode::NOT_FOUND => {
+                    return Err(Error::OriginalTxNotAvailable(old_txid))
+                }
+                result => result?,
+            };
+            let old_tx: bitcoin::Transaction = deserialize(old_ser.as_ref())?;
+            let (new_ser, _) = state.tx(new_txid, false).await?;
+            let new_tx: bitcoin::Transaction = deserialize(new_ser.as_ref())?;
+            let old_wf = weight_fee(old_txid, &old_tx, &state).await?;
+            let new_wf = weight_fee(new_txid, &new_tx, &state).await?;
+
+            // txs and their prevouts never change
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=3600");
+            let page = pages::diff::page(
+                (old_txid, &old_tx, old_wf),
+                (new_txid, &new_tx, new_wf),
+                &parsed_req,
+            )
+            .into_string();
+            match parsed_req.response_type {
+                ResponseType::Text(col) => builder
+                    .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                    .body(convert_text_html(&page, col))?,
+                ResponseType::Html => builder
+                    .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+                    .body(page.into())?,
+                ResponseType::Bytes | ResponseType::Json | ResponseType::Hex => {
                     return Err(Error::ContentTypeUnsupported(
                         parsed_req.response_type,
                         req.uri().to_string(),
@@ -383,27 +1106,47 @@ pub async fn route(
                     .body(Body::empty())?
             }
         }
-        Resource::FullTx(ref tx) => {
-            let mempool_fees = state.mempool_fees.lock().await.clone();
-            let txid = tx.compute_txid();
-            let prevouts = fetch_prevouts(txid, tx, &state, true).await?;
-            let output_status = output_status(&state, db, txid, tx.output.len()).await;
-
-            let page = pages::tx::page(
-                txid,
-                tx,
-                None,
-                &prevouts,
-                output_status,
-                0,
-                mempool_fees,
-                &parsed_req,
-                true,
-                None,
-            )?
-            .into_string();
+        Resource::SearchPsbt(ref psbt) => {
+            let network = network().as_url_path();
+            Response::builder()
+                .header(LOCATION, format!("{network}psbt/{}", psbt.serialize_hex()))
+                .status(StatusCode::TEMPORARY_REDIRECT)
+                .body(Body::empty())?
+        }
+        Resource::Psbt(ref psbt) => {
+            let extracted = psbt.clone().extract_tx().ok();
+            let page = pages::psbt::page(psbt, extracted.as_ref(), &parsed_req).into_string();
+
             let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=3600");
+            match parsed_req.response_type {
+                ResponseType::Text(col) => builder
+                    .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                    .body(convert_text_html(&page, col))?,
+                ResponseType::Html => builder
+                    .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+                    .body(page.into())?,
+                ResponseType::Bytes | ResponseType::Json | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+        Resource::SearchInvoice(ref invoice) => {
+            let network = network().as_url_path();
+            Response::builder()
+                .header(LOCATION, format!("{network}invoice/{invoice}"))
+                .status(StatusCode::TEMPORARY_REDIRECT)
+                .body(Body::empty())?
+        }
+        Resource::Invoice(ref invoice) => {
+            if !invoice.is_current_network() {
+                return Err(Error::InvoiceWrongNetwork(network()));
+            }
+            let page = pages::invoice::page(invoice, &parsed_req).into_string();
 
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=3600");
             match parsed_req.response_type {
                 ResponseType::Text(col) => builder
                     .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
@@ -411,15 +1154,95 @@ pub async fn route(
                 ResponseType::Html => builder
                     .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
                     .body(page.into())?,
+                ResponseType::Bytes | ResponseType::Json | ResponseType::Hex => {
+                    return Err(Error::ContentTypeUnsupported(
+                        parsed_req.response_type,
+                        req.uri().to_string(),
+                    ))
+                }
+            }
+        }
+        Resource::FullTx(ref tx) => {
+            let mempool_fees = state.mempool_fees.lock().await.clone();
+            let txid = tx.compute_txid();
+            let prevouts = fetch_prevouts(txid, tx, &state, true).await?;
+            // the node knows nothing about a user provided tx which is not broadcasted
+            let output_status = output_status(&state, db, txid, &tx.output, None).await;
+            let ancestors = ancestors_weight_fee(tx, &state, &mempool_fees.mempool).await;
+            let builder = Response::builder().header(CACHE_CONTROL, "public, max-age=3600");
+
+            match parsed_req.response_type {
+                ResponseType::Json => {
+                    // a user provided tx is never confirmed, the tip height is unused
+                    let tx_json =
+                        json::tx::Tx::new(txid, tx, None, 0, &prevouts, &output_status, true);
+                    json::response(builder, &tx_json)?
+                }
                 ResponseType::Bytes => builder
                     .header(CONTENT_TYPE, APPLICATION_OCTET_STREAM.as_ref())
                     .body(Bytes::from(serialize(&tx)).into())?,
+                ResponseType::Hex => builder
+                    .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                    .body(serialize_hex(tx).into())?,
+                response_type => {
+                    let page = pages::tx::page(
+                        txid,
+                        tx,
+                        None,
+                        &prevouts,
+                        output_status,
+                        0,
+                        mempool_fees,
+                        ancestors,
+                        None,
+                        &[],
+                        None,
+                        &parsed_req,
+                        true,
+                        None,
+                    )?
+                    .into_string();
+                    match response_type {
+                        ResponseType::Text(col) => builder
+                            .header(CONTENT_TYPE, TEXT_PLAIN_UTF_8.as_ref())
+                            .body(convert_text_html(&page, col))?,
+                        _ => builder
+                            .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+                            .body(page.into())?,
+                    }
+                }
             }
         }
+        Resource::Health => {
+            let chain_info = state.chain_info.lock().await.clone();
+            let (tx_cache_len, tx_cache_full) = {
+                let txs = state.txs.lock().await;
+                (txs.len(), txs.full())
+            };
+            let health = json::health::Health {
+                blocks: chain_info.blocks,
+                synced: !chain_info.initial_block_download,
+                bootstrap_done: state.bootstrap_done.load(Ordering::Relaxed),
+                tx_cache_len,
+                tx_cache_full,
+                address_index_caught_up: db
+                    .as_ref()
+                    .map(|db| db.is_block_hash_indexed(&chain_info.best_block_hash)),
+            };
+            let status = if health.is_ready() {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            let builder = Response::builder()
+                .status(status)
+                .header(CACHE_CONTROL, "no-cache");
+            json::response(builder, &health)?
+        }
         Resource::Metrics => {
             let encoder = prometheus::TextEncoder::new();
 
-            let metric_families = prometheus::gather();
+            let metric_families = crate::globals::gather_metrics();
             let mut buffer = vec![];
             encoder.encode(&metric_families, &mut buffer)?;
             Response::builder()
@@ -429,75 +1252,329 @@ pub async fn route(
         }
     };
 
-    log::debug!("{:?} executed in {:?}", req.uri(), now.elapsed());
+    if let Some(origin) = state.args.cors_allow_origin.as_ref() {
+        if parsed_req.is_api() {
+            resp.headers_mut()
+                .insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        }
+    }
+
+    log::debug!(
+        "{:?} executed in {:?} client={}",
+        req.uri(),
+        now.elapsed(),
+        client_ip(&req, state.args.trust_forwarded_for)
+            .map_or("-".to_string(), |ip| ip.to_string())
+    );
+    resp.extensions_mut()
+        .insert(ResourceKind(parsed_req.resource.kind()));
 
     Ok(resp)
 }
 
 fn handle_http_counter(parsed_req: &req::ParsedRequest) {
-    let resource = match &parsed_req.resource {
-        Resource::Home => "Home",
-        Resource::Favicon => "Favicon",
-        Resource::Css => "Css",
-        Resource::Contact => "Contact",
-        Resource::SearchHeight(_) => "SearchHeight",
-        Resource::SearchBlock(_) => "SearchBlock",
-        Resource::SearchTx(_) => "SearchTx",
-        Resource::SearchAddress(_) => "SearchAddress",
-        Resource::SearchFullTx(_) => "SearchFullTx",
-        Resource::Tx(_, _) => "Tx",
-        Resource::Block(_, _) => "Block",
-        Resource::TxOut(_, _) => "TxOut",
-        Resource::Head => "Head",
-        Resource::Robots => "Robots",
-        Resource::BlockToB(_) => "BlockToB",
-        Resource::TxToT(_) => "TxToT",
-        Resource::Address(_, _) => "Address",
-        Resource::AddressToA(_) => "AddressToA",
-        Resource::FullTx(_) => "FullTx",
-        Resource::Metrics => "Metrics",
-    };
+    let resource = parsed_req.resource.kind();
     let content = match &parsed_req.response_type {
         ResponseType::Text(_) => "Text",
         ResponseType::Html => "Html",
         ResponseType::Bytes => "Bytes",
+        ResponseType::Json => "Json",
+        ResponseType::Hex => "Hex",
     };
     crate::HTTP_COUNTER
         .with_label_values(&[resource, content])
         .inc();
 }
 
+/// When the address index is not available, the node is asked via `getutxos` whether the
+/// outputs in the `node_fallback` range are still unspent. OP_RETURN outputs are never in the utxo
+/// set and they are not asked.
 async fn output_status(
     state: &Arc<SharedState>,
     db: Option<Arc<Database>>,
     txid: Txid,
-    len: usize,
+    outputs: &[TxOut],
+    node_fallback: Option<Range<usize>>,
 ) -> Vec<OutputStatus> {
+    let len = outputs.len();
+    let confirmed_spending = db.as_ref().map(|db| db.iter_spending(txid, len));
+    let mempool_spending = state.mempool_spending.lock().await;
     let mut result = Vec::with_capacity(len);
     for i in 0..len {
         let k = OutPoint::new(txid, i as u32);
-        let r = match state.mempool_spending.lock().await.get(&k).cloned() {
+        let r = match mempool_spending.get(&k).cloned() {
             Some(v) => OutputStatus::UnconfirmedSpent(v),
-            None => {
-                match db.as_ref() {
-                    Some(db) => {
-                        // TODO use iteration
-                        let outpoint = OutPoint::new(txid, i as u32);
-                        if let Some(res) = db.get_spending(&outpoint) {
-                            OutputStatus::ConfirmedSpent(res)
-                        } else {
-                            OutputStatus::Unspent
-                        }
+            None => match confirmed_spending.as_ref() {
+                Some(confirmed_spending) => match confirmed_spending[i] {
+                    Some(height) => OutputStatus::ConfirmedSpent(height),
+                    None => OutputStatus::Unspent,
+                },
+                None => OutputStatus::Unknown,
+            },
+        };
+        result.push(r);
+    }
+    drop(mempool_spending);
+
+    let node_fallback = match node_fallback {
+        Some(range) if db.is_none() => range,
+        _ => return result,
+    };
+    let mut unknown = vec![];
+    for i in node_fallback.start.min(len)..node_fallback.end.min(len) {
+        if outputs[i].script_pubkey.is_op_return() {
+            result[i] = OutputStatus::Unspent;
+        } else if matches!(result[i], OutputStatus::Unknown) {
+            unknown.push(i as u32);
+        }
+    }
+    let from_node: Vec<_> = stream::iter(unknown.chunks(rpc::txout::MAX_OUTPOINTS))
+        .map(|vouts| async move { (vouts, rpc::txout::call(txid, vouts).await) })
+        .buffered(state.args.fetch_parallelism)
+        .collect()
+        .await;
+    for (vouts, res) in from_node {
+        match res {
+            Ok(txout) => {
+                for (vout, unspent) in vouts.iter().zip(txout.unspent()) {
+                    result[*vout as usize] = if unspent {
+                        OutputStatus::Unspent
+                    } else {
+                        OutputStatus::Spent
+                    };
+                }
+            }
+            Err(e) => log::warn!("getutxos failed for {txid} {vouts:?} {e:?}"),
+        }
+    }
+
+    result
+}
+
+/// Convert `output_status` of the outputs of `txid` in the json schema. The spending txs of
+/// confirmed spent outputs are looked for in the blocks at the first [`OUTSPENDS_MAX_BLOCKS`]
+/// spending heights.
+async fn outspends(
+    state: &SharedState,
+    txid: Txid,
+    output_status: Vec<OutputStatus>,
+) -> Result<Vec<OutSpend>, Error> {
+    let mut heights: Vec<_> = output_status
+        .iter()
+        .filter_map(|s| match s {
+            OutputStatus::ConfirmedSpent(height) => Some(*height),
+            _ => None,
+        })
+        .collect();
+    heights.sort_unstable();
+    heights.dedup();
+    heights.truncate(OUTSPENDS_MAX_BLOCKS);
+
+    /// Collects the inputs spending outputs of `txid` as `vout -> (spending txid, vin)`
+    struct FindSpending {
+        txid: [u8; 32],
+        pending: Vec<(u32, usize)>,
+        found: HashMap<u32, (Txid, usize)>,
+    }
+    impl Visitor for FindSpending {
+        fn visit_transaction(&mut self, tx: &bsl::Transaction) -> core::ops::ControlFlow<()> {
+            let spending_txid: Txid = tx.txid().into();
+            for (vout, vin) in self.pending.drain(..) {
+                self.found.insert(vout, (spending_txid, vin));
+            }
+            core::ops::ControlFlow::Continue(())
+        }
+
+        fn visit_tx_in(&mut self, vin: usize, tx_in: &bsl::TxIn) -> core::ops::ControlFlow<()> {
+            let prevout = tx_in.prevout().as_ref();
+            if prevout[..32] == self.txid {
+                let vout = u32::from_le_bytes(prevout[32..36].try_into().expect("36 bytes"));
+                self.pending.push((vout, vin));
+            }
+            core::ops::ControlFlow::Continue(())
+        }
+    }
+    let mut visitor = FindSpending {
+        txid: txid.to_byte_array(),
+        pending: vec![],
+        found: HashMap::new(),
+    };
+    for (_, block) in state.blocks_from_heights(&heights).await? {
+        bsl::Block::visit(&block.0, &mut visitor).map_err(|_| Error::NotFound)?;
+    }
+
+    Ok(output_status
+        .into_iter()
+        .enumerate()
+        .map(|(vout, status)| {
+            let vout = vout as u32;
+            match status {
+                OutputStatus::ConfirmedSpent(height) => {
+                    let spending = visitor.found.get(&vout);
+                    OutSpend {
+                        spent: Some(true),
+                        txid: spending.map(|s| s.0),
+                        vin: spending.map(|s| s.1 as u32),
+                        height: Some(height),
                     }
-                    None => OutputStatus::Unknown,
                 }
+                OutputStatus::UnconfirmedSpent(s) => OutSpend {
+                    spent: Some(true),
+                    txid: Some(*s.txid()),
+                    vin: Some(s.vin()),
+                    height: None,
+                },
+                OutputStatus::Spent => OutSpend {
+                    spent: Some(true),
+                    ..OutSpend::unspent()
+                },
+                OutputStatus::Unspent => OutSpend::unspent(),
+                OutputStatus::Unknown => OutSpend {
+                    spent: None,
+                    ..OutSpend::unspent()
+                },
             }
+        })
+        .collect())
+}
+
+async fn retarget(
+    state: &SharedState,
+    chain_info: &ChainInfo,
+    tip: &HeightTime,
+) -> Option<Retarget> {
+    let period_start = tip.height - tip.height % RETARGET_INTERVAL;
+    let period_start_hash = state.height_to_hash(period_start).await?;
+    let period_start_time = state.height_time(period_start_hash).await.ok()?.time;
+    Some(Retarget::new(
+        tip.height,
+        tip.time,
+        period_start_time,
+        chain_info.difficulty,
+    ))
+}
+
+/// Block timestamps aren't strictly increasing, blocks are visited until this many seconds before
+/// the activity window
+const ACTIVITY_TIMESTAMP_TOLERANCE: u32 = 2 * 60 * 60;
+
+async fn activity(state: &SharedState, tip: &HeightTime, fee_trend: Vec<f64>) -> Activity {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or(tip.time);
+    let stop = now.saturating_sub(ACTIVITY_WINDOW + ACTIVITY_TIMESTAMP_TOLERANCE);
+    let mut block_times = vec![tip.time];
+    for height in (0..tip.height).rev() {
+        let time = match state.height_to_hash(height).await {
+            Some(block_hash) => match state.height_time(block_hash).await {
+                Ok(height_time) => height_time.time,
+                Err(_) => break,
+            },
+            None => break,
         };
-        result.push(r);
+        if time < stop {
+            break;
+        }
+        block_times.push(time);
+    }
+    Activity::new(&block_times, now, fee_trend)
+}
+
+/// The height of the most recent confirmed funding or spending, with its time if known. Spendings
+/// whose block is not fetched have only the height.
+fn address_last_activity(address_seen: &[AddressSeen]) -> Option<(Height, Option<HeightTime>)> {
+    address_seen
+        .iter()
+        .flat_map(|seen| {
+            [
+                Some((
+                    seen.funding.height_time.height,
+                    Some(seen.funding.height_time),
+                )),
+                seen.spending
+                    .as_ref()
+                    .map(|s| (s.height_time.height, Some(s.height_time))),
+                seen.spent_height.map(|height| (height, None)),
+            ]
+        })
+        .flatten()
+        .max_by_key(|(height, height_time)| (*height, height_time.is_some()))
+}
+
+/// The time of the most recent confirmed funding or spending, `None` if there is no history or
+/// the time is unknown
+async fn address_last_modified(
+    address_seen: &[AddressSeen],
+    state: &SharedState,
+) -> Option<String> {
+    let height_time = match address_last_activity(address_seen)? {
+        (_, Some(height_time)) => height_time,
+        (height, None) => {
+            let block_hash = state.height_to_hash(height).await?;
+            state.height_time(block_hash).await.ok()?
+        }
+    };
+    Some(height_time.date_time_utc())
+}
+
+/// The funding outpoints of the address history, each one consensus encoded in 36 bytes: the
+/// txid in internal byte order followed by the little endian vout
+fn funding_outpoints_bytes(address_seen: &[AddressSeen]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(address_seen.len() * 36);
+    for seen in address_seen {
+        seen.funding
+            .out_point
+            .consensus_encode(&mut bytes)
+            .expect("vec doesn't error");
+    }
+    bytes
+}
+
+/// Last line of [`address_txids`] when the list may be incomplete
+const TXIDS_TRUNCATED: &str = "# truncated";
+
+/// The txids funding and spending the outputs in `address_seen`, deduplicated, one per line.
+/// Followed by [`TXIDS_TRUNCATED`] if older fundings may be missing or some spending txs are not
+/// known, only their height.
+fn address_txids(address_seen: &[AddressSeen]) -> String {
+    let mut seen = HashSet::new();
+    let mut result = String::new();
+    let txids = address_seen.iter().flat_map(|s| {
+        std::iter::once(s.funding.out_point.txid).chain(s.spending.as_ref().map(|s| s.txid))
+    });
+    for txid in txids {
+        if seen.insert(txid) {
+            result.push_str(&format!("{txid}\n"));
+        }
+    }
+    let truncated = possibly_truncated(address_seen.iter().map(|s| s.funding.height_time.height))
+        || address_seen
+            .iter()
+            .any(|s| s.spent_height.is_some() && s.spending.is_none());
+    if truncated {
+        result.push_str(TXIDS_TRUNCATED);
+        result.push('\n');
     }
     result
 }
 
+/// Read the whole request body, failing as soon as it exceeds `max_size` bytes
+async fn read_body(body: &mut Body, max_size: usize) -> Result<Vec<u8>, Error> {
+    if HttpBody::size_hint(body).lower() > max_size as u64 {
+        return Err(Error::BadRequest);
+    }
+    let mut result = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if result.len() + chunk.len() > max_size {
+            return Err(Error::BadRequest);
+        }
+        result.extend_from_slice(&chunk);
+    }
+    Ok(result)
+}
+
 fn convert_text_html(page: &str, columns: u16) -> Body {
     convert_text_html_string(page, columns).into()
 }
@@ -506,15 +1583,18 @@ pub(crate) fn convert_text_html_string(page: &str, columns: u16) -> String {
     html2text::from_read_with_decorator(page.as_bytes(), columns as usize, BaseTextDecorator {})
 }
 
-fn cache_time_from_confirmations(confirmation: Option<u32>) -> u32 {
-    match confirmation {
-        None => 5,     // for txs, means it's unconfirmed
+/// Seconds a page can be cached, the deeper the confirmation the less likely a reorg changes it
+fn cache_time_from_confirmations(confirmation: Option<u32>, args: &crate::Arguments) -> u32 {
+    let deep_secs = args.cache_deep_secs();
+    let secs = match confirmation {
+        None => 5, // for txs, means it's unconfirmed
+        Some(c) if c >= args.cache_deep_confirmations => deep_secs,
         Some(0) => 60, // means it's the block at the top
         Some(1) => 60 * 5,
         Some(2) => 60 * 30,
-        Some(3) => 60 * 180,
-        _ => 60 * 60 * 24 * 30, // one month
-    }
+        Some(_) => 60 * 180,
+    };
+    secs.min(deep_secs)
 }
 
 pub async fn fetch_prevouts(
@@ -523,6 +1603,7 @@ pub async fn fetch_prevouts(
     state: &SharedState,
     fill_missing: bool,
 ) -> Result<Vec<bitcoin::TxOut>, Error> {
+    let _phase = server_timing::Timer::new("prevouts");
     if tx.input.len() > 1 {
         state.preload_prevouts(txid, tx).await;
     }
@@ -551,6 +1632,298 @@ pub async fn fetch_prevouts(
     Ok(prevouts)
 }
 
+/// Addresses of the inputs spent together with `address`, in the first
+/// [`RELATED_ADDRESSES_MAX_TXS`] txs spending from it
+async fn related_addresses(
+    address: &Address,
+    address_seen: &[AddressSeen],
+    state: &SharedState,
+) -> Result<Vec<Address>, Error> {
+    let mut txids = vec![];
+    for spending in address_seen.iter().filter_map(|s| s.spending.as_ref()) {
+        if !txids.contains(&spending.txid) {
+            txids.push(spending.txid);
+        }
+    }
+    let mut related = vec![];
+    for txid in txids.into_iter().take(RELATED_ADDRESSES_MAX_TXS) {
+        let (ser_tx, _) = state.tx(txid, false).await?;
+        let tx: bitcoin::Transaction = deserialize(ser_tx.as_ref())?;
+        for prevout in fetch_prevouts(txid, &tx, state, true).await? {
+            if let Ok(other) = Address::from_script(&prevout.script_pubkey, network()) {
+                if other != *address && !related.contains(&other) {
+                    related.push(other);
+                }
+            }
+        }
+    }
+    Ok(related)
+}
+
+/// Weight and fee of `tx`, `None` for the coinbase
+async fn weight_fee(
+    txid: Txid,
+    tx: &bitcoin::Transaction,
+    state: &SharedState,
+) -> Result<Option<WeightFee>, Error> {
+    if tx.is_coinbase() {
+        return Ok(None);
+    }
+    let prevouts = fetch_prevouts(txid, tx, state, false).await?;
+    let sum_inputs: u64 = prevouts.iter().map(|o| o.value.to_sat()).sum();
+    let sum_outputs: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
+    Ok(Some(WeightFee {
+        weight: tx.weight(),
+        fee: sum_inputs.saturating_sub(sum_outputs) as usize,
+    }))
+}
+
+/// Heights of the blocks including the txs spent by `inputs`, `None` for the coinbase input or if
+/// the previous tx is unconfirmed
+async fn prevout_heights(state: &SharedState, inputs: &[TxIn]) -> Vec<Option<u32>> {
+    stream::iter(inputs)
+        .map(|input| async move {
+            let txid = input.previous_output.txid;
+            if txid == Txid::all_zeros() {
+                return None;
+            }
+            let (_, block_hash) = state.tx(txid, true).await.ok()?;
+            Some(state.height_time(block_hash?).await.ok()?.height)
+        })
+        .buffered(state.args.fetch_parallelism)
+        .collect()
+        .await
+}
+
+/// Fee rates in sat/vB of the txs in the block, coinbase excluded, sorted ascending.
+/// Every previous output spent in the block is needed, so the result is cached.
+async fn block_fee_rates(
+    state: &SharedState,
+    block_hash: BlockHash,
+) -> Result<Arc<Vec<f64>>, Error> {
+    let cell = state
+        .block_fee_rates
+        .lock()
+        .await
+        .get_or_insert(block_hash, Default::default)
+        .clone();
+    let rates = cell
+        .get_or_try_init(|| compute_block_fee_rates(state, block_hash))
+        .await?;
+    Ok(rates.clone())
+}
+
+async fn compute_block_fee_rates(
+    state: &SharedState,
+    block_hash: BlockHash,
+) -> Result<Arc<Vec<f64>>, Error> {
+    let ser_block = rpc::block::call_raw(block_hash).await?;
+    let block: bitcoin::Block = deserialize(&ser_block.0)?;
+
+    // outputs created in the same block are taken from it, without inserting the whole block in
+    // the txs cache which would evict the entries in use
+    let block_txs: FxHashMap<Txid, &bitcoin::Transaction> = block
+        .txdata
+        .iter()
+        .map(|tx| (tx.compute_txid(), tx))
+        .collect();
+    let block_txs = &block_txs;
+
+    let mut rates: Vec<f64> = stream::iter(block.txdata.iter().skip(1))
+        .map(|tx| async move {
+            let mut sum_inputs = 0u64;
+            for input in tx.input.iter() {
+                let prevout = input.previous_output;
+                let value = match block_txs.get(&prevout.txid) {
+                    Some(prev_tx) => prev_tx
+                        .output
+                        .get(prevout.vout as usize)
+                        .ok_or(Error::NotFound)?
+                        .value
+                        .to_sat(),
+                    None => {
+                        let (prev_tx, _) = state.tx(prevout.txid, false).await?;
+                        tx_output(prev_tx.as_ref(), prevout.vout, false)
+                            .expect("invalid tx bytes")
+                            .value
+                            .to_sat()
+                    }
+                };
+                sum_inputs += value;
+            }
+            let sum_outputs: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
+            let wf = WeightFee {
+                weight: tx.weight(),
+                fee: sum_inputs.saturating_sub(sum_outputs) as usize,
+            };
+            Ok::<_, Error>(wf.rate_sat_over_vb())
+        })
+        .buffer_unordered(state.args.fetch_parallelism)
+        .try_collect()
+        .await?;
+    rates.sort_by(f64::total_cmp);
+
+    Ok(Arc::new(rates))
+}
+
+/// Sum of the weight and the fee of the unconfirmed ancestors of `tx`, `None` if there are none
+/// or some of them cannot be fetched
+async fn ancestors_weight_fee(
+    tx: &bitcoin::Transaction,
+    state: &SharedState,
+    mempool: &FxHashSet<Txid>,
+) -> Option<WeightFee> {
+    let mut to_visit: Vec<_> = tx
+        .input
+        .iter()
+        .map(|i| i.previous_output.txid)
+        .filter(|t| mempool.contains(t))
+        .collect();
+    let mut visited = FxHashSet::default();
+    let mut weight = Weight::ZERO;
+    let mut fee = 0u64;
+
+    while let Some(txid) = to_visit.pop() {
+        if visited.len() >= MAX_ANCESTORS {
+            break;
+        }
+        if !visited.insert(txid) {
+            continue;
+        }
+        let (ser_tx, _) = state.tx(txid, false).await.ok()?;
+        let OutPointsAndSum {
+            prevouts,
+            sum,
+            weight: tx_weight,
+        } = outpoints_and_sum(ser_tx.as_ref()).expect("invalid tx bytes");
+
+        let mut sum_inputs = 0u64;
+        for prevout in prevouts.iter() {
+            let (prev_tx, _) = state.tx(prevout.txid, false).await.ok()?;
+            let tx_out =
+                tx_output(prev_tx.as_ref(), prevout.vout, false).expect("invalid tx bytes");
+            sum_inputs += tx_out.value.to_sat();
+            if mempool.contains(&prevout.txid) {
+                to_visit.push(prevout.txid);
+            }
+        }
+        weight += tx_weight;
+        fee += sum_inputs.saturating_sub(sum);
+    }
+
+    (!visited.is_empty()).then_some(WeightFee {
+        weight,
+        fee: fee as usize,
+    })
+}
+
+/// State and address index of every network served by this process, the main network first
+pub type Served = Vec<(Network, Arc<SharedState>, Option<Arc<Database>>)>;
+
+/// Route the request to the network selected by the path prefix, eg. `/testnet/t/xxx...`, which is
+/// stripped. Requests without a prefix of a served network go to the `root_network()` if served,
+/// to the main network otherwise.
+pub async fn route_networks(
+    mut req: Request<Body>,
+    served: Arc<Served>,
+) -> Result<Response<Body>, Infallible> {
+    let mut selected = served
+        .iter()
+        .find(|entry| entry.0 == root_network())
+        .unwrap_or(&served[0]);
+    for entry in served.iter() {
+        let network = entry.0;
+        if network == root_network() {
+            continue; // root network urls have no prefix
+        }
+        let prefix = format!("/{network}");
+        let path = req.uri().path();
+        let rest = match path.strip_prefix(&prefix) {
+            Some("") => "/",
+            Some(rest) if rest.starts_with('/') => rest,
+            _ => continue,
+        };
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("{rest}?{query}"),
+            None => rest.to_string(),
+        };
+        if let Ok(uri) = path_and_query.parse() {
+            *req.uri_mut() = uri;
+            selected = entry;
+        }
+        break;
+    }
+    let (network, state, db) = selected.clone();
+    with_network(network, route_infallible(req, state, db)).await
+}
+
+/// Scheme and host for absolute links: the onion host if the request is made to it, the dns host
+/// otherwise, `None` if the needed one isn't configured
+fn public_origin(req: &Request<Body>, args: &crate::Arguments) -> Option<String> {
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.split(':').next().unwrap_or(h));
+    match args.onion_host.as_ref() {
+        Some(onion_host) if host == Some(onion_host.as_str()) => {
+            // onion services are already encrypted and usually don't have certificates
+            Some(format!("http://{onion_host}"))
+        }
+        _ => args.dns_host.as_ref().map(|h| format!("https://{h}")),
+    }
+}
+
+/// The client ip as reported by the last entry of `X-Forwarded-For`, which is the one appended by
+/// the proxy in front of us. When the header isn't trusted or is missing, the ip of the connection,
+/// missing for unix sockets.
+fn client_ip(req: &Request<Body>, trust_forwarded_for: bool) -> Option<IpAddr> {
+    trust_forwarded_for
+        .then(|| req.headers().get("x-forwarded-for")?.to_str().ok())
+        .flatten()
+        .and_then(|v| v.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+        .or_else(|| req.extensions().get::<RemoteIp>()?.0)
+}
+
+/// Compare the hashes of the tokens in constant time, so that the response time doesn't reveal
+/// how much of the token is right, nor its length
+fn token_eq(a: &str, b: &str) -> bool {
+    let a = sha256::Hash::hash(a.as_bytes());
+    let b = sha256::Hash::hash(b.as_bytes());
+    a.as_byte_array()
+        .iter()
+        .zip(b.as_byte_array())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+/// Kind of the resource served, carried in the response extensions for logging
+#[derive(Clone, Copy)]
+struct ResourceKind(&'static str);
+
+/// Ip of the client of the connection, carried in the request extensions
+#[derive(Clone, Copy)]
+pub struct RemoteIp(pub Option<IpAddr>);
+
+/// Add the request headers the response depends on to `Vary`, returns if the response is html
+fn add_vary(resp: &mut Response<Body>) -> bool {
+    // the response type can be chosen with the accept header
+    resp.headers_mut()
+        .append(VARY, HeaderValue::from_static("Accept"));
+    let is_html_resp = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .map_or(false, |c| c.as_bytes().starts_with(b"text/html"));
+    // html pages depend on the theme cookie, also without one since the cached page has the
+    // default theme
+    if is_html_resp || resp.headers().contains_key(SET_COOKIE) {
+        resp.headers_mut()
+            .append(VARY, HeaderValue::from_static("Cookie"));
+    }
+    is_html_resp
+}
+
 pub async fn route_infallible(
     req: Request<Body>,
     state: Arc<SharedState>,
@@ -560,15 +1933,152 @@ pub async fn route_infallible(
         .with_label_values(&["all"])
         .start_timer();
 
-    let resp = route(req, state, db).await.unwrap_or_else(|e| {
-        let body = format!("{}", e);
-        Response::builder()
+    let now = Instant::now();
+    let slow_request = Duration::from_millis(state.args.slow_request_ms);
+    let uri = req.uri().clone();
+    let client = client_ip(&req, state.args.trust_forwarded_for)
+        .map_or("-".to_string(), |ip| ip.to_string());
+
+    let encoding = Encoding::from_request(&req);
+    let is_html = matches!(req::response_type(&req), ResponseType::Html);
+    let theme = req::theme_cookie(&req).unwrap_or_default();
+    let server_timing = state.args.server_timing;
+    let cors_allow_origin = state.args.cors_allow_origin.clone();
+    // links rendered without the request at hand keep the unit
+    let routed = req::unit_param(&req).scope(route(req, state, db));
+    let (resp, phases) = if server_timing {
+        server_timing::scope(routed).await
+    } else {
+        (routed.await, vec![])
+    };
+    let resp = resp.unwrap_or_else(|e| {
+        let builder = Response::builder();
+        let (builder, body) = if is_html && pages::error::is_user_facing(&e) {
+            let page = pages::error::page(&e, theme).into_string();
+            (builder.header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref()), page)
+        } else {
+            (builder, format!("{}", e))
+        };
+        // the request may fail before knowing if it's for the api, so that API clients can read
+        // the error it's added to every error
+        let builder = match cors_allow_origin {
+            Some(origin) => builder.header(ACCESS_CONTROL_ALLOW_ORIGIN, origin),
+            None => builder,
+        };
+        builder
             .status(StatusCode::from(e)) // TODO map errors to bad request or internal error
             .body(body.into())
             .expect("msg")
     });
+    let mut resp = compress(encoding, resp).await;
+    let is_html_resp = add_vary(&mut resp);
+    if is_html_resp && server_timing {
+        let value = server_timing::header_value(&phases, now.elapsed());
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            resp.headers_mut().insert("server-timing", value);
+        }
+    }
 
     timer.observe_duration();
 
+    let elapsed = now.elapsed();
+    if elapsed > slow_request {
+        let kind = resp
+            .extensions()
+            .get::<ResourceKind>()
+            .map(|k| k.0)
+            .unwrap_or("Error");
+        let len = HttpBody::size_hint(resp.body()).exact();
+        log::warn!(
+            "slow request uri={uri} resource={kind} status={} bytes={len:?} elapsed={elapsed:?} client={client}",
+            resp.status().as_u16()
+        );
+    }
+
     Ok(resp)
 }
+
+#[cfg(test)]
+mod test {
+    use bitcoin::{hashes::Hash, BlockHash, OutPoint};
+    use hyper::{
+        header::{CONTENT_TYPE, VARY},
+        Body, Response,
+    };
+    use mime::{APPLICATION_JSON, TEXT_HTML_UTF_8};
+
+    use crate::{rpc::headers::HeightTime, threads::index_addresses::AddressSeen};
+
+    use super::{add_vary, address_last_activity, address_txids, TXIDS_TRUNCATED};
+
+    #[test]
+    fn test_add_vary() {
+        let vary = |resp: &Response<Body>| -> Vec<_> {
+            resp.headers()
+                .get_all(VARY)
+                .iter()
+                .map(|v| v.to_str().unwrap().to_string())
+                .collect()
+        };
+
+        // no cookie in the request, the page still depends on it
+        let mut resp = Response::builder()
+            .header(CONTENT_TYPE, TEXT_HTML_UTF_8.as_ref())
+            .body(Body::empty())
+            .unwrap();
+        assert!(add_vary(&mut resp));
+        assert_eq!(vary(&resp), vec!["Accept", "Cookie"]);
+
+        let mut resp = Response::builder()
+            .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+            .body(Body::empty())
+            .unwrap();
+        assert!(!add_vary(&mut resp));
+        assert_eq!(vary(&resp), vec!["Accept"]);
+    }
+
+    #[test]
+    fn test_address_last_activity() {
+        let height_time = |height| HeightTime { height, time: 0 };
+        let funding = |height| {
+            AddressSeen::new(
+                OutPoint::null(),
+                BlockHash::all_zeros(),
+                height_time(height),
+                1000,
+            )
+        };
+        assert_eq!(address_last_activity(&[]), None);
+
+        let mut seen = vec![funding(10), funding(20)];
+        assert_eq!(
+            address_last_activity(&seen),
+            Some((20, Some(height_time(20))))
+        );
+
+        // the older funding is spent after the last one, but its spending block is not fetched
+        seen[0].spent_height = Some(30);
+        assert_eq!(address_last_activity(&seen), Some((30, None)));
+    }
+
+    #[test]
+    fn test_address_txids() {
+        let mut seen = vec![AddressSeen::new(
+            OutPoint::null(),
+            BlockHash::all_zeros(),
+            HeightTime {
+                height: 10,
+                time: 0,
+            },
+            1000,
+        )];
+        let txids = address_txids(&seen);
+        assert_eq!(txids.lines().count(), 1);
+        assert!(!txids.contains(TXIDS_TRUNCATED));
+
+        // spent, but the spending tx is not known
+        seen[0].spent_height = Some(20);
+        let txids = address_txids(&seen);
+        assert_eq!(txids.lines().last(), Some(TXIDS_TRUNCATED));
+    }
+}
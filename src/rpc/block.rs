@@ -1,24 +1,25 @@
 // curl -s http://localhost:8332/rest/block/notxdetails/000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f.json | jq
 
-use super::{ts_to_date_time_utc, CLIENT};
+use super::{get, ts_to_date_time_utc};
 use crate::{
-    error::Error, globals::network, pages::NBSP, rpc::check_status, NetworkExt, NODE_REST_COUNTER,
+    error::Error, globals::network, pages::NBSP, rpc::check_status, state::SerTx, NetworkExt,
+    NODE_REST_COUNTER,
 };
 use bitcoin::{consensus::deserialize, Block, BlockHash, Txid};
-use hyper::body::Buf;
+use bitcoin_slices::{bsl, Visit, Visitor};
+use hyper::{body::Buf, StatusCode};
 use maud::{html, Markup};
 use serde::Deserialize;
 
 pub struct SerBlock(pub Vec<u8>);
 
 pub async fn call_json(block_hash: BlockHash) -> Result<BlockNoTxDetails, Error> {
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
 
     let uri =
         format!("http://{bitcoind_addr}/rest/block/notxdetails/{block_hash}.json",).parse()?;
     log::trace!("asking {:?}", uri);
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["block/notxdetails", "json"]).await?;
     NODE_REST_COUNTER
         .with_label_values(&["block/notxdetails", "json"])
         .inc();
@@ -35,11 +36,10 @@ pub async fn call(block_hash: BlockHash) -> Result<Block, Error> {
 }
 
 pub async fn call_raw(block_hash: BlockHash) -> Result<SerBlock, Error> {
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
 
     let uri = format!("http://{bitcoind_addr}/rest/block/{block_hash}.bin",).parse()?;
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["block", "bin"]).await?;
     NODE_REST_COUNTER.with_label_values(&["block", "bin"]).inc();
     check_status(resp.status(), |s| Error::RpcBlockRaw(s, block_hash)).await?;
     let body_bytes = hyper::body::to_bytes(resp.into_body()).await?;
@@ -47,6 +47,28 @@ pub async fn call_raw(block_hash: BlockHash) -> Result<SerBlock, Error> {
     Ok(SerBlock(body_bytes.to_vec()))
 }
 
+/// The coinbase of the block, parsing only the first transaction of the block bytes
+pub async fn call_coinbase(block_hash: BlockHash) -> Result<SerTx, Error> {
+    struct FirstTx(Option<Vec<u8>>);
+    impl Visitor for FirstTx {
+        fn visit_transaction(&mut self, tx: &bsl::Transaction) -> core::ops::ControlFlow<()> {
+            self.0 = Some(tx.as_ref().to_vec());
+            core::ops::ControlFlow::Break(())
+        }
+    }
+
+    let ser_block = call_raw(block_hash).await?;
+    let mut visitor = FirstTx(None);
+    match bsl::Block::visit(&ser_block.0, &mut visitor) {
+        Ok(_) | Err(bitcoin_slices::Error::VisitBreak) => (),
+        Err(_) => return Err(Error::RpcBlockRaw(StatusCode::OK, block_hash)),
+    }
+    visitor
+        .0
+        .map(SerTx)
+        .ok_or(Error::RpcBlockRaw(StatusCode::OK, block_hash))
+}
+
 #[derive(Deserialize)]
 pub struct BlockNoTxDetails {
     pub hash: BlockHash,
@@ -1,16 +1,15 @@
 // curl -s localhost:8332/rest/blockhashbyheight/1.json
 
-use super::{check_status, CLIENT};
+use super::{check_status, get};
 use crate::{error::Error, NODE_REST_COUNTER};
 use bitcoin::BlockHash;
 use hyper::body::Buf;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub async fn _call(height: usize) -> Result<BlockHashByHeight, Error> {
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
     let uri = format!("http://{bitcoind_addr}/rest/blockhashbyheight/{height}.json",).parse()?;
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["blockhashbyheight", "json"]).await?;
     NODE_REST_COUNTER
         .with_label_values(&["blockhashbyheight", "json"])
         .inc();
@@ -24,7 +23,7 @@ pub async fn _call(height: usize) -> Result<BlockHashByHeight, Error> {
     Ok(hash)
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct BlockHashByHeight {
     #[serde(rename = "blockhash")]
     pub block_hash: BlockHash,
@@ -1,11 +1,11 @@
-use super::{check_status, CLIENT};
+use super::{check_status, get};
 use crate::{error::Error, NODE_REST_COUNTER};
 use bitcoin::BlockHash;
 use hyper::body::Buf;
 use serde::Deserialize;
 
 // curl -s http://localhost:8332/rest/chaininfo.json | jq
-#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Clone, Debug, PartialEq)]
 pub struct ChainInfo {
     pub chain: String,
     pub blocks: u32,
@@ -15,15 +15,18 @@ pub struct ChainInfo {
     pub initial_block_download: bool,
 
     pub size_on_disk: u64,
+    pub difficulty: f64,
+
+    /// Hex of the script the blocks must satisfy, only on signet
+    pub signet_challenge: Option<String>,
 }
 
 // curl -s http://localhost:8332/rest/chaininfo.json | jq
 
 pub async fn call() -> Result<ChainInfo, Error> {
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
     let uri = format!("http://{bitcoind_addr}/rest/chaininfo.json",).parse()?;
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["chaininfo", "json"]).await?;
     NODE_REST_COUNTER
         .with_label_values(&["chaininfo", "json"])
         .inc();
@@ -5,7 +5,7 @@ use std::{
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use super::{check_status, ts_to_date_time_utc, CLIENT};
+use super::{check_status, get, ts_to_date_time_utc, ts_to_rfc3339};
 use crate::{error::Error, NODE_REST_COUNTER};
 use bitcoin::{consensus::Decodable, BlockHash};
 use hyper::body::Buf;
@@ -15,11 +15,10 @@ pub async fn call_many(
     block_hash: BlockHash,
     count: u32,
 ) -> Result<Vec<bitcoin::block::Header>, Error> {
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
     //let uri = format!("http://{bitcoind_addr}/rest/headers/{block_hash}.bin?count={count}").parse()?;  // TODO move to this with bitcoind 0.24
     let uri = format!("http://{bitcoind_addr}/rest/headers/{count}/{block_hash}.bin").parse()?;
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["headers/x", "bin"]).await?;
     NODE_REST_COUNTER
         .with_label_values(&["headers/x", "bin"])
         .inc();
@@ -42,10 +41,9 @@ pub async fn call_many(
 }
 
 pub async fn call_one(block_hash: BlockHash) -> Result<BlockheaderJson, Error> {
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
     let uri = format!("http://{bitcoind_addr}/rest/headers/1/{block_hash}.json").parse()?;
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["headers/1", "bin"]).await?;
     NODE_REST_COUNTER
         .with_label_values(&["headers/1", "bin"])
         .inc();
@@ -84,6 +82,10 @@ impl HeightTime {
         ts_to_date_time_utc(self.time)
     }
 
+    pub fn rfc3339(&self) -> String {
+        ts_to_rfc3339(self.time)
+    }
+
     pub(crate) fn since_now(&self) -> std::time::Duration {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
         Duration::from_secs(now.as_secs().saturating_sub(self.time as u64))
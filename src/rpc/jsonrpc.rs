@@ -0,0 +1,69 @@
+//! Authenticated bitcoind JSON-RPC calls, for what the REST interface doesn't offer, like
+//! broadcasting. Reads keep using REST, which doesn't need credentials.
+
+use super::request;
+use crate::globals::{bitcoind_addr, rpc_auth, RpcAuth};
+use crate::{error::Error, NODE_REST_COUNTER};
+use base64::Engine;
+use hyper::{
+    body::Buf,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Body, Method, Request, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+// curl --user user:pass --data-binary '{"jsonrpc":"1.0","id":"fbbe","method":"getblockcount","params":[]}' http://localhost:8332/
+/// Call `method` with `params`, a json array. Errors returned by the node, like an invalid
+/// parameter, are [`Error::RpcCallFailed`]
+pub async fn call<T: DeserializeOwned>(
+    method: &'static str,
+    params: serde_json::Value,
+) -> Result<T, Error> {
+    let auth = match rpc_auth().ok_or(Error::RpcAuthMissing)? {
+        RpcAuth::UserPassword(user_password) => user_password.clone(),
+        // read at every call since the node rewrites it at every restart
+        RpcAuth::CookieFile(path) => std::fs::read_to_string(path)?.trim().to_string(),
+    };
+    let auth = base64::engine::general_purpose::STANDARD.encode(auth);
+
+    let body = json!({
+        "jsonrpc": "1.0",
+        "id": "fbbe",
+        "method": method,
+        "params": params,
+    });
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://{}/", bitcoind_addr()))
+        .header(AUTHORIZATION, format!("Basic {auth}"))
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(&body)?))?;
+
+    let resp = request(req, &[method, "json"]).await?;
+    NODE_REST_COUNTER.with_label_values(&[method, "json"]).inc();
+
+    // Core returns an error status code with the reason in the body when the call fails
+    if resp.status() == StatusCode::UNAUTHORIZED {
+        return Err(Error::RpcUnauthorized);
+    }
+    let body_bytes = hyper::body::to_bytes(resp.into_body()).await?;
+    let resp: RpcResponse = serde_json::from_reader(body_bytes.reader())?;
+    match resp.error {
+        Some(error) => Err(Error::RpcCallFailed(method, error.message)),
+        // a null result is valid for some methods, it is up to `T` to accept it, eg. `()`
+        None => Ok(serde_json::from_value(resp.result)?),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcResponse {
+    #[serde(default)]
+    result: serde_json::Value,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    message: String,
+}
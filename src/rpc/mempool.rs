@@ -1,7 +1,7 @@
 // GET /rest/mempool/info.json
 // GET /rest/mempool/contents.json
 
-use super::{check_status, CLIENT};
+use super::{check_status, get};
 use crate::{error::Error, NODE_REST_COUNTER};
 use bitcoin::Txid;
 use fxhash::FxHashSet;
@@ -11,11 +11,10 @@ use std::collections::HashMap;
 
 // curl -s http://localhost:8332/rest/mempool/info.json | jq
 pub async fn info() -> Result<MempoolInfo, Error> {
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
 
     let uri = format!("http://{bitcoind_addr}/rest/mempool/info.json").parse()?;
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["mempool/info", "json"]).await?;
     NODE_REST_COUNTER
         .with_label_values(&["mempool/info", "json"])
         .inc();
@@ -30,11 +29,10 @@ pub struct Empty {}
 
 // curl -s http://localhost:8332/rest/mempool/contents.json?verbose=false | jq
 pub async fn content(support_verbose: bool) -> Result<FxHashSet<Txid>, Error> {
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
 
     let uri = format!("http://{bitcoind_addr}/rest/mempool/contents.json?verbose=false").parse()?;
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["mempool/contents", "json"]).await?;
     NODE_REST_COUNTER
         .with_label_values(&["mempool/contents", "json"])
         .inc();
@@ -1,7 +1,12 @@
-use crate::error::Error;
+use crate::{
+    error::Error,
+    globals::{rpc_retries, rpc_timeout},
+    server_timing, NODE_REST_HISTOGRAM,
+};
 use chrono::DateTime;
-use hyper::{client::HttpConnector, Client, StatusCode};
+use hyper::{client::HttpConnector, Body, Client, Request, Response, StatusCode, Uri};
 use once_cell::sync::Lazy;
+use std::time::Duration;
 
 pub static CLIENT: Lazy<Client<HttpConnector>> = Lazy::new(Client::new);
 
@@ -9,15 +14,60 @@ pub mod block;
 pub mod blockhashbyheight;
 pub mod chaininfo;
 pub mod headers;
+pub mod jsonrpc;
 pub mod mempool;
+pub mod sendrawtransaction;
 pub mod tx;
 pub mod txout;
 
-fn ts_to_date_time_utc(ts: u32) -> String {
+pub(crate) fn ts_to_date_time_utc(ts: u32) -> String {
     let ndt = DateTime::from_timestamp(ts as i64, 0).unwrap();
     ndt.format("%Y-%m-%d %H:%M:%S %Z").to_string() // 2022-11-18 07:53:03 UTC
 }
 
+fn ts_to_rfc3339(ts: u32) -> String {
+    let ndt = DateTime::from_timestamp(ts as i64, 0).unwrap();
+    ndt.format("%Y-%m-%dT%H:%M:%SZ").to_string() // 2022-11-18T07:53:03Z
+}
+
+/// GET `uri` from bitcoind, failing with [`Error::RpcTimeout`] if it doesn't answer in time.
+///
+/// Calls answered with 503 are retried up to `--rpc-retries` times with increasing delay, other
+/// statuses like 404 (tx or block not found) are returned immediately.
+///
+/// `labels` are the method and the content type of the call, as in `NODE_REST_COUNTER`
+async fn get(uri: Uri, labels: &[&str]) -> Result<Response<Body>, Error> {
+    let mut delay = Duration::from_millis(100);
+    let mut retries = rpc_retries();
+    loop {
+        let resp = request(Request::get(uri.clone()).body(Body::empty())?, labels).await?;
+        if resp.status() != StatusCode::SERVICE_UNAVAILABLE || retries == 0 {
+            return Ok(resp);
+        }
+        log::debug!("{uri} returned 503, retrying in {delay:?}");
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        retries -= 1;
+    }
+}
+
+/// Send `req` to bitcoind, failing with [`Error::RpcTimeout`] if the whole response, body
+/// included, isn't received in time. The returned body is already in memory.
+/// The time to receive the response is observed in `NODE_REST_HISTOGRAM`
+async fn request(req: Request<Body>, labels: &[&str]) -> Result<Response<Body>, Error> {
+    let _timer = NODE_REST_HISTOGRAM.with_label_values(labels).start_timer();
+    let _phase = server_timing::Timer::new("rpc");
+    let receive = async {
+        let (parts, body) = CLIENT.request(req).await?.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        Ok::<_, hyper::Error>(Response::from_parts(parts, Body::from(body)))
+    };
+    match tokio::time::timeout(rpc_timeout(), receive).await {
+        Ok(resp) => Ok(resp?),
+        Err(_) => Err(Error::RpcTimeout),
+    }
+}
+
 async fn check_status<F: FnOnce(StatusCode) -> Error>(
     status: StatusCode,
     error: F,
@@ -0,0 +1,12 @@
+use super::jsonrpc;
+use crate::error::Error;
+use bitcoin::{consensus::encode::serialize_hex, Transaction, Txid};
+use serde_json::json;
+
+// REST doesn't support broadcasting, JSON-RPC is used instead
+pub async fn call(tx: &Transaction) -> Result<Txid, Error> {
+    match jsonrpc::call("sendrawtransaction", json!([serialize_hex(tx)])).await {
+        Err(Error::RpcCallFailed(_, message)) => Err(Error::TxRejected(message)),
+        result => result,
+    }
+}
@@ -1,18 +1,19 @@
-use std::str::FromStr;
-
-use super::{check_status, CLIENT};
+use super::{check_status, get};
 use crate::error::Error;
+use crate::globals::network;
 use crate::state::SerTx;
 use crate::NODE_REST_COUNTER;
 use bitcoin::consensus::serialize;
 use bitcoin::{blockdata::constants::genesis_block, BlockHash, Network, Txid};
 use hyper::body::Buf;
-use once_cell::sync::Lazy;
 use serde::Deserialize;
 
-static GENESIS_TX: Lazy<Txid> = Lazy::new(|| {
-    Txid::from_str("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b").unwrap()
-});
+/// The genesis coinbase of the current `network()`, it's not part of the utxo set and it's not
+/// returned by the node. Computed at every call since a process may serve networks with different
+/// genesis, like testnet4.
+fn genesis_txid() -> Txid {
+    genesis_block(network()).txdata[0].compute_txid()
+}
 
 // curl -s http://localhost:8332/rest/tx/3d0db8e24ffab61fb96e8a8fc5a0b14989b6e851495232018192b3e98f6b904e.json | jq
 pub async fn call_json(txid: Txid) -> Result<TxJson, Error> {
@@ -28,13 +29,12 @@ pub async fn call_json_only_hash(txid: Txid) -> Result<Option<BlockHash>, Error>
 }
 
 async fn json_bytes(txid: Txid) -> Result<hyper::body::Bytes, Error> {
-    if txid == *GENESIS_TX {
+    if txid == genesis_txid() {
         return Err(Error::GenesisTx);
     }
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
     let uri = format!("http://{bitcoind_addr}/rest/tx/{txid}.json").parse()?;
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["tx", "json"]).await?;
     NODE_REST_COUNTER.with_label_values(&["tx", "json"]).inc();
     check_status(resp.status(), |s| Error::RpcTxJson(s, txid)).await?;
     let body_bytes = hyper::body::to_bytes(resp.into_body()).await?;
@@ -59,14 +59,13 @@ pub async fn call_parse_json(
 }
 
 pub async fn call_raw(txid: Txid) -> Result<Vec<u8>, Error> {
-    if txid == *GENESIS_TX {
-        return Err(Error::GenesisTx);
+    if txid == genesis_txid() {
+        return Ok(serialize(&genesis_block(network()).txdata[0]));
     }
-    let client = CLIENT.clone();
     let bitcoind_addr = crate::globals::bitcoind_addr();
 
     let uri = format!("http://{bitcoind_addr}/rest/tx/{txid}.bin").parse()?;
-    let resp = client.get(uri).await?;
+    let resp = get(uri, &["tx", "bin"]).await?;
     NODE_REST_COUNTER.with_label_values(&["tx", "bin"]).inc();
 
     check_status(resp.status(), |s| Error::RpcTx(s, txid)).await?;
@@ -1,22 +1,27 @@
-use super::{check_status, tx::ScriptPubKey, CLIENT};
+use super::{check_status, get, tx::ScriptPubKey};
 use crate::{error::Error, NODE_REST_COUNTER};
 use bitcoin::{BlockHash, Txid};
 use hyper::body::Buf;
 use serde::Deserialize;
 
-// curl -s localhost:8332/rest/getutxos/checkmempool/f63db148598c3f3a7ae4590a7f70f16968e01872455281a8e487f6992721febc-0.json | jq
-pub async fn _call(txid: Txid, vout: u32) -> Result<TxOutJson, Error> {
-    let client = CLIENT.clone();
+/// Maximum number of outpoints the node accepts in a single `getutxos` request
+pub const MAX_OUTPOINTS: usize = 15;
+
+// curl -s localhost:8332/rest/getutxos/checkmempool/f63db148598c3f3a7ae4590a7f70f16968e01872455281a8e487f6992721febc-0/f63db148598c3f3a7ae4590a7f70f16968e01872455281a8e487f6992721febc-1.json | jq
+/// Query the outputs `vouts` of `txid` in a single request, at most [`MAX_OUTPOINTS`] of them
+pub async fn call(txid: Txid, vouts: &[u32]) -> Result<TxOutJson, Error> {
     let bitcoind_addr = crate::globals::bitcoind_addr();
 
+    let outpoints: Vec<_> = vouts.iter().map(|vout| format!("{txid}-{vout}")).collect();
+    let outpoints = outpoints.join("/");
     let uri =
-        format!("http://{bitcoind_addr}/rest/getutxos/checkmempool/{txid}-{vout}.json").parse()?;
-    let resp = client.get(uri).await?;
+        format!("http://{bitcoind_addr}/rest/getutxos/checkmempool/{outpoints}.json").parse()?;
+    let resp = get(uri, &["getutxos/checkmempool", "json"]).await?;
     NODE_REST_COUNTER
         .with_label_values(&["getutxos/checkmempool", "json"])
         .inc();
 
-    check_status(resp.status(), |s| Error::RpcTxOut(s, txid, vout)).await?;
+    check_status(resp.status(), |s| Error::RpcTxOut(s, txid)).await?;
     let body_bytes = hyper::body::to_bytes(resp.into_body()).await?;
     let tx: TxOutJson = serde_json::from_reader(body_bytes.reader())?;
     Ok(tx)
@@ -30,10 +35,18 @@ pub struct TxOutJson {
     #[serde(rename = "chaintipHash")]
     pub chaintip_hash: BlockHash,
 
+    /// A `1` for every unspent outpoint of the request, a `0` for the spent ones
     pub bitmap: String,
     pub utxos: Vec<Utxo>,
 }
 
+impl TxOutJson {
+    /// Whether the outpoints of the request are unspent, in the same order
+    pub fn unspent(&self) -> impl Iterator<Item = bool> + '_ {
+        self.bitmap.chars().map(|c| c == '1')
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Utxo {
     pub height: u32,
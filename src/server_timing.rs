@@ -0,0 +1,133 @@
+//! `Server-Timing` header of the html responses, with the time spent in the main phases of a
+//! request, eg. the calls to bitcoind and the rendering.
+//!
+//! Phases are measured with [`Timer`]s collecting into a task local, so that the functions
+//! measured don't need to return their timings.
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+struct Phase {
+    name: &'static str,
+    duration: Duration,
+
+    /// Number of live timers of this phase
+    running: usize,
+
+    /// Since when at least one timer of this phase is live, meaningful if `running > 0`
+    since: Instant,
+}
+
+type Phases = Arc<Mutex<Vec<Phase>>>;
+
+tokio::task_local! {
+    static PHASES: Phases;
+}
+
+/// Measures a phase until dropped. The duration of a phase is the wall time when at least one of
+/// its timers is live, thus concurrent calls count once. Different phases may nest, eg. the "rpc"
+/// calls made while looking for the "prevouts". Outside [`scope`] it does nothing.
+pub(crate) struct Timer {
+    name: &'static str,
+}
+
+impl Timer {
+    pub(crate) fn new(name: &'static str) -> Self {
+        let now = Instant::now();
+        let _ = PHASES.try_with(|phases| {
+            let mut phases = phases.lock().expect("not poisoned");
+            match phases.iter_mut().find(|phase| phase.name == name) {
+                Some(phase) => {
+                    if phase.running == 0 {
+                        phase.since = now;
+                    }
+                    phase.running += 1;
+                }
+                None => phases.push(Phase {
+                    name,
+                    duration: Duration::ZERO,
+                    running: 1,
+                    since: now,
+                }),
+            }
+        });
+        Timer { name }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let _ = PHASES.try_with(|phases| {
+            let mut phases = phases.lock().expect("not poisoned");
+            if let Some(phase) = phases.iter_mut().find(|phase| phase.name == self.name) {
+                phase.running -= 1;
+                if phase.running == 0 {
+                    phase.duration += phase.since.elapsed();
+                }
+            }
+        });
+    }
+}
+
+/// Run `f` returning also the phases measured in it, in the order they started first
+pub(crate) async fn scope<F: Future>(f: F) -> (F::Output, Vec<(&'static str, Duration)>) {
+    let phases = Phases::default();
+    let output = PHASES.scope(phases.clone(), f).await;
+    let phases = std::mem::take(&mut *phases.lock().expect("not poisoned"));
+    let phases = phases
+        .into_iter()
+        .map(|phase| (phase.name, phase.duration))
+        .collect();
+    (output, phases)
+}
+
+/// Value of the `Server-Timing` header, eg. `rpc;dur=12.5, render;dur=0.3, total;dur=13.1`
+pub(crate) fn header_value(phases: &[(&'static str, Duration)], total: Duration) -> String {
+    phases
+        .iter()
+        .chain(std::iter::once(&("total", total)))
+        .map(|(name, duration)| format!("{name};dur={:.1}", duration.as_secs_f64() * 1000.0))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{header_value, scope, Timer};
+
+    #[tokio::test]
+    async fn test_server_timing() {
+        let ((), phases) = scope(async {
+            drop(Timer::new("rpc"));
+            drop(Timer::new("render"));
+            drop(Timer::new("rpc"));
+        })
+        .await;
+        let names: Vec<_> = phases.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["rpc", "render"]);
+
+        // concurrent timers of the same phase count the wall time once
+        let ((), phases) = scope(async {
+            let timers: Vec<_> = (0..10).map(|_| Timer::new("rpc")).collect();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(timers);
+        })
+        .await;
+        assert!(phases[0].1 >= Duration::from_millis(20));
+        assert!(phases[0].1 < Duration::from_millis(200));
+
+        // outside a scope nothing is collected nor panics
+        drop(Timer::new("rpc"));
+
+        let phases = [("rpc", Duration::from_micros(12_345))];
+        assert_eq!(
+            header_value(&phases, Duration::from_millis(20)),
+            "rpc;dur=12.3, total;dur=20.0"
+        );
+    }
+}
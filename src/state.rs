@@ -1,27 +1,34 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::ops::ControlFlow;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::Instant;
 
 use bitcoin::consensus::Encodable;
 use bitcoin::hashes::Hash;
 use bitcoin::OutPoint;
-use bitcoin::{Block, BlockHash, Transaction, Txid, Weight};
+use bitcoin::{Block, BlockHash, Network, Transaction, Txid, Weight};
 use bitcoin_slices::Parse;
 use bitcoin_slices::{bsl, SliceCache, Visit, Visitor};
 use futures::prelude::*;
 use fxhash::FxHashMap;
 use fxhash::FxHashSet;
 use lru::LruCache;
-use prometheus::Registry;
-use tokio::sync::{Mutex, MutexGuard};
+use maud::{Markup, Render};
+use prometheus::{IntCounterVec, Opts, Registry};
+use tokio::sync::{broadcast, Mutex, MutexGuard, OnceCell};
 
 use crate::cache_counter;
 use crate::rpc::block::SerBlock;
 use crate::{
     error::Error,
+    json::cache::Cache,
     network,
+    pools::Pool,
     rpc::{self, chaininfo::ChainInfo, headers::HeightTime, mempool::MempoolInfo},
-    threads::update_mempool_info::TxidWeightFee,
+    threads::update_mempool_info::{
+        TxidWeightFee, TxidWeightFeeCompact, WeightFee, FEE_ESTIMATE_TARGETS,
+    },
     Arguments,
 };
 
@@ -65,27 +72,130 @@ impl From<&Txid> for TruncTxid {
     }
 }
 
+/// Cache of serialized transactions, `Txid -> Transaction`, remembering the insertion order so
+/// that the content can be moved in a cache of a different size
+pub struct TxCache {
+    cache: SliceCache<Txid>,
+
+    /// The txids in `cache`, oldest first as they are evicted
+    txids: VecDeque<Txid>,
+}
+
+impl TxCache {
+    pub fn new(size: usize) -> Self {
+        TxCache {
+            cache: SliceCache::new(size),
+            txids: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&self, txid: &Txid) -> Option<&[u8]> {
+        self.cache.get(txid)
+    }
+
+    pub fn contains(&self, txid: &Txid) -> bool {
+        self.cache.contains(txid)
+    }
+
+    /// Returns whether `tx` is inserted, it isn't if already present or larger than the cache
+    pub fn insert<V: AsRef<[u8]>>(&mut self, txid: Txid, tx: &V) -> bool {
+        match self.cache.insert(txid, tx) {
+            Ok(removed) => {
+                self.txids.drain(..removed.min(self.txids.len()));
+                // a tx as big as the cache may evict itself
+                if self.cache.len() > self.txids.len() {
+                    self.txids.push_back(txid);
+                }
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn avg(&self) -> f64 {
+        self.cache.avg()
+    }
+
+    pub fn full(&self) -> bool {
+        self.cache.full()
+    }
+
+    /// A cache of `size` bytes with the same txs, the oldest are evicted if they don't fit
+    fn resized(&self, size: usize) -> Self {
+        let mut resized = TxCache::new(size);
+        for txid in self.txids.iter() {
+            if let Some(tx) = self.cache.get(txid) {
+                resized.insert(*txid, &tx);
+            }
+        }
+        resized
+    }
+
+    /// Register the hit and miss counters, replacing the ones of a previous cache
+    fn register_metric(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        // equivalent to the counters of `SliceCache`, which are not accessible
+        let previous = IntCounterVec::new(
+            Opts::new("slice_cache", "Counters for cache Hit/Miss"),
+            &["event"],
+        )?;
+        let _ = registry.unregister(Box::new(previous));
+        self.cache.register_metric(registry)
+    }
+}
+
+/// Number of blocks for which the fee rates of the txs are kept
+const BLOCK_FEE_RATES_CACHE_LEN: usize = 16;
+
+/// Number of blocks for which the txids are kept
+const BLOCK_TXIDS_CACHE_LEN: usize = 16;
+
+/// Elements checked by the prefix searches before releasing the lock, so that other tasks are not
+/// blocked for long
+const PREFIX_SCAN_CHUNK: usize = 100_000;
+
 pub struct SharedState {
     // pub requests: AtomicUsize,
     // pub rpc_calls: AtomicUsize,
     pub chain_info: Mutex<ChainInfo>,
 
     /// By default 100MB of cached transactions, `Txid -> Transaction`
-    pub txs: Mutex<SliceCache<Txid>>,
+    pub txs: Mutex<TxCache>,
 
     /// A cache to know in which block hash a tx is in
     tx_in_block: Mutex<LruCache<TruncTxid, BlockHash>>,
 
     hash_to_height_time: Mutex<FxHashMap<BlockHash, HeightTime>>,
 
+    /// Sorted fee rates in sat/vB of the txs in the block, used with `--block-fee-percentile`.
+    /// The cell is inserted before computing them, so that concurrent requests for the same
+    /// block wait the first one instead of computing them again
+    pub block_fee_rates: Mutex<LruCache<BlockHash, Arc<OnceCell<Arc<Vec<f64>>>>>>,
+
+    /// Txids of the block in block order, used to link the txs before and after a confirmed tx
+    block_txids: Mutex<LruCache<BlockHash, Arc<Vec<Txid>>>>,
+
     /// mainnet 800k -> at least 800_000 * 32 B = 25.6 MB
     height_to_hash: Mutex<Vec<BlockHash>>, // all zero if missing
 
     pub args: Arguments,
-    pub mempool_info: Mutex<MempoolInfo>,
     pub mempool_fees: Mutex<BlockTemplate>,
+
+    /// The data of the mempool section of the home page, rendered at every request since it
+    /// depends on the unit requested
+    pub mempool_info: Mutex<MempoolInfo>,
+
+    /// The fee estimation and block template sections of the home page, rendered when
+    /// `mempool_fees` is updated
+    pub block_template_section: Mutex<Markup>,
     pub minutes_since_block: Mutex<Option<String>>,
 
+    /// The most recent reorg seen since startup
+    pub last_reorg: Mutex<Option<Reorg>>,
+
     // Added when found tx in mempool, removed when not in mempool
     // for each inputs in the mempool the SpendPoint and the relatvie spent OutPoint
     // if the mempool has 100k with an average of 1.5 inputs, we have 150k*(36+36) = 10MB
@@ -93,6 +203,25 @@ pub struct SharedState {
 
     /// A note on known transactions
     pub known_txs: HashMap<Txid, String>,
+
+    /// Known mining pools, to show who mined a block
+    pub pools: Vec<Pool>,
+
+    /// Set when the bootstrap thread completed successfully
+    pub bootstrap_done: AtomicBool,
+
+    /// Serialized [`crate::ws::Event`]s pushed to websocket clients
+    pub events: broadcast::Sender<String>,
+}
+
+/// A chain reorganization
+#[derive(Debug, Clone, Copy)]
+pub struct Reorg {
+    /// Number of blocks disconnected
+    pub depth: u32,
+
+    /// Height of the first block disconnected, and when the reorg was seen
+    pub at: HeightTime,
 }
 
 #[derive(Debug, Clone)]
@@ -131,6 +260,17 @@ pub struct BlockTemplate {
 
     /// Transactions in the mempool
     pub mempool: FxHashSet<Txid>,
+
+    /// Mempool transactions with known fee, sorted by descending fee rate.
+    /// In an `Arc` because the template is cloned on every tx page request.
+    pub by_rate: Arc<Vec<TxidWeightFeeCompact>>,
+
+    /// Suggested fee rate for every target in `FEE_ESTIMATE_TARGETS`, `None` if the mempool is
+    /// smaller than the target
+    pub fee_estimates: [Option<WeightFee>; FEE_ESTIMATE_TARGETS.len()],
+
+    /// Hourly samples of the `middle_in_block` fee rate in sat/vB, oldest first
+    pub fee_trend: VecDeque<f64>,
 }
 
 impl SharedState {
@@ -139,10 +279,21 @@ impl SharedState {
         args: Arguments,
         mempool_info: MempoolInfo,
         known_txs: HashMap<Txid, String>,
+        pools: Vec<Pool>,
         registry: &Registry,
     ) -> Self {
-        let txs = SliceCache::new(args.tx_cache_byte_size());
+        let txs = TxCache::new(args.tx_cache_byte_size());
         txs.register_metric(registry).unwrap(); // TODO
+        let block_template = BlockTemplate {
+            highest: None,
+            last_in_block: None,
+            middle_in_block: None,
+            transactions: None,
+            mempool: FxHashSet::default(),
+            by_rate: Arc::new(Vec::new()),
+            fee_trend: VecDeque::new(),
+            fee_estimates: Default::default(),
+        };
         Self {
             // requests: AtomicUsize::new(0),
             // rpc_calls: AtomicUsize::new(0),
@@ -150,20 +301,24 @@ impl SharedState {
             txs: Mutex::new(txs),
             tx_in_block: Mutex::new(LruCache::new(args.txid_blockhash_len().try_into().unwrap())), //TODO
             hash_to_height_time: Mutex::new(FxHashMap::default()),
+            block_fee_rates: Mutex::new(LruCache::new(
+                BLOCK_FEE_RATES_CACHE_LEN.try_into().expect("not zero"),
+            )),
+            block_txids: Mutex::new(LruCache::new(
+                BLOCK_TXIDS_CACHE_LEN.try_into().expect("not zero"),
+            )),
             height_to_hash: Mutex::new(Vec::new()),
             args,
             mempool_info: Mutex::new(mempool_info),
-
-            mempool_fees: Mutex::new(BlockTemplate {
-                highest: None,
-                last_in_block: None,
-                middle_in_block: None,
-                transactions: None,
-                mempool: FxHashSet::default(),
-            }),
+            block_template_section: Mutex::new(block_template.render()),
+            mempool_fees: Mutex::new(block_template),
             minutes_since_block: Mutex::new(None),
+            last_reorg: Mutex::new(None),
             mempool_spending: Mutex::new(FxHashMap::default()),
             known_txs,
+            pools,
+            bootstrap_done: AtomicBool::new(false),
+            events: broadcast::channel(16).0,
         }
     }
 
@@ -190,10 +345,123 @@ impl SharedState {
             .cloned()
     }
 
+    /// Block hashes whose hex representation starts with `prefix`
+    pub async fn block_hashes_with_prefix(&self, prefix: &str) -> Vec<BlockHash> {
+        let mut result = vec![];
+        let mut start = 0;
+        loop {
+            let height_to_hash = self.height_to_hash.lock().await;
+            let chunk = height_to_hash.get(start..).unwrap_or_default();
+            let chunk = &chunk[..chunk.len().min(PREFIX_SCAN_CHUNK)];
+            if chunk.is_empty() {
+                break;
+            }
+            result.extend(
+                chunk
+                    .iter()
+                    .filter(|h| **h != BlockHash::all_zeros())
+                    .filter(|h| hash_starts_with(h.as_byte_array(), prefix)),
+            );
+            start += chunk.len();
+            drop(height_to_hash);
+            tokio::task::yield_now().await;
+        }
+        result
+    }
+
+    /// Mempool, known and cached txids, like the ones in recent blocks, whose hex representation
+    /// starts with `prefix`
+    pub async fn txids_with_prefix(&self, prefix: &str) -> Vec<Txid> {
+        let matching = |t: &&Txid| hash_starts_with(t.as_byte_array(), prefix);
+        let mut result: Vec<Txid> = {
+            let mempool_fees = self.mempool_fees.lock().await;
+            mempool_fees
+                .mempool
+                .iter()
+                .filter(matching)
+                .cloned()
+                .collect()
+        };
+        result.extend(self.known_txs.keys().filter(matching));
+
+        // the cache changes between the chunks, a tx may be missed if it's inserted meanwhile
+        let mut start = 0;
+        loop {
+            let txs = self.txs.lock().await;
+            let end = txs.txids.len().min(start + PREFIX_SCAN_CHUNK);
+            if start >= end {
+                break;
+            }
+            result.extend(txs.txids.range(start..end).filter(matching));
+            start = end;
+            drop(txs);
+            tokio::task::yield_now().await;
+        }
+
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    /// Resize the caches, `None` keeps the current size.
+    ///
+    /// The tx cache cannot shrink or grow in place, so a new one is created with the same txs, the
+    /// oldest ones are dropped if it's smaller. Its metrics replace the ones of the previous cache.
+    pub async fn resize_caches(
+        &self,
+        tx_cache_byte_size: Option<usize>,
+        txid_blockhash_len: Option<usize>,
+    ) -> Result<(), Error> {
+        if let Some(len) = txid_blockhash_len {
+            let len = len.try_into().map_err(|_| Error::BadRequest)?;
+            self.tx_in_block.lock().await.resize(len);
+        }
+        if let Some(size) = tx_cache_byte_size {
+            let mut txs = self.txs.lock().await;
+            let resized = txs.resized(size);
+            resized.register_metric(crate::globals::registry())?;
+            *txs = resized;
+        }
+        Ok(())
+    }
+
+    pub async fn cache_stats(&self) -> Cache {
+        let (tx_cache_len, tx_cache_avg, tx_cache_full) = {
+            let txs = self.txs.lock().await;
+            (txs.len(), txs.avg(), txs.full())
+        };
+        let tx_in_block = self.tx_in_block.lock().await;
+        Cache {
+            tx_cache_len,
+            tx_cache_avg,
+            tx_cache_full,
+            txid_blockhash_len: tx_in_block.len(),
+            txid_blockhash_cap: tx_in_block.cap().get(),
+        }
+    }
+
     pub async fn tx_in_block(&self, txid: &Txid) -> Option<BlockHash> {
+        if let Some(blocks) = duplicate_coinbase(txid, network()) {
+            // the node indexes the later block, which is the one with spendable outputs
+            return Some(blocks[1].1);
+        }
         self.tx_in_block.lock().await.get(&txid.into()).cloned()
     }
 
+    pub async fn block_txids(&self, block_hash: BlockHash) -> Result<Arc<Vec<Txid>>, Error> {
+        let cached = self.block_txids.lock().await.get(&block_hash).cloned();
+        cache_counter("block_txids", cached.is_some());
+        if let Some(txids) = cached {
+            return Ok(txids);
+        }
+        let txids = Arc::new(rpc::block::call_json(block_hash).await?.tx);
+        self.block_txids
+            .lock()
+            .await
+            .push(block_hash, txids.clone());
+        Ok(txids)
+    }
+
     pub async fn add_tx_in_block(&self, txid: Txid, block_hash: BlockHash) {
         self.tx_in_block.lock().await.push(txid.into(), block_hash);
     }
@@ -234,6 +502,36 @@ impl SharedState {
         }
     }
 
+    /// Median of the timestamps of the (up to) 11 blocks preceding `height`, `None` for genesis or
+    /// if the time of a preceding block is not available.
+    ///
+    /// Timestamps are taken from the `hash_to_height_time` cache, so usually no RPC is made.
+    pub async fn median_time_past(&self, height: u32) -> Option<u32> {
+        let mut times = Vec::with_capacity(11);
+        for h in height.saturating_sub(11)..height {
+            let block_hash = self.height_to_hash(h).await?;
+            match self.height_time(block_hash).await {
+                Ok(height_time) => times.push(height_time.time),
+                Err(e) => {
+                    log::warn!("cannot get the time of block {block_hash}: {e:?}");
+                    return None;
+                }
+            }
+        }
+        times.sort_unstable();
+        times.get(times.len() / 2).copied()
+    }
+
+    /// The coinbase of `block_hash`, from the cache or parsing only the first tx of the block
+    pub async fn coinbase(&self, block_hash: BlockHash, txid: Txid) -> Result<SerTx, Error> {
+        if let Some(tx) = self.txs.lock().await.get(&txid) {
+            return Ok(SerTx(tx.to_vec()));
+        }
+        let tx = rpc::block::call_coinbase(block_hash).await?;
+        let _ = self.txs.lock().await.insert(txid, &tx.0);
+        Ok(tx)
+    }
+
     pub async fn tx(
         &self,
         txid: Txid,
@@ -353,6 +651,21 @@ impl SharedState {
         }
     }
 
+    /// Evict the `tx_in_block` entries of the txs in `block`, which has been disconnected by a reorg.
+    ///
+    /// Entries already pointing to another block are kept, txs also included in the new chain are
+    /// re-pointed when the new block is added with `update_cache`.
+    pub async fn disconnect_block(&self, block: &Block) {
+        let block_hash = block.block_hash();
+        let mut tx_in_block = self.tx_in_block.lock().await;
+        for tx in block.txdata.iter() {
+            let txid: TruncTxid = tx.compute_txid().into();
+            if tx_in_block.peek(&txid) == Some(&block_hash) {
+                tx_in_block.pop(&txid);
+            }
+        }
+    }
+
     pub async fn update_cache(&self, block: &Block, height: Option<u32>) -> Result<(), Error> {
         let block_hash = block.block_hash();
         let time = block.header.time;
@@ -387,6 +700,57 @@ impl SharedState {
     }
 }
 
+/// Coinbase txids included in two mainnet blocks before BIP30, with the heights and the hashes of
+/// both blocks
+const DUPLICATE_COINBASES: [(&str, [(u32, &str); 2]); 2] = [
+    (
+        "d5d27987d2a3dfc724e359870c6644b40e497bdc0589a033220fe15429d88599",
+        [
+            (
+                91812,
+                "00000000000af0aed4792b1acee3d966af36cf5def14935db8de83d6f9306f2f",
+            ),
+            (
+                91842,
+                "00000000000a4d0a398161ffc163c503763b1f4360639393e0e4c8e300e0caec",
+            ),
+        ],
+    ),
+    (
+        "e3bf3d07d4b0375638d5f1db5255fe07ba2c4cb067cd81b84ee974b6585fb468",
+        [
+            (
+                91722,
+                "00000000000271a2dc26e7667f8419f2e15416dc6955e5a6c6cdf3f2574dd08e",
+            ),
+            (
+                91880,
+                "00000000000743f190a18c5577a3c2d2a1f610ae9601ac046a38084ccb7cd721",
+            ),
+        ],
+    ),
+];
+
+/// The two blocks, older first, containing `txid` if it's one of the duplicate coinbases
+pub fn duplicate_coinbase(txid: &Txid, network: Network) -> Option<[(u32, BlockHash); 2]> {
+    if network != Network::Bitcoin {
+        return None;
+    }
+    let txid = txid.to_string();
+    let (_, blocks) = DUPLICATE_COINBASES.iter().find(|(t, _)| *t == txid)?;
+    Some(blocks.map(|(height, hash)| (height, hash.parse().expect("valid hash"))))
+}
+
+/// Whether the hash, displayed in the usual reversed byte order, starts with the hex `prefix`
+fn hash_starts_with(hash: &[u8; 32], prefix: &str) -> bool {
+    prefix.len() <= 64
+        && prefix.chars().enumerate().all(|(i, c)| {
+            let byte = hash[31 - i / 2];
+            let nibble = if i % 2 == 0 { byte >> 4 } else { byte & 0x0f };
+            c.to_digit(16) == Some(nibble as u32)
+        })
+}
+
 pub(crate) fn reserve(height_to_hash: &mut MutexGuard<Vec<BlockHash>>, height: usize) {
     if height_to_hash.len() <= height {
         height_to_hash.resize(height + 1000, BlockHash::all_zeros());
@@ -465,7 +829,32 @@ pub fn tx_output(
 #[cfg(test)]
 mod test {
 
-    use crate::state::outpoints_and_sum;
+    use std::str::FromStr;
+
+    use bitcoin::{hashes::Hash, Network, Txid};
+
+    use crate::state::{duplicate_coinbase, hash_starts_with, outpoints_and_sum, TxCache};
+
+    #[test]
+    fn test_tx_cache_resized() {
+        let txid = |i: u8| Txid::from_byte_array([i; 32]);
+        let mut cache = TxCache::new(35);
+        for i in 0..5 {
+            assert!(cache.insert(txid(i), &[i; 10]));
+        }
+        assert!(!cache.insert(txid(4), &[4; 10]));
+        assert_eq!(cache.len(), 3);
+
+        let bigger = cache.resized(100);
+        assert_eq!(bigger.len(), 3);
+        assert_eq!(bigger.get(&txid(4)), Some(&[4u8; 10][..]));
+
+        // the oldest is dropped
+        let smaller = bigger.resized(25);
+        assert_eq!(smaller.len(), 2);
+        assert!(!smaller.contains(&txid(2)));
+        assert!(smaller.contains(&txid(4)));
+    }
 
     #[test]
     fn test_prevouts() {
@@ -475,4 +864,32 @@ mod test {
         assert_eq!(res.sum, 100000000);
         assert_eq!(res.prevouts.len(), 1);
     }
+
+    #[test]
+    fn test_duplicate_coinbase() {
+        let txid =
+            Txid::from_str("e3bf3d07d4b0375638d5f1db5255fe07ba2c4cb067cd81b84ee974b6585fb468")
+                .unwrap();
+        let blocks = duplicate_coinbase(&txid, Network::Bitcoin).unwrap();
+        assert_eq!(blocks[0].0, 91722);
+        assert_eq!(blocks[1].0, 91880);
+        assert!(duplicate_coinbase(&txid, Network::Testnet).is_none());
+
+        let txid =
+            Txid::from_str("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+                .unwrap();
+        assert!(duplicate_coinbase(&txid, Network::Bitcoin).is_none());
+    }
+
+    #[test]
+    fn test_hash_starts_with() {
+        let txid =
+            Txid::from_str("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+                .unwrap();
+        let bytes = txid.as_byte_array();
+        assert!(hash_starts_with(bytes, "4a5e1e4b"));
+        assert!(hash_starts_with(bytes, "4a5e1e4ba"));
+        assert!(!hash_starts_with(bytes, "4a5e1e4bb"));
+        assert!(!hash_starts_with(bytes, "3b"));
+    }
 }
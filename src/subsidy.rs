@@ -0,0 +1,70 @@
+//! Block subsidy and issued coins, following the halving schedule.
+
+use bitcoin::Network;
+
+/// Blocks between halvings, regtest halves every 150 blocks
+fn halving_interval(network: Network) -> u32 {
+    match network {
+        Network::Regtest => 150,
+        _ => 210_000,
+    }
+}
+
+/// Block subsidy in satoshi at the given `height`
+pub fn subsidy(height: u32, network: Network) -> u64 {
+    let halvings = height / halving_interval(network);
+    if halvings >= 64 {
+        0
+    } else {
+        (50 * 100_000_000) >> halvings
+    }
+}
+
+/// Sum in satoshi of the subsidies of the blocks from the genesis to `height` included.
+///
+/// It's an upper bound of the coins in circulation: the genesis output is unspendable and some
+/// miners claimed less than allowed.
+pub fn supply(height: u32, network: Network) -> u64 {
+    let interval = halving_interval(network);
+    let mut total = 0;
+    let mut start = 0u32;
+    while start <= height {
+        let current = subsidy(start, network);
+        if current == 0 {
+            break;
+        }
+        let end = start.saturating_add(interval - 1).min(height);
+        total += (end - start + 1) as u64 * current;
+        match start.checked_add(interval) {
+            Some(next) => start = next,
+            None => break,
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod test {
+    use bitcoin::Network;
+
+    use super::{subsidy, supply};
+
+    #[test]
+    fn test_subsidy() {
+        assert_eq!(subsidy(0, Network::Bitcoin), 5_000_000_000);
+        assert_eq!(subsidy(209_999, Network::Bitcoin), 5_000_000_000);
+        assert_eq!(subsidy(210_000, Network::Bitcoin), 2_500_000_000);
+        assert_eq!(subsidy(840_000, Network::Bitcoin), 312_500_000);
+        assert_eq!(subsidy(64 * 210_000, Network::Bitcoin), 0);
+        assert_eq!(subsidy(150, Network::Regtest), 2_500_000_000);
+    }
+
+    #[test]
+    fn test_supply() {
+        assert_eq!(supply(0, Network::Bitcoin), 5_000_000_000);
+        assert_eq!(supply(209_999, Network::Bitcoin), 1_050_000_000_000_000);
+        assert_eq!(supply(840_000, Network::Bitcoin), 1_968_750_312_500_000);
+        assert_eq!(supply(u32::MAX, Network::Bitcoin), 2_099_999_997_690_000);
+        assert_eq!(supply(150, Network::Regtest), 752_500_000_000);
+    }
+}
@@ -3,13 +3,30 @@ use crate::rpc::headers::HeightTime;
 use crate::state::SharedState;
 use crate::{network, rpc};
 use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus::deserialize;
 use bitcoin::hashes::Hash;
-use bitcoin::BlockHash;
+use bitcoin::{BlockHash, Transaction};
+use clap::ValueEnum;
+use futures::prelude::*;
 use std::collections::HashMap;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 const HEADERS_PER_REQUEST: usize = 101;
 
+/// How the tx cache is filled at startup
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Warm {
+    /// The txs of the blocks, from the tip backward, until the cache is full
+    RecentBlocks,
+
+    /// The txs in the mempool and their previous txs, then like `recent-blocks`
+    MempoolFirst,
+
+    /// The cache is filled only by the requests
+    None,
+}
+
 pub(crate) async fn bootstrap_state_infallible(shared_state: Arc<SharedState>) {
     if let Err(e) = bootstrap_state(shared_state).await {
         log::error!("{:?}", e);
@@ -46,13 +63,25 @@ pub async fn bootstrap_state(shared_state: Arc<SharedState>) -> Result<(), Error
         .bootstrap_hash_to_height_time(hash_to_height_time)
         .await;
 
+    match shared_state.args.warm {
+        Warm::RecentBlocks => warm_recent_blocks(&shared_state).await?,
+        Warm::MempoolFirst => {
+            warm_mempool(&shared_state).await?;
+            warm_recent_blocks(&shared_state).await?;
+        }
+        Warm::None => log::info!("tx cache not warmed"),
+    }
+
+    log::info!("bootstrap ending, headers ending at {}", height);
+    shared_state.bootstrap_done.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+async fn warm_recent_blocks(shared_state: &SharedState) -> Result<(), Error> {
     let mut current = shared_state.chain_info.lock().await.best_block_hash;
     let mut count = 0;
     loop {
-        let block = rpc::block::call(current).await?;
-        current = block.header.prev_blockhash;
-        shared_state.update_cache(&block, None).await?;
-        count += 1;
         let cache = shared_state.txs.lock().await;
         if cache.full() {
             log::info!(
@@ -61,13 +90,41 @@ pub async fn bootstrap_state(shared_state: Arc<SharedState>) -> Result<(), Error
             );
             break;
         }
+        drop(cache);
         if current == BlockHash::all_zeros() {
             log::info!("reached genesis in bootstraping state, breaking");
             break;
         }
+        let block = rpc::block::call(current).await?;
+        current = block.header.prev_blockhash;
+        shared_state.update_cache(&block, None).await?;
+        count += 1;
     }
+    Ok(())
+}
 
-    log::info!("bootstrap ending, headers ending at {}", height);
+async fn warm_mempool(shared_state: &SharedState) -> Result<(), Error> {
+    let mempool = match rpc::mempool::content(true).await {
+        Ok(mempool) => mempool,
+        Err(_) => rpc::mempool::content(false).await?,
+    };
+    let mut txs = stream::iter(mempool)
+        .map(|txid| async move { (txid, shared_state.tx(txid, false).await) })
+        .buffer_unordered(shared_state.args.fetch_parallelism);
 
+    let mut count = 0;
+    while let Some((txid, result)) = txs.next().await {
+        let ser_tx = match result {
+            Ok((ser_tx, _)) => ser_tx,
+            Err(_) => continue, // confirmed or evicted in the meantime
+        };
+        let tx: Transaction = deserialize(ser_tx.as_ref())?;
+        shared_state.preload_prevouts(txid, &tx).await;
+        count += 1;
+        if shared_state.txs.lock().await.full() {
+            break;
+        }
+    }
+    log::info!("tx cache warmed with {count} mempool txs and their previous txs");
     Ok(())
 }
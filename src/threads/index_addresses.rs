@@ -7,15 +7,20 @@ use std::{
     sync::Arc,
 };
 
-use bitcoin::{hashes::Hash, Address, Block, BlockHash, OutPoint, Script, ScriptBuf, Txid};
+use bitcoin::{
+    blockdata::script::Instruction, hashes::Hash, Block, BlockHash, OutPoint, Script, Txid,
+};
 use bitcoin_slices::{bsl, Visit, Visitor};
+use futures::prelude::*;
 use fxhash::FxHasher64;
 use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, Options, WriteBatch, DB};
+use tokio::task::JoinHandle;
 
 use crate::{
     error::Error,
+    globals::{shutting_down, WriterGuard},
     rpc::{self, block::SerBlock, headers::HeightTime},
-    state::SharedState,
+    state::{tx_output, SharedState},
 };
 
 #[derive(Debug)]
@@ -25,11 +30,28 @@ type ScriptHash = u64;
 pub type Height = u32;
 
 fn script_hash(script: &Script) -> ScriptHash {
+    data_hash(script.as_bytes())
+}
+
+fn data_hash(data: &[u8]) -> u64 {
     let mut hasher = FxHasher64::default();
-    hasher.write(script.as_bytes());
+    hasher.write(data);
     hasher.finish()
 }
 
+/// The non-empty data pushes of an OP_RETURN script, nothing for other scripts
+fn op_return_pushes(script: &Script) -> impl Iterator<Item = &[u8]> {
+    script
+        .is_op_return()
+        .then(|| script.instructions())
+        .into_iter()
+        .flatten()
+        .filter_map(|i| match i {
+            Ok(Instruction::PushBytes(data)) if !data.is_empty() => Some(data.as_bytes()),
+            _ => None,
+        })
+}
+
 impl AsRef<[u8]> for ScriptHashHeight {
     fn as_ref(&self) -> &[u8] {
         &self.0[..]
@@ -39,12 +61,20 @@ impl AsRef<[u8]> for ScriptHashHeight {
 const BLOCK_HASH_CF: &str = "BLOCK_HASH_CF"; // BlockHash -> [] // indexed blocks
 const FUNDING_CF: &str = "FUNDING_CF"; // hash(Script) || height -> []
 const SPENDING_CF: &str = "SPENDING_CF"; // hash(prevout) || height -> []
+const OP_RETURN_CF: &str = "OP_RETURN_CF"; // hash(OP_RETURN push) || height -> []
 
-const COLUMN_FAMILIES: &[&str] = &[BLOCK_HASH_CF, FUNDING_CF, SPENDING_CF];
+const COLUMN_FAMILIES: &[&str] = &[BLOCK_HASH_CF, FUNDING_CF, SPENDING_CF, OP_RETURN_CF];
+
+/// Blocks fetched to find the spending txs of an address history, the fundings spent in other
+/// blocks are known to be spent but their spending tx is not looked up
+const SPENDING_HEIGHTS_LIMIT: usize = 10;
 
 #[derive(Debug)]
 pub struct Database {
     db: DB,
+
+    /// Whether the OP_RETURN pushes are indexed
+    index_op_return: bool,
 }
 
 impl Database {
@@ -55,14 +85,21 @@ impl Database {
             .collect()
     }
 
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, rocksdb::Error> {
+    pub fn new<P: AsRef<Path>>(path: P, index_op_return: bool) -> Result<Self, rocksdb::Error> {
         let mut db_opts = Options::default();
 
         db_opts.create_if_missing(true);
         db_opts.create_missing_column_families(true);
 
         let db = DB::open_cf_descriptors(&db_opts, path, Self::create_cf_descriptors())?;
-        Ok(Self { db })
+        Ok(Self {
+            db,
+            index_op_return,
+        })
+    }
+
+    pub fn index_op_return(&self) -> bool {
+        self.index_op_return
     }
 
     fn block_hash_cf(&self) -> &ColumnFamily {
@@ -79,6 +116,12 @@ impl Database {
         self.db.cf_handle(SPENDING_CF).expect("missing SPENDING_CF")
     }
 
+    fn op_return_cf(&self) -> &ColumnFamily {
+        self.db
+            .cf_handle(OP_RETURN_CF)
+            .expect("missing OP_RETURN_CF")
+    }
+
     pub fn indexed_block_hash(&self) -> HashSet<BlockHash> {
         let mut result = HashSet::new();
         for el in self
@@ -91,7 +134,7 @@ impl Database {
         result
     }
 
-    fn is_block_hash_indexed(&self, block_hash: &BlockHash) -> bool {
+    pub fn is_block_hash_indexed(&self, block_hash: &BlockHash) -> bool {
         self.db
             .get_pinned_cf(self.block_hash_cf(), block_hash)
             .unwrap()
@@ -99,23 +142,33 @@ impl Database {
     }
 
     pub fn script_hash_heights(&self, script_pubkey: &Script) -> Vec<Height> {
-        let script_hash = script_hash(script_pubkey).to_be_bytes();
-        let mut starting = script_hash.to_vec();
+        self.hash_heights(self.funding_cf(), script_hash(script_pubkey))
+    }
+
+    /// Heights of the blocks with an OP_RETURN output pushing `data`
+    pub fn op_return_heights(&self, data: &[u8]) -> Vec<Height> {
+        self.hash_heights(self.op_return_cf(), data_hash(data))
+    }
+
+    /// Most recent heights first, of the keys `hash || height` in `cf`, at most [`HEIGHTS_LIMIT`]
+    fn hash_heights(&self, cf: &ColumnFamily, hash: u64) -> Vec<Height> {
+        let hash = hash.to_be_bytes();
+        let mut starting = hash.to_vec();
         starting.extend(&[0xff; 4]);
         let mut result = vec![];
 
         for el in self.db.iterator_cf(
-            self.funding_cf(),
+            cf,
             rocksdb::IteratorMode::From(&starting[..], rocksdb::Direction::Reverse),
         ) {
             let el = el.unwrap().0;
-            if el.starts_with(&script_hash) {
+            if el.starts_with(&hash) {
                 let height = u32::from_be_bytes(el[8..].try_into().unwrap());
                 result.push(height);
             } else {
                 break;
             }
-            if result.len() > 9 {
+            if result.len() == HEIGHTS_LIMIT {
                 // TODO paging
                 break;
             }
@@ -144,9 +197,30 @@ impl Database {
         }
     }
 
-    pub fn _iter_spending(&self, _txid: Txid, _max: usize) -> Vec<Option<u32>> {
-        // TODO implement iteration on outputs so that is only one db access for all the output of a tx, however it's not trivial
-        todo!()
+    /// Spending height of the first `len` outputs of `txid`, in vout order.
+    ///
+    /// Keys of the outputs of the same tx are contiguous, so a single ranged iteration is needed.
+    pub fn iter_spending(&self, txid: Txid, len: usize) -> Vec<Option<Height>> {
+        let searched_key_start = outpoint_to_key_vec(&OutPoint::new(txid, 0));
+        let start = u64::from_be_bytes(searched_key_start[..].try_into().unwrap());
+        let mut result = vec![None; len];
+
+        for el in self.db.iterator_cf(
+            self.spending_cf(),
+            rocksdb::IteratorMode::From(&searched_key_start[..], rocksdb::Direction::Forward),
+        ) {
+            let key = el.unwrap().0;
+            let vout = (u64::from_be_bytes(key[..8].try_into().unwrap()) - start) as usize;
+            if vout >= len {
+                break;
+            }
+            // like `get_spending`, the lowest height wins
+            if result[vout].is_none() {
+                result[vout] = Some(u32::from_be_bytes((&key[8..]).try_into().unwrap()));
+            }
+        }
+
+        result
     }
 
     pub fn write_hashes(&self, index_res: IndexBlockResult) -> Result<(), Error> {
@@ -166,6 +240,14 @@ impl Database {
             buffer.extend(&height_bytes[..]);
             batch.put_cf(self.spending_cf(), &buffer, []);
         }
+        if self.index_op_return {
+            for data_hash in index_res.op_return_hashes {
+                buffer.clear();
+                buffer.extend(data_hash.to_be_bytes());
+                buffer.extend(&height_bytes[..]);
+                batch.put_cf(self.op_return_cf(), &buffer, []);
+            }
+        }
 
         batch.put_cf(self.block_hash_cf(), index_res.block_hash, []);
 
@@ -193,11 +275,26 @@ pub struct IndexBlockResult {
 
     funding_sh: BTreeSet<ScriptHash>,
     spending_sh: BTreeSet<OutPoint>,
+
+    /// Hashes of the OP_RETURN pushes, written only if the database indexes them
+    op_return_hashes: BTreeSet<u64>,
+}
+
+/// Maximum number of blocks returned for a script or an OP_RETURN data, the most recent ones,
+/// when reached the history is possibly truncated
+pub const HEIGHTS_LIMIT: usize = 10;
+
+/// Whether a history found in the blocks at `heights` may be truncated by [`HEIGHTS_LIMIT`]
+pub fn possibly_truncated(heights: impl Iterator<Item = Height>) -> bool {
+    heights.collect::<BTreeSet<_>>().len() >= HEIGHTS_LIMIT
 }
 
 #[derive(PartialEq, Eq, Debug)]
 pub struct AddressSeen {
     pub funding: Funding,
+
+    /// Height of the block spending the funding, known even if `spending` is not looked up
+    pub spent_height: Option<Height>,
     pub spending: Option<Spending>,
 }
 
@@ -206,16 +303,26 @@ pub struct Funding {
     pub out_point: OutPoint,
     pub block_hash: BlockHash,
     pub height_time: HeightTime,
+
+    /// Value of the funding output in satoshi
+    pub value: u64,
 }
 
 impl AddressSeen {
-    pub fn new(out_point: OutPoint, block_hash: BlockHash, height_time: HeightTime) -> Self {
+    pub fn new(
+        out_point: OutPoint,
+        block_hash: BlockHash,
+        height_time: HeightTime,
+        value: u64,
+    ) -> Self {
         Self {
             funding: Funding {
                 out_point,
                 block_hash,
                 height_time,
+                value,
             },
+            spent_height: None,
             spending: None,
         }
     }
@@ -230,36 +337,55 @@ pub struct Spending {
 }
 
 pub async fn address_seen(
-    address: &Address,
+    script_pubkey: &Script,
     db: Arc<Database>,
     shared_state: Arc<SharedState>,
 ) -> Result<Vec<AddressSeen>, Error> {
-    let script_pubkey = address.script_pubkey();
-    let heights = db.script_hash_heights(&script_pubkey);
+    let heights = db.script_hash_heights(script_pubkey);
     let blocks = shared_state.blocks_from_heights(&heights).await?;
     let mut outpoints_with_script_pubkey = vec![];
     for (h, b) in blocks {
         let t = shared_state.height_time(h).await.unwrap();
         outpoints_with_script_pubkey.extend(
-            find_outpoints_with_script_pubkey(&script_pubkey, b)
+            find_outpoints_with_script_pubkey(script_pubkey, b)
                 .into_iter()
                 .map(|e| (h, e, t)),
         );
     }
 
+    // every funding is checked, or it would be wrongly reported as unspent, but only the blocks of
+    // the most recent fundings are fetched to find the spending tx
+    let spent_heights: Vec<_> = outpoints_with_script_pubkey
+        .iter()
+        .map(|(_, outpoint, _)| db.get_spending(outpoint))
+        .collect();
     let mut heights_with_spending = vec![];
-    for (_, outpoint, _) in outpoints_with_script_pubkey.iter().take(10) {
-        //TODO handle pagination?
-        if let Some(h) = db.get_spending(outpoint) {
-            heights_with_spending.push(h);
+    for h in spent_heights.iter().flatten() {
+        if heights_with_spending.len() == SPENDING_HEIGHTS_LIMIT {
+            break;
+        }
+        if !heights_with_spending.contains(h) {
+            heights_with_spending.push(*h);
         }
     }
     let blocks = shared_state
         .blocks_from_heights(&heights_with_spending)
         .await?;
+
+    let values: Vec<_> = stream::iter(outpoints_with_script_pubkey.iter().map(|(_, o, _)| *o))
+        .map(|o| funding_value(&shared_state, o))
+        .buffered(shared_state.args.fetch_parallelism)
+        .try_collect()
+        .await?;
+
     let mut address_seen: Vec<_> = outpoints_with_script_pubkey
         .into_iter()
-        .map(|(h, o, t)| AddressSeen::new(o, h, t))
+        .zip(values)
+        .zip(spent_heights)
+        .map(|(((h, o, t), v), spent_height)| AddressSeen {
+            spent_height,
+            ..AddressSeen::new(o, h, t, v)
+        })
         .collect();
     for (h, b) in blocks {
         let t = shared_state.height_time(h).await.unwrap();
@@ -268,6 +394,12 @@ pub async fn address_seen(
 
     Ok(address_seen)
 }
+async fn funding_value(shared_state: &SharedState, out_point: OutPoint) -> Result<u64, Error> {
+    let (tx, _) = shared_state.tx(out_point.txid, false).await?;
+    let tx_out = tx_output(tx.as_ref(), out_point.vout, false).expect("invalid tx bytes");
+    Ok(tx_out.value.to_sat())
+}
+
 fn find_txids_with_prevout(
     h: BlockHash,
     b: SerBlock,
@@ -317,8 +449,74 @@ fn find_txids_with_prevout(
     bsl::Block::visit(&b.0, &mut visitor).unwrap();
 }
 
+/// An OP_RETURN output pushing the searched data
+#[derive(PartialEq, Eq, Debug)]
+pub struct OpReturnSeen {
+    pub out_point: OutPoint,
+    pub block_hash: BlockHash,
+    pub height_time: HeightTime,
+}
+
+/// The OP_RETURN outputs pushing `data`, most recent blocks first
+pub async fn op_return_seen(
+    data: &[u8],
+    db: Arc<Database>,
+    shared_state: Arc<SharedState>,
+) -> Result<Vec<OpReturnSeen>, Error> {
+    let heights = db.op_return_heights(data);
+    let blocks = shared_state.blocks_from_heights(&heights).await?;
+    let mut result = vec![];
+    for (h, b) in blocks {
+        let t = shared_state.height_time(h).await?;
+        // the db key is a hash, collisions are filtered out here
+        result.extend(
+            find_outpoints_pushing(data, &b)
+                .into_iter()
+                .map(|out_point| OpReturnSeen {
+                    out_point,
+                    block_hash: h,
+                    height_time: t,
+                }),
+        );
+    }
+    Ok(result)
+}
+
+/// The outputs of the transactions in block `b` with an OP_RETURN pushing `data`
+fn find_outpoints_pushing(data: &[u8], b: &SerBlock) -> Vec<OutPoint> {
+    struct TxPushingData<'a> {
+        outpoints: Vec<OutPoint>,
+        data: &'a [u8],
+        current_tx_matching_vouts: Vec<u32>,
+    }
+    impl<'a> Visitor for TxPushingData<'a> {
+        fn visit_tx_out(&mut self, vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<()> {
+            let script = Script::from_bytes(tx_out.script_pubkey());
+            if op_return_pushes(script).any(|push| push == self.data) {
+                self.current_tx_matching_vouts.push(vout as u32);
+            }
+            ControlFlow::Continue(())
+        }
+
+        fn visit_transaction(&mut self, tx: &bsl::Transaction) -> ControlFlow<()> {
+            let txid: Txid = tx.txid().into();
+            for vout in self.current_tx_matching_vouts.drain(..) {
+                self.outpoints.push(OutPoint { txid, vout });
+            }
+            ControlFlow::Continue(())
+        }
+    }
+    let mut visitor = TxPushingData {
+        outpoints: vec![],
+        data,
+        current_tx_matching_vouts: vec![],
+    };
+    bsl::Block::visit(&b.0, &mut visitor).unwrap(); // TODO
+    visitor.outpoints
+}
+
 /// Add txid to txids of transactions in block `b` containing `script_pubkey` in the outputs
-fn find_outpoints_with_script_pubkey(script_pubkey: &ScriptBuf, b: SerBlock) -> Vec<OutPoint> {
+fn find_outpoints_with_script_pubkey(script_pubkey: &Script, b: SerBlock) -> Vec<OutPoint> {
     struct TxContainingScript<'a> {
         outpoints: Vec<OutPoint>,
         script_pubkey: &'a [u8],
@@ -374,11 +572,20 @@ pub fn index_block(block: &Block, height: u32) -> Result<IndexBlockResult, crate
         .map(|i| i.previous_output)
         .collect();
 
+    let op_return_hashes: BTreeSet<u64> = block
+        .txdata
+        .iter()
+        .flat_map(|tx| tx.output.iter())
+        .flat_map(|txout| op_return_pushes(&txout.script_pubkey))
+        .map(data_hash)
+        .collect();
+
     Ok(IndexBlockResult {
         block_hash,
         height,
         funding_sh,
         spending_sh,
+        op_return_hashes,
     })
 }
 
@@ -426,10 +633,18 @@ impl Display for HitRate {
 async fn index_addresses(db: Arc<Database>, shared_state: Arc<SharedState>) -> Result<(), Error> {
     log::info!("Starting index_addresses");
 
+    let _writer = WriterGuard::new();
     let indexed_block_hash = db.indexed_block_hash();
     log::info!("already_indexed:{}", indexed_block_hash.len());
 
+    // writing a block overlaps with downloading the next one, but no more than one is pending
+    let mut pending_write: Option<JoinHandle<Result<(), Error>>> = None;
+
     for height in 0.. {
+        if shutting_down() {
+            log::info!("stopping initial block indexing for shutdown at height {height}");
+            break;
+        }
         let block_hash = match shared_state.height_to_hash(height).await {
             Some(hash) if hash != BlockHash::all_zeros() => hash,
             _ => {
@@ -447,22 +662,161 @@ async fn index_addresses(db: Arc<Database>, shared_state: Arc<SharedState>) -> R
 
         let block = loop {
             match rpc::block::call(block_hash).await {
-                Ok(block) => break block,
+                Ok(block) => break Some(block),
+                Err(_) if shutting_down() => break None,
                 Err(e) => {
                     log::warn!("Cannot download block: {block_hash} {e}");
                     tokio::time::sleep(tokio::time::Duration::from_secs(1)).await
                 }
             }
         };
+        let Some(block) = block else { break };
         let index_res = index_block(&block, height)?;
+        if let Some(pending) = pending_write.take() {
+            pending.await.expect("write task doesn't panic")?;
+        }
         let db = db.clone();
-        tokio::spawn(async move { db.write_hashes(index_res) });
+        // the write may outlive this function if it returns early with an error
+        let writer = WriterGuard::new();
+        pending_write = Some(tokio::task::spawn_blocking(move || {
+            let _writer = writer;
+            db.write_hashes(index_res)
+        }));
+    }
+    if let Some(pending) = pending_write {
+        pending.await.expect("write task doesn't panic")?;
     }
     Ok(())
 }
 
 #[cfg(test)]
 mod test {
+    use std::{collections::BTreeSet, str::FromStr};
+
+    use bitcoin::{
+        blockdata::constants::genesis_block, hashes::Hash, BlockHash, Network, OutPoint, ScriptBuf,
+        Transaction, TxIn, Txid,
+    };
+
+    use super::{
+        data_hash, index_block, op_return_pushes, possibly_truncated, Database, IndexBlockResult,
+        HEIGHTS_LIMIT,
+    };
+
+    #[test]
+    fn test_iter_spending() {
+        let path = std::env::temp_dir().join(format!("fbbe-test-{}", std::process::id()));
+        let db = Database::new(&path, false).unwrap();
+        let txid =
+            Txid::from_str("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+                .unwrap();
+        let spending = |vout, height| IndexBlockResult {
+            block_hash: BlockHash::all_zeros(),
+            height,
+            funding_sh: BTreeSet::new(),
+            spending_sh: [OutPoint::new(txid, vout)].into_iter().collect(),
+            op_return_hashes: BTreeSet::new(),
+        };
+        db.write_hashes(spending(0, 10)).unwrap();
+        db.write_hashes(spending(2, 12)).unwrap();
+
+        let result = db.iter_spending(txid, 4);
+        assert_eq!(result, vec![Some(10), None, Some(12), None]);
+        for (vout, height) in result.into_iter().enumerate() {
+            assert_eq!(db.get_spending(&OutPoint::new(txid, vout as u32)), height);
+        }
+
+        drop(db);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_index_block_spending() {
+        let path = std::env::temp_dir().join(format!("fbbe-test-block-{}", std::process::id()));
+        let db = Database::new(&path, false).unwrap();
+        let mut block = genesis_block(Network::Regtest);
+        let mut funding = block.txdata[0].clone();
+        funding.output = vec![funding.output[0].clone(); 3];
+        let funding_txid = funding.compute_txid();
+        let spending = |vouts: &[u32]| Transaction {
+            input: vouts
+                .iter()
+                .map(|vout| TxIn {
+                    previous_output: OutPoint::new(funding_txid, *vout),
+                    ..Default::default()
+                })
+                .collect(),
+            ..funding.clone()
+        };
+
+        block.txdata = vec![funding.clone()];
+        db.write_hashes(index_block(&block, 1).unwrap()).unwrap();
+        assert_eq!(db.iter_spending(funding_txid, 3), vec![None, None, None]);
+
+        block.txdata = vec![spending(&[2])];
+        db.write_hashes(index_block(&block, 2).unwrap()).unwrap();
+        block.txdata = vec![spending(&[0])];
+        db.write_hashes(index_block(&block, 3).unwrap()).unwrap();
+
+        let result = db.iter_spending(funding_txid, 3);
+        assert_eq!(result, vec![Some(3), None, Some(2)]);
+        for (vout, height) in result.into_iter().enumerate() {
+            assert_eq!(
+                db.get_spending(&OutPoint::new(funding_txid, vout as u32)),
+                height
+            );
+        }
+
+        drop(db);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_op_return_index() {
+        let path = std::env::temp_dir().join(format!("fbbe-test-op-{}", std::process::id()));
+        let db = Database::new(&path, true).unwrap();
+        let script = ScriptBuf::from_hex("6a0568656c6c6f03616263").unwrap();
+        let pushes: Vec<_> = op_return_pushes(&script).collect();
+        assert_eq!(pushes, vec![&b"hello"[..], &b"abc"[..]]);
+        assert_eq!(
+            op_return_pushes(&ScriptBuf::from_hex("0014").unwrap()).count(),
+            0
+        );
+
+        db.write_hashes(IndexBlockResult {
+            block_hash: BlockHash::all_zeros(),
+            height: 7,
+            funding_sh: BTreeSet::new(),
+            spending_sh: BTreeSet::new(),
+            op_return_hashes: pushes.into_iter().map(data_hash).collect(),
+        })
+        .unwrap();
+        assert_eq!(db.op_return_heights(b"hello"), vec![7]);
+        assert_eq!(db.op_return_heights(b"abc"), vec![7]);
+        assert!(db.op_return_heights(b"other").is_empty());
+        assert!(!possibly_truncated(
+            db.op_return_heights(b"hello").into_iter()
+        ));
+
+        for height in 10..22 {
+            db.write_hashes(IndexBlockResult {
+                block_hash: BlockHash::all_zeros(),
+                height,
+                funding_sh: BTreeSet::new(),
+                spending_sh: BTreeSet::new(),
+                op_return_hashes: [data_hash(b"hello")].into_iter().collect(),
+            })
+            .unwrap();
+        }
+        let heights = db.op_return_heights(b"hello");
+        assert_eq!(heights.len(), HEIGHTS_LIMIT);
+        assert_eq!(heights[0], 21);
+        assert!(possibly_truncated(heights.into_iter()));
+
+        drop(db);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
     #[test]
     fn test_endianness() {
         let value = 1u64;
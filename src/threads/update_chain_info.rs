@@ -1,13 +1,16 @@
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::error::Error;
-use crate::rpc;
+use crate::globals::{shutting_down, sleep_unless_shutdown, WriterGuard};
 use crate::rpc::chaininfo::ChainInfo;
-use crate::state::SharedState;
+use crate::rpc::headers::HeightTime;
+use crate::state::{Reorg, SharedState};
 use crate::threads::index_addresses::index_block;
+use crate::ws::Event;
+use crate::{network, rpc};
 use bitcoin::hashes::Hash;
 use bitcoin::BlockHash;
-use tokio::time::sleep;
 
 use super::index_addresses::Database;
 
@@ -28,11 +31,15 @@ async fn update_chain_info(
 ) -> Result<(), Error> {
     log::info!("Starting update_chain_info");
 
+    let _writer = db.as_ref().map(|_| WriterGuard::new());
     let mut current = initial_chain_info;
     loop {
         update_blocks_in_last_hour(&shared_state, current.blocks as usize).await;
 
-        sleep(tokio::time::Duration::from_secs(2)).await;
+        if !sleep_unless_shutdown(tokio::time::Duration::from_secs(2)).await {
+            log::info!("stopping update_chain_info for shutdown");
+            return Ok(());
+        }
 
         match rpc::chaininfo::call().await {
             Ok(last_tip) => {
@@ -42,8 +49,20 @@ async fn update_chain_info(
 
                     let mut last_height = last_tip.blocks;
                     let mut last_block_hash = last_tip.best_block_hash;
+                    let mut disconnected = 0;
+
+                    // the new chain may be shorter than the old one
+                    for height in (last_height + 1)..=current.blocks {
+                        if disconnect_block(&shared_state, height, None).await {
+                            disconnected += 1;
+                        }
+                    }
 
                     loop {
+                        if shutting_down() {
+                            log::info!("stopping update_chain_info for shutdown");
+                            return Ok(());
+                        }
                         log::info!("asking {last_block_hash}");
                         let last_block = match rpc::block::call(last_block_hash).await {
                             Ok(b) => b,
@@ -56,6 +75,11 @@ async fn update_chain_info(
                         };
                         let prev_blockhash = last_block.header.prev_blockhash;
 
+                        if disconnect_block(&shared_state, last_height, Some(last_block_hash)).await
+                        {
+                            disconnected += 1;
+                        }
+
                         shared_state
                             .update_cache(&last_block, Some(last_height))
                             .await?;
@@ -80,6 +104,14 @@ async fn update_chain_info(
                         }
                     }
 
+                    if disconnected > 0 {
+                        record_reorg(&shared_state, disconnected, last_height).await;
+                    }
+
+                    shared_state.publish(Event::Tip {
+                        height: last_tip.blocks,
+                        block_hash: last_tip.best_block_hash,
+                    });
                     current = last_tip.clone();
                     *shared_state.chain_info.lock().await = last_tip;
                 }
@@ -91,6 +123,40 @@ async fn update_chain_info(
     }
 }
 
+/// `depth` blocks were disconnected, the first one at `height`
+async fn record_reorg(shared_state: &SharedState, depth: u32, height: u32) {
+    log::warn!("reorg of {depth} blocks from height {height}");
+    crate::REORG_COUNTER
+        .with_label_values(&[&network().to_string(), &depth.to_string()])
+        .inc();
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as u32);
+    *shared_state.last_reorg.lock().await = Some(Reorg {
+        depth,
+        at: HeightTime { height, time },
+    });
+}
+
+/// If the block we know at `height` is not `new_block_hash`, it has been disconnected by a reorg:
+/// its txs must not be reported as confirmed in it anymore. Returns whether it was disconnected.
+async fn disconnect_block(
+    shared_state: &SharedState,
+    height: u32,
+    new_block_hash: Option<BlockHash>,
+) -> bool {
+    let old_block_hash = match shared_state.height_to_hash(height).await {
+        Some(hash) if hash != BlockHash::all_zeros() && Some(hash) != new_block_hash => hash,
+        _ => return false,
+    };
+    log::info!("block {old_block_hash} at height {height} disconnected");
+    match rpc::block::call(old_block_hash).await {
+        Ok(old_block) => shared_state.disconnect_block(&old_block).await,
+        Err(e) => log::warn!("Failed to ask disconnected block {old_block_hash} with {e:?}"),
+    }
+    true
+}
+
 async fn update_blocks_in_last_hour(shared_state: &Arc<SharedState>, last_tip_height: usize) {
     let mut data = Vec::with_capacity(6);
 
@@ -2,17 +2,18 @@ use std::collections::BTreeSet;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use crate::globals::{sleep_unless_shutdown, spawn};
 use crate::rpc;
 use crate::state::{outpoints_and_sum, tx_output, OutPointsAndSum, SharedState, SpendPoint};
+use crate::ws::Event;
 use bitcoin::{Txid, Weight};
 use fxhash::FxHashSet;
 use maud::{html, Render};
-use tokio::time::sleep;
 
 pub async fn update_mempool(shared_state: Arc<SharedState>) {
     {
         let shared_state = shared_state.clone();
-        tokio::spawn(async move {
+        spawn(async move {
             update_mempool_info(shared_state).await;
         });
     }
@@ -26,10 +27,21 @@ async fn update_mempool_info(shared_state: Arc<SharedState>) {
         if let Ok(mempool_info) = rpc::mempool::info().await {
             *shared_state.mempool_info.lock().await = mempool_info;
         }
-        sleep(tokio::time::Duration::from_secs(2)).await;
+        if !sleep_unless_shutdown(tokio::time::Duration::from_secs(2)).await {
+            log::info!("stopping update_mempool_info for shutdown");
+            return;
+        }
     }
 }
 
+/// Samples of the block template median fee rate kept for the trend shown in the home page
+const FEE_TREND_SAMPLES: usize = 24;
+
+const FEE_TREND_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Confirmation targets, in number of blocks, of the fee estimates shown in the home page
+pub const FEE_ESTIMATE_TARGETS: [u64; 3] = [1, 3, 6];
+
 #[derive(Debug, Clone)]
 pub struct WeightFee {
     /// The weight of the tx in vbytes
@@ -97,13 +109,47 @@ impl PartialEq for TxidWeightFeeCompact {
     }
 }
 
+/// Coarse classification of a fee rate, so that rates standing out are colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeTier {
+    /// Below 5 sat/vB
+    Low,
+    Medium,
+
+    /// From 50 sat/vB
+    High,
+}
+
+impl FeeTier {
+    pub fn new(sat_over_vb: f64) -> Self {
+        if sat_over_vb < 5.0 {
+            FeeTier::Low
+        } else if sat_over_vb < 50.0 {
+            FeeTier::Medium
+        } else {
+            FeeTier::High
+        }
+    }
+
+    fn class(&self) -> Option<&'static str> {
+        match self {
+            FeeTier::Low => Some("green"),
+            FeeTier::Medium => None,
+            FeeTier::High => Some("red"),
+        }
+    }
+}
+
 impl Render for WeightFee {
     fn render(&self) -> maud::Markup {
         // em data-tooltip=(rate_sat_vb) style="font-style: normal" { (rate_btc_kvb)
         let btc_over_kvb = format!("{:.8}", self.rate_btc_over_kvb());
         let sat_over_vb = self.sat_over_vb_str();
+        let tier = FeeTier::new(self.rate_sat_over_vb());
 
-        html! { span data-tooltip=(sat_over_vb) { (btc_over_kvb) } }
+        html! {
+            span data-tooltip=(sat_over_vb) class=[tier.class()] { (btc_over_kvb) }
+        }
     }
 }
 
@@ -149,7 +195,7 @@ impl WeightFee {
     }
 
     /// for example `180.0` (sat/vB)
-    fn rate_sat_over_vb(&self) -> f64 {
+    pub fn rate_sat_over_vb(&self) -> f64 {
         (self.fee as f64) / (self.weight.to_wu() as f64 / 4.0)
     }
 
@@ -180,6 +226,7 @@ async fn update_mempool_details(shared_state: Arc<SharedState>) {
     let mut rates_id: FxHashSet<Txid> = FxHashSet::default();
     let support_verbose = rpc::mempool::content(true).await.is_ok();
     log::info!("Node support compact mempool: {support_verbose}");
+    let mut last_fee_sample: Option<Instant> = None;
 
     loop {
         if let Ok(mempool) = rpc::mempool::content(support_verbose).await {
@@ -258,7 +305,7 @@ async fn update_mempool_details(shared_state: Arc<SharedState>) {
         }
 
         let mut sum = Weight::ZERO;
-        let max = Weight::from_wu(4_000_000); // TODO use bitcoin::Weight::MAX_BLOCK once 0.31 released
+        let max = Weight::MAX_BLOCK;
 
         // TODO this doesn't take into account txs dependency
         let block_template_last = rates
@@ -283,19 +330,87 @@ async fn update_mempool_details(shared_state: Arc<SharedState>) {
             mempool_fees.middle_in_block = rates.iter().nth_back(n / 2).map(Into::into);
             mempool_fees.transactions = Some(n + 1);
         }
+        mempool_fees.fee_estimates = fee_estimates(&rates, max);
+        mempool_fees.by_rate = Arc::new(rates.iter().rev().cloned().collect());
+        let sample_due = last_fee_sample.map_or(true, |t| t.elapsed() >= FEE_TREND_INTERVAL);
+        if let (true, Some(middle)) = (sample_due, mempool_fees.middle_in_block.as_ref()) {
+            let rate = middle.wf.rate_sat_over_vb();
+            if mempool_fees.fee_trend.len() == FEE_TREND_SAMPLES {
+                mempool_fees.fee_trend.pop_front();
+            }
+            mempool_fees.fee_trend.push_back(rate);
+            last_fee_sample = Some(Instant::now());
+        }
+        let event = Event::Fees {
+            transactions: mempool_fees.transactions,
+            highest: mempool_fees
+                .highest
+                .as_ref()
+                .map(|e| e.wf.rate_sat_over_vb()),
+            middle_in_block: mempool_fees
+                .middle_in_block
+                .as_ref()
+                .map(|e| e.wf.rate_sat_over_vb()),
+            last_in_block: mempool_fees
+                .last_in_block
+                .as_ref()
+                .map(|e| e.wf.rate_sat_over_vb()),
+        };
+        let section = mempool_fees.render();
         drop(mempool_fees);
-
-        sleep(tokio::time::Duration::from_secs(10)).await;
+        *shared_state.block_template_section.lock().await = section;
+        shared_state.publish(event);
 
         log::trace!("mempool tx with fee: {}", rates.len());
+
+        if !sleep_unless_shutdown(tokio::time::Duration::from_secs(10)).await {
+            log::info!("stopping update_mempool_details for shutdown");
+            return;
+        }
+    }
+}
+
+/// For every target in `FEE_ESTIMATE_TARGETS`, the rate of the tx filling that many blocks of
+/// `max_block_weight`, taking txs from the highest rate. `None` if the mempool doesn't fill them.
+// TODO this doesn't take into account txs dependency
+fn fee_estimates(
+    rates: &BTreeSet<TxidWeightFeeCompact>,
+    max_block_weight: Weight,
+) -> [Option<WeightFee>; FEE_ESTIMATE_TARGETS.len()] {
+    let mut result: [Option<WeightFee>; FEE_ESTIMATE_TARGETS.len()] = Default::default();
+    let mut targets = FEE_ESTIMATE_TARGETS
+        .iter()
+        .zip(result.iter_mut())
+        .peekable();
+    let mut sum = 0u64;
+    for e in rates.iter().rev() {
+        sum += e.wf.weight as u64;
+        while let Some((_, estimate)) =
+            targets.next_if(|(blocks, _)| sum >= **blocks * max_block_weight.to_wu())
+        {
+            *estimate = Some((&e.wf).into());
+        }
+        if targets.peek().is_none() {
+            break;
+        }
     }
+    result
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use bitcoin::hashes::Hash;
     use std::mem::size_of;
 
+    #[test]
+    fn test_fee_tier() {
+        assert_eq!(FeeTier::new(1.0), FeeTier::Low);
+        assert_eq!(FeeTier::new(5.0), FeeTier::Medium);
+        assert_eq!(FeeTier::new(49.9), FeeTier::Medium);
+        assert_eq!(FeeTier::new(50.0), FeeTier::High);
+    }
+
     #[test]
     fn size_weight_fee() {
         assert_eq!(size_of::<WeightFee>(), 16);
@@ -303,4 +418,21 @@ mod test {
         assert_eq!(size_of::<TxidWeightFee>(), 48);
         assert_eq!(size_of::<TxidWeightFeeCompact>(), 40);
     }
+
+    #[test]
+    fn test_fee_estimates() {
+        let rates: BTreeSet<_> = (1..=10u32)
+            .map(|i| TxidWeightFeeCompact {
+                wf: WeightFeeCompact {
+                    weight: 1_000,
+                    fee: i * 100,
+                },
+                txid: Txid::all_zeros(),
+            })
+            .collect();
+        let [next, three, six] = fee_estimates(&rates, Weight::from_wu(2_000));
+        assert_eq!(next.unwrap().fee, 900);
+        assert_eq!(three.unwrap().fee, 500);
+        assert!(six.is_none());
+    }
 }
@@ -5,7 +5,7 @@ use bitcoin::{hashes::Hash, Txid};
 use bitcoin_slices::{bsl, Parse};
 use futures::StreamExt;
 
-use crate::{state::SharedState, Error};
+use crate::{globals::shutdown_requested, state::SharedState, Error};
 
 pub async fn update_tx_zmq_infallible(socket: &SocketAddr, state: Arc<SharedState>) {
     if let Err(e) = update_tx_zmq(socket, state).await {
@@ -23,7 +23,15 @@ async fn update_tx_zmq(socket: &SocketAddr, state: Arc<SharedState>) -> Result<(
     sub.set_subscribe("rawtx")?;
     let mut count = 0u64;
 
-    while let Some(msg) = sub.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = sub.next() => msg,
+            _ = shutdown_requested() => {
+                log::info!("stopping update_tx_zmq for shutdown");
+                break;
+            }
+        };
+        let Some(msg) = msg else { break };
         let msg = msg.unwrap();
         // | "rawtx" | <serialized transaction> | <uint32 sequence number in Little Endian>
         if let Some(tx) = msg.get(1) {
@@ -36,7 +44,7 @@ async fn update_tx_zmq(socket: &SocketAddr, state: Arc<SharedState>) -> Result<(
                 // TODO load also prevouts?
 
                 let insert_result = state.txs.lock().await.insert(txid, tx.parsed());
-                log::trace!("inserting {} {}", txid, insert_result.is_ok());
+                log::trace!("inserting {} {}", txid, insert_result);
             }
         }
         if count % 10_000 == 0 {
@@ -0,0 +1,263 @@
+//! Minimal websocket server (RFC 6455) pushing [`Event`]s to connected clients.
+//!
+//! Only what's needed to push small text messages is implemented: client messages are read and
+//! discarded, pings are answered and a close frame ends the connection. Connections are closed
+//! when the process is stopping.
+
+use base64::Engine;
+use bitcoin::hashes::{sha1, Hash};
+use bitcoin::BlockHash;
+use hyper::header::{
+    CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE,
+};
+use hyper::upgrade::Upgraded;
+use hyper::{Body, Request, Response, StatusCode};
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, WriteHalf};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinSet;
+
+use crate::error::Error;
+use crate::globals::shutdown_requested;
+use crate::state::SharedState;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Client frames bigger than this close the connection, we don't expect clients to send data
+const MAX_CLIENT_PAYLOAD: u64 = 4096;
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Close status codes, sent as the payload of the close frame
+const CLOSE_GOING_AWAY: u16 = 1001;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+
+/// Messages pushed to websocket clients, serialized as json
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// The chain has a new tip
+    Tip { height: u32, block_hash: BlockHash },
+
+    /// Block template fees have been recomputed, rates are in sat/vB
+    Fees {
+        transactions: Option<usize>,
+        highest: Option<f64>,
+        middle_in_block: Option<f64>,
+        last_in_block: Option<f64>,
+    },
+}
+
+/// Answer the handshake and spawn the task serving the connection once upgraded
+pub fn upgrade(req: &mut Request<Body>, state: &SharedState) -> Result<Response<Body>, Error> {
+    let headers = req.headers();
+    let is_websocket = headers
+        .get(UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false);
+    let version_ok = headers
+        .get(SEC_WEBSOCKET_VERSION)
+        .map(|v| v.as_bytes() == b"13")
+        .unwrap_or(false);
+    let key = match headers.get(SEC_WEBSOCKET_KEY) {
+        Some(key) if is_websocket && version_ok => key.as_bytes(),
+        _ => return Err(Error::BadRequest),
+    };
+    let accept = accept_key(key);
+
+    let events = state.events.subscribe();
+    let on_upgrade = hyper::upgrade::on(req);
+    tokio::spawn(async move {
+        let upgraded = tokio::select! {
+            upgraded = on_upgrade => upgraded,
+            _ = shutdown_requested() => return,
+        };
+        match upgraded {
+            Ok(upgraded) => serve(upgraded, events).await,
+            Err(e) => log::warn!("websocket upgrade failed: {e:?}"),
+        }
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "upgrade")
+        .header(SEC_WEBSOCKET_ACCEPT, accept)
+        .body(Body::empty())?)
+}
+
+fn accept_key(key: &[u8]) -> String {
+    let mut data = key.to_vec();
+    data.extend(GUID.as_bytes());
+    let hash = sha1::Hash::hash(&data);
+    base64::engine::general_purpose::STANDARD.encode(hash.to_byte_array())
+}
+
+async fn serve(upgraded: Upgraded, mut events: broadcast::Receiver<String>) {
+    let (reader, mut writer) = tokio::io::split(upgraded);
+
+    // the reader task forwards the control frames the writer must answer to, it's aborted when
+    // the set is dropped
+    let (control_tx, mut control_rx) = mpsc::channel(4);
+    let mut tasks = JoinSet::new();
+    tasks.spawn(read_frames(reader, control_tx));
+
+    loop {
+        let res = tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => send(&mut writer, OPCODE_TEXT, event.as_bytes()).await,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    log::debug!("websocket client lagged {n} events");
+                    Ok(())
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            control = control_rx.recv() => match control {
+                Some((OPCODE_PING, payload)) => send(&mut writer, OPCODE_PONG, &payload).await,
+                Some((opcode, payload)) => {
+                    let _ = send(&mut writer, opcode, &payload).await;
+                    break;
+                }
+                None => break,
+            },
+            _ = shutdown_requested() => {
+                let _ = send(&mut writer, OPCODE_CLOSE, &CLOSE_GOING_AWAY.to_be_bytes()).await;
+                break;
+            }
+        };
+        if let Err(e) = res {
+            log::debug!("websocket write failed: {e:?}");
+            break;
+        }
+    }
+}
+
+/// Read client frames until close or error, pings and close are forwarded to the writer
+async fn read_frames<R: AsyncRead + Unpin>(mut reader: R, control: mpsc::Sender<(u8, Vec<u8>)>) {
+    loop {
+        let (opcode, payload) = match read_frame(&mut reader).await {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::debug!("websocket read failed: {e:?}");
+                if e.kind() == std::io::ErrorKind::InvalidData {
+                    let payload = CLOSE_PROTOCOL_ERROR.to_be_bytes().to_vec();
+                    let _ = control.send((OPCODE_CLOSE, payload)).await;
+                }
+                return;
+            }
+        };
+        match opcode {
+            OPCODE_PING => {
+                if control.send((OPCODE_PING, payload)).await.is_err() {
+                    return;
+                }
+            }
+            OPCODE_CLOSE => {
+                let _ = control.send((OPCODE_CLOSE, payload)).await;
+                return;
+            }
+            _ => (),
+        }
+    }
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header).await?;
+    let opcode = header[0] & 0x0F;
+    // clients must mask every frame
+    if header[1] & 0x80 == 0 {
+        return Err(std::io::ErrorKind::InvalidData.into());
+    }
+    let len = match header[1] & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+    if len > MAX_CLIENT_PAYLOAD {
+        return Err(std::io::ErrorKind::InvalidData.into());
+    }
+    let mut mask = [0u8; 4];
+    reader.read_exact(&mut mask).await?;
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).await?;
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+    Ok((opcode, payload))
+}
+
+async fn send(writer: &mut WriteHalf<Upgraded>, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&frame(opcode, payload)).await?;
+    writer.flush().await
+}
+
+/// An unmasked, unfragmented server frame
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(payload.len() + 10);
+    result.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => result.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            result.push(126);
+            result.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            result.push(127);
+            result.extend((len as u64).to_be_bytes());
+        }
+    }
+    result.extend(payload);
+    result
+}
+
+impl SharedState {
+    /// Push `event` to every connected websocket client
+    pub fn publish(&self, event: Event) {
+        match serde_json::to_string(&event) {
+            Ok(s) => {
+                // an error means there are no clients connected
+                let _ = self.events.send(s);
+            }
+            Err(e) => log::warn!("cannot serialize {event:?}: {e:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{accept_key, frame, read_frame};
+
+    #[test]
+    fn test_accept_key() {
+        // example from RFC 6455
+        assert_eq!(
+            accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaGJ0ZHwc7AQQo+Gzo="
+        );
+    }
+
+    #[test]
+    fn test_frame() {
+        assert_eq!(frame(0x1, b"Hello"), b"\x81\x05Hello");
+        let long = frame(0x1, &[0u8; 256]);
+        assert_eq!(&long[..4], &[0x81, 126, 1, 0]);
+        assert_eq!(long.len(), 260);
+    }
+
+    #[tokio::test]
+    async fn test_read_frame() {
+        // masked "Hello" from RFC 6455
+        let mut masked = &b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58"[..];
+        let (opcode, payload) = read_frame(&mut masked).await.unwrap();
+        assert_eq!((opcode, &payload[..]), (0x1, &b"Hello"[..]));
+
+        let mut unmasked = &frame(0x1, b"Hello")[..];
+        let err = read_frame(&mut unmasked).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,157 @@
+use std::{fmt::Display, str::FromStr};
+
+use bitcoin::{
+    base58,
+    bip32::{self, ChildNumber, Xpub},
+    secp256k1::{Secp256k1, VerifyOnly},
+    Address, Network, NetworkKind,
+};
+use once_cell::sync::Lazy;
+
+use crate::network;
+
+static SECP: Lazy<Secp256k1<VerifyOnly>> = Lazy::new(Secp256k1::verification_only);
+
+/// Addresses derived for every chain, receive and change
+pub const DERIVED_PER_CHAIN: u32 = 20;
+
+/// The script type of the derived addresses, implied by the SLIP-132 version bytes of the key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptKind {
+    /// xpub/tpub
+    P2pkh,
+
+    /// ypub/upub
+    P2shP2wpkh,
+
+    /// zpub/vpub
+    P2wpkh,
+}
+
+const VERSIONS: [([u8; 4], NetworkKind, ScriptKind); 6] = [
+    (
+        [0x04, 0x88, 0xb2, 0x1e],
+        NetworkKind::Main,
+        ScriptKind::P2pkh,
+    ),
+    (
+        [0x04, 0x9d, 0x7c, 0xb2],
+        NetworkKind::Main,
+        ScriptKind::P2shP2wpkh,
+    ),
+    (
+        [0x04, 0xb2, 0x47, 0x46],
+        NetworkKind::Main,
+        ScriptKind::P2wpkh,
+    ),
+    (
+        [0x04, 0x35, 0x87, 0xcf],
+        NetworkKind::Test,
+        ScriptKind::P2pkh,
+    ),
+    (
+        [0x04, 0x4a, 0x52, 0x62],
+        NetworkKind::Test,
+        ScriptKind::P2shP2wpkh,
+    ),
+    (
+        [0x04, 0x5f, 0x1c, 0xf6],
+        NetworkKind::Test,
+        ScriptKind::P2wpkh,
+    ),
+];
+
+/// An extended public key, in the xpub, ypub or zpub form (or the testnet equivalents)
+#[derive(Debug, Clone)]
+pub struct ExtendedKey {
+    pub xpub: Xpub,
+    pub kind: ScriptKind,
+
+    /// The key as given, with the original version bytes
+    pub original: String,
+}
+
+impl FromStr for ExtendedKey {
+    type Err = bip32::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut data = base58::decode_check(s)?;
+        if data.len() != 78 {
+            return Err(bip32::Error::WrongExtendedKeyLength(data.len()));
+        }
+        let (_, network, kind) = VERSIONS
+            .iter()
+            .find(|(version, _, _)| data.starts_with(version))
+            .ok_or_else(|| bip32::Error::UnknownVersion([data[0], data[1], data[2], data[3]]))?;
+
+        // `Xpub::decode` knows only the xpub/tpub version bytes
+        let plain_version = VERSIONS
+            .iter()
+            .find(|(_, n, k)| n == network && *k == ScriptKind::P2pkh)
+            .map(|(version, _, _)| version)
+            .expect("every network has the P2pkh version");
+        data[..4].copy_from_slice(plain_version);
+
+        Ok(ExtendedKey {
+            xpub: Xpub::decode(&data)?,
+            kind: *kind,
+            original: s.to_string(),
+        })
+    }
+}
+
+impl Display for ExtendedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.original)
+    }
+}
+
+impl ExtendedKey {
+    /// Whether the key is meant for the network of this instance
+    pub fn is_current_network(&self) -> bool {
+        self.xpub.network == NetworkKind::from(network())
+    }
+
+    /// The address at `chain/index`, where chain is 0 for receive and 1 for change addresses
+    pub fn derive(
+        &self,
+        chain: u32,
+        index: u32,
+        network: Network,
+    ) -> Result<Address, bip32::Error> {
+        let path = [
+            ChildNumber::from_normal_idx(chain)?,
+            ChildNumber::from_normal_idx(index)?,
+        ];
+        let pk = self.xpub.derive_pub(&SECP, &path)?.to_pub();
+        Ok(match self.kind {
+            ScriptKind::P2pkh => Address::p2pkh(pk, network),
+            ScriptKind::P2shP2wpkh => Address::p2shwpkh(&pk, network),
+            ScriptKind::P2wpkh => Address::p2wpkh(&pk, network),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bitcoin::Network;
+
+    use super::{ExtendedKey, ScriptKind};
+
+    #[test]
+    fn test_derive() {
+        // BIP84 test vector, account 0
+        let key: ExtendedKey = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs".parse().unwrap();
+        assert_eq!(key.kind, ScriptKind::P2wpkh);
+        assert_eq!(
+            key.derive(0, 0, Network::Bitcoin).unwrap().to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            key.derive(1, 0, Network::Bitcoin).unwrap().to_string(),
+            "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+        );
+
+        assert!("xpub".parse::<ExtendedKey>().is_err());
+    }
+}
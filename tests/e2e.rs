@@ -117,6 +117,12 @@ fn check_pages() {
     let page = get(tx_page);
     assert!(page.contains(genesis_block));
     assert!(page.contains(genesis_tx));
+
+    let tx_json = format!("http://{fbbe_addr}/t/{genesis_tx}/json");
+    let json: serde_json::Value = serde_json::from_str(&get(tx_json)).unwrap();
+    assert_eq!(json["txid"], genesis_tx);
+    assert_eq!(json["status"]["block_hash"], genesis_block);
+    assert_eq!(json["inputs"][0]["prevout"]["type"], "coinbase");
 }
 #[test]
 fn check_wrong_network() {